@@ -0,0 +1,132 @@
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use payments::bank::Bank;
+use payments::generator::{Generator, TypeMix, WorkloadConfig};
+use payments::ingest::{add_records_to_bank, trimmed_csv_reader};
+use payments::output::output_bank;
+use payments::transaction::Transaction;
+use std::io;
+
+const TRANSACTIONS: u32 = 100_000;
+
+fn workload(config: WorkloadConfig) -> Vec<u8> {
+    let mut input = Vec::new();
+    Generator::new(config).write(&mut input).unwrap();
+    input
+}
+
+fn transactions(input: &[u8]) -> Vec<Transaction> {
+    trimmed_csv_reader(input)
+        .deserialize()
+        .filter_map(Result::ok)
+        .collect()
+}
+
+fn transact_all(transactions: Vec<Transaction>) -> Bank {
+    let mut bank = Bank::new();
+    for transaction in transactions {
+        let _ = bank.transact(transaction);
+    }
+    bank
+}
+
+fn deserialize(c: &mut Criterion) {
+    let input = workload(WorkloadConfig {
+        transactions: TRANSACTIONS,
+        ..WorkloadConfig::default()
+    });
+
+    let mut group = c.benchmark_group("deserialize");
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    group.bench_function("transaction", |b| {
+        b.iter(|| {
+            trimmed_csv_reader(&input[..])
+                .deserialize::<Transaction>()
+                .count()
+        })
+    });
+    group.finish();
+}
+
+fn transact(c: &mut Criterion) {
+    let mut group = c.benchmark_group("transact");
+    group.throughput(Throughput::Elements(TRANSACTIONS as u64));
+    for clients in [10, 1_000, u16::MAX] {
+        let transactions = transactions(&workload(WorkloadConfig {
+            clients,
+            transactions: TRANSACTIONS,
+            ..WorkloadConfig::default()
+        }));
+
+        group.bench_with_input(
+            BenchmarkId::new("clients", clients),
+            &transactions,
+            |b, transactions| {
+                b.iter_batched(|| transactions.clone(), transact_all, BatchSize::LargeInput)
+            },
+        );
+    }
+    group.finish();
+}
+
+fn dispute_heavy(c: &mut Criterion) {
+    let transactions = transactions(&workload(WorkloadConfig {
+        clients: 1_000,
+        transactions: TRANSACTIONS,
+        mix: TypeMix {
+            deposit: 40,
+            withdrawal: 10,
+            dispute: 25,
+        },
+        chargeback_ratio: 0.01,
+        ..WorkloadConfig::default()
+    }));
+
+    let mut group = c.benchmark_group("transact");
+    group.throughput(Throughput::Elements(TRANSACTIONS as u64));
+    group.bench_function("dispute_heavy", |b| {
+        b.iter_batched(|| transactions.clone(), transact_all, BatchSize::LargeInput)
+    });
+    group.finish();
+}
+
+fn ingest(c: &mut Criterion) {
+    let input = workload(WorkloadConfig {
+        transactions: TRANSACTIONS,
+        malformed_rate: 0.01,
+        ..WorkloadConfig::default()
+    });
+
+    let mut group = c.benchmark_group("ingest");
+    group.throughput(Throughput::Elements(TRANSACTIONS as u64));
+    group.bench_function("add_records_to_bank", |b| {
+        b.iter(|| add_records_to_bank(Bank::new(), trimmed_csv_reader(&input[..])).unwrap())
+    });
+    group.finish();
+}
+
+fn output(c: &mut Criterion) {
+    let mut group = c.benchmark_group("output_bank");
+    for clients in [10, 1_000, u16::MAX] {
+        let bank = transact_all(transactions(&workload(WorkloadConfig {
+            clients,
+            transactions: TRANSACTIONS,
+            ..WorkloadConfig::default()
+        })));
+
+        group.throughput(Throughput::Elements(bank.accounts().unwrap().len() as u64));
+        group.bench_with_input(BenchmarkId::new("clients", clients), &bank, |b, bank| {
+            b.iter(|| output_bank(bank, io::sink()).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    deserialize,
+    transact,
+    dispute_heavy,
+    ingest,
+    output
+);
+criterion_main!(benches);
@@ -0,0 +1,57 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use payments::bank::Bank;
+use payments::id::ClientId;
+use payments::ingest::trimmed_csv_reader;
+use payments::transaction::Transaction;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+
+type Balances = (Decimal, Decimal, Decimal, bool);
+
+/*
+ * Every account, as a row may change more than its own client's, e.g. the destination of a
+ * transfer or the payee of a standing order
+ */
+fn balances(bank: &Bank) -> HashMap<ClientId, Balances> {
+    bank.accounts()
+        .unwrap()
+        .iter()
+        .map(|account| {
+            (
+                account.client_id(),
+                (
+                    *account.available(),
+                    *account.held(),
+                    *account.total(),
+                    account.locked(),
+                ),
+            )
+        })
+        .collect()
+}
+
+fuzz_target!(|data: &[u8]| {
+    let mut bank = Bank::new();
+    let mut reader = trimmed_csv_reader(data);
+
+    for transaction in reader.deserialize::<Transaction>() {
+        let transaction = match transaction {
+            Ok(t) => t,
+            Err(_) => continue,
+        };
+        let before = balances(&bank);
+
+        let _ = bank.transact(transaction);
+
+        for (client_id, after) in balances(&bank) {
+            let (available, held, total, locked) = after;
+            assert_eq!(total, available + held);
+            assert!(held >= Decimal::ZERO);
+            if let Some(before) = before.get(&client_id).filter(|before| before.3) {
+                assert!(locked);
+                assert_eq!(before, &after);
+            }
+        }
+    }
+});
@@ -0,0 +1,33 @@
+CREATE SEQUENCE positions MINVALUE 0 START WITH 0;
+
+CREATE TABLE accounts (
+    client INTEGER PRIMARY KEY,
+    available NUMERIC NOT NULL,
+    held NUMERIC NOT NULL,
+    total NUMERIC NOT NULL,
+    locked BOOLEAN NOT NULL
+);
+
+CREATE TABLE transaction_clients (
+    tx BIGINT PRIMARY KEY,
+    client INTEGER NOT NULL
+);
+
+CREATE TABLE transactions (
+    tx BIGINT PRIMARY KEY,
+    client INTEGER NOT NULL REFERENCES accounts (client),
+    type TEXT NOT NULL,
+    amount NUMERIC NOT NULL,
+    disputed BOOLEAN NOT NULL
+);
+
+CREATE INDEX transactions_client ON transactions (client);
+
+CREATE TABLE disputes (
+    tx BIGINT PRIMARY KEY REFERENCES transactions (tx),
+    client INTEGER NOT NULL REFERENCES accounts (client),
+    opened BIGINT NOT NULL,
+    expired BOOLEAN NOT NULL
+);
+
+CREATE INDEX disputes_expiry ON disputes (expired, opened);
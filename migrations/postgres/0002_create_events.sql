@@ -0,0 +1,11 @@
+CREATE TABLE events (
+    id BIGSERIAL PRIMARY KEY,
+    sequence BIGINT NOT NULL,
+    timestamp BIGINT NOT NULL,
+    client INTEGER NOT NULL REFERENCES accounts (client),
+    kind TEXT NOT NULL,
+    tx BIGINT,
+    amount NUMERIC
+);
+
+CREATE INDEX events_client ON events (client, id);
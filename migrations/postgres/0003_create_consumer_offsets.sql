@@ -0,0 +1,6 @@
+CREATE TABLE consumer_offsets (
+    topic TEXT NOT NULL,
+    partition_id BIGINT NOT NULL,
+    next_offset BIGINT NOT NULL,
+    PRIMARY KEY (topic, partition_id)
+);
@@ -0,0 +1,10 @@
+ALTER TABLE accounts ADD COLUMN authorized NUMERIC NOT NULL DEFAULT 0;
+
+CREATE TABLE authorizations (
+    tx BIGINT PRIMARY KEY,
+    client INTEGER NOT NULL REFERENCES accounts (client),
+    opened BIGINT NOT NULL,
+    expired BOOLEAN NOT NULL
+);
+
+CREATE INDEX authorizations_expiry ON authorizations (expired, opened);
@@ -0,0 +1,8 @@
+ALTER TABLE accounts ADD COLUMN fees NUMERIC NOT NULL DEFAULT 0;
+
+CREATE TABLE revenue (
+    id INTEGER PRIMARY KEY CHECK (id = 0),
+    amount NUMERIC NOT NULL
+);
+
+INSERT INTO revenue (id, amount) VALUES (0, 0);
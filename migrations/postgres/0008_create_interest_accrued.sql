@@ -0,0 +1,4 @@
+CREATE TABLE interest_accrued (
+    client INTEGER PRIMARY KEY,
+    date TEXT NOT NULL
+);
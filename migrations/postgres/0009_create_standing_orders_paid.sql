@@ -0,0 +1,6 @@
+CREATE TABLE standing_orders_paid (
+    order_index BIGINT PRIMARY KEY,
+    due BIGINT NOT NULL
+);
+
+CREATE SEQUENCE bank_transactions MINVALUE 0 START WITH 0;
@@ -1,3 +1,6 @@
+use crate::event::AccountEvent;
+use crate::fee::Fee;
+use crate::id::{ClientId, TransactionId};
 use crate::transaction::transaction_error::TransactionError;
 use crate::transaction::transaction_lite::TransactionLite;
 use crate::transaction::transaction_type::TransactionType;
@@ -5,10 +8,21 @@ use crate::transaction::Transaction;
 use ahash::AHashMap;
 use rust_decimal::Decimal;
 use serde::Serialize;
-use std::collections::hash_map::Entry;
 
 pub type TransactionResult<T> = Result<T, TransactionError>;
 
+fn checked_add(value: Decimal, amount: Decimal) -> TransactionResult<Decimal> {
+    value
+        .checked_add(amount)
+        .ok_or_else(TransactionError::overflow)
+}
+
+fn checked_sub(value: Decimal, amount: Decimal) -> TransactionResult<Decimal> {
+    value
+        .checked_sub(amount)
+        .ok_or_else(TransactionError::overflow)
+}
+
 pub fn decimal_normalize_serialize<S>(value: &Decimal, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: serde::Serializer,
@@ -16,12 +30,21 @@ where
     rust_decimal::serde::str::serialize(&value.normalize(), serializer)
 }
 
-#[derive(Serialize, Debug)]
+/* Amounts only ever move funds the way their transaction does, so they must be positive */
+fn positive(amount: Decimal) -> TransactionResult<Decimal> {
+    if amount <= Decimal::ZERO {
+        return Err(TransactionError::non_positive_amount());
+    }
+
+    Ok(amount)
+}
+
+#[derive(Serialize, Clone, Debug)]
 pub struct Account {
     #[serde(rename = "client")]
-    client_id: u16,
+    client_id: ClientId,
     #[serde(skip)]
-    transactions: AHashMap<u32, TransactionLite>,
+    transactions: AHashMap<TransactionId, TransactionLite>,
     #[serde(serialize_with = "decimal_normalize_serialize")]
     available: Decimal,
     #[serde(serialize_with = "decimal_normalize_serialize")]
@@ -29,10 +52,25 @@ pub struct Account {
     #[serde(serialize_with = "decimal_normalize_serialize")]
     total: Decimal,
     locked: bool,
+    /* The part of held that is reserved by authorizations rather than disputes */
+    #[serde(serialize_with = "decimal_normalize_serialize")]
+    authorized: Decimal,
+    /* Fees charged to the account, already taken out of available and total */
+    #[serde(serialize_with = "decimal_normalize_serialize")]
+    fees: Decimal,
+    /* How far below zero withdrawals and the like may take available */
+    #[serde(skip)]
+    overdraft_limit: Decimal,
+    /* The part of a negative available balance within the overdraft limit */
+    #[serde(serialize_with = "decimal_normalize_serialize")]
+    overdraft: Decimal,
+    /* The part of a negative available balance beyond the limit, only disputes and fees reach it */
+    #[serde(serialize_with = "decimal_normalize_serialize")]
+    debt: Decimal,
 }
 
 impl Account {
-    pub fn new(client_id: u16) -> Self {
+    pub fn new(client_id: ClientId) -> Self {
         Self {
             client_id,
             transactions: AHashMap::new(),
@@ -40,10 +78,153 @@ impl Account {
             held: Decimal::new(0, 4),
             total: Decimal::new(0, 4),
             locked: false,
+            authorized: Decimal::new(0, 4),
+            fees: Decimal::new(0, 4),
+            overdraft_limit: Decimal::new(0, 4),
+            overdraft: Decimal::new(0, 4),
+            debt: Decimal::new(0, 4),
+        }
+    }
+
+    pub fn from_balances(
+        client_id: ClientId,
+        available: Decimal,
+        held: Decimal,
+        total: Decimal,
+        locked: bool,
+        authorized: Decimal,
+        fees: Decimal,
+    ) -> Self {
+        let mut account = Self {
+            client_id,
+            transactions: AHashMap::new(),
+            available,
+            held,
+            total,
+            locked,
+            authorized,
+            fees,
+            overdraft_limit: Decimal::new(0, 4),
+            overdraft: Decimal::new(0, 4),
+            debt: Decimal::new(0, 4),
+        };
+        account.update_overdraft();
+
+        account
+    }
+
+    /* Set by the bank from its overdraft limits, and by storage backends when loading accounts */
+    pub fn set_overdraft_limit(&mut self, limit: Decimal) {
+        self.overdraft_limit = limit;
+        self.update_overdraft();
+    }
+
+    fn update_overdraft(&mut self) {
+        let negative = (-self.available).max(Decimal::ZERO);
+        self.overdraft = negative.min(self.overdraft_limit);
+        self.debt = negative - self.overdraft;
+    }
+
+    /* What withdrawals, authorizations, transfers, refunds and fees may take out of available */
+    fn spendable(&self) -> Decimal {
+        self.available.saturating_add(self.overdraft_limit)
+    }
+
+    /*
+     * Used by storage backends to load previously applied transactions into the account, this
+     * bypasses all balance changes
+     */
+    pub fn restore_transaction(&mut self, id: TransactionId, transaction: TransactionLite) {
+        self.transactions.insert(id, transaction);
+    }
+
+    /*
+     * Validates the transaction against the current state and applies the resulting events, which
+     * are returned so they can be recorded, the account is unchanged on error. Only the source
+     * leg of a transfer is applied here, see counterparty
+     */
+    pub fn transact(&mut self, transaction: Transaction) -> TransactionResult<Vec<AccountEvent>> {
+        let events = self.decide(&transaction)?;
+        self.settle(&transaction, &events)?;
+
+        Ok(events)
+    }
+
+    /* Applies the events decided for the transaction, which are checked before any is applied */
+    pub fn settle(
+        &mut self,
+        transaction: &Transaction,
+        events: &[AccountEvent],
+    ) -> TransactionResult<()> {
+        self.balances().apply_all(events)?;
+        self.apply_all(events)?;
+
+        if transaction.t_type == TransactionType::Transfer {
+            if let Some(transfer) = self.transactions.get_mut(&transaction.id) {
+                transfer.counterparty = transaction.to;
+            }
+        }
+
+        Ok(())
+    }
+
+    /*
+     * The fee for the transaction given the events decided for it, charged on the amount of the
+     * first of them, it must be covered by the available balance left by the transaction except
+     * for chargebacks, whose fees may leave it negative
+     */
+    pub fn decide_fee(
+        &self,
+        transaction: &Transaction,
+        events: &[AccountEvent],
+        fee: &Fee,
+    ) -> TransactionResult<Option<AccountEvent>> {
+        let amount = match events.first().and_then(AccountEvent::amount) {
+            Some(amount) => fee.charge(amount)?,
+            None => return Ok(None),
+        };
+        if amount <= Decimal::ZERO {
+            return Ok(None);
+        }
+
+        let mut balances = self.balances();
+        balances.apply_all(events)?;
+        if transaction.t_type != TransactionType::Chargeback && amount > balances.spendable() {
+            return Err(TransactionError::negative_balance());
         }
+
+        Ok(Some(AccountEvent::FeeCharged {
+            id: transaction.id,
+            amount,
+        }))
     }
 
-    pub fn transact(&mut self, transaction: Transaction) -> TransactionResult<()> {
+    /* A copy of the balances without the transactions, to check events against or hand to listeners */
+    pub fn balances(&self) -> Account {
+        let mut balances = Account::from_balances(
+            self.client_id,
+            self.available,
+            self.held,
+            self.total,
+            self.locked,
+            self.authorized,
+            self.fees,
+        );
+        balances.set_overdraft_limit(self.overdraft_limit);
+
+        balances
+    }
+
+    fn apply_all(&mut self, events: &[AccountEvent]) -> TransactionResult<()> {
+        for event in events {
+            self.apply(event)?;
+        }
+
+        Ok(())
+    }
+
+    /* The events the transaction would produce, without applying them */
+    pub fn decide(&self, transaction: &Transaction) -> TransactionResult<Vec<AccountEvent>> {
         if transaction.client_id != self.client_id {
             return Err(TransactionError::incorrect_client());
         }
@@ -52,110 +233,444 @@ impl Account {
             return Err(TransactionError::locked());
         }
 
-        match transaction.t_type {
-            TransactionType::Deposit => self.deposit(transaction)?,
-            TransactionType::Withdrawal => self.withdrawal(transaction)?,
-            TransactionType::Dispute => self.dispute(transaction)?,
-            TransactionType::Resolve => self.resolve(transaction)?,
+        Ok(match transaction.t_type {
+            TransactionType::Deposit => vec![self.deposit(transaction)?],
+            TransactionType::Withdrawal => vec![self.withdrawal(transaction)?],
+            TransactionType::Dispute => vec![self.dispute(transaction)?],
+            TransactionType::Resolve => vec![self.resolve(transaction)?],
             TransactionType::Chargeback => self.chargeback(transaction)?,
-        }
+            TransactionType::Authorize => vec![self.authorize(transaction)?],
+            TransactionType::Capture => vec![self.capture(transaction)?],
+            TransactionType::Void => vec![self.void(transaction)?],
+            TransactionType::Transfer => vec![self.transfer(transaction)?],
+            TransactionType::Refund => vec![self.refund(transaction)?],
+            TransactionType::Interest => vec![self.interest(transaction)?],
+        })
+    }
 
-        Ok(())
+    /*
+     * The other account a transaction applies to, the destination of a transfer, which is also
+     * where the funds are held while the transfer is disputed
+     */
+    pub fn counterparty(&self, transaction: &Transaction) -> Option<ClientId> {
+        match transaction.t_type {
+            TransactionType::Transfer => transaction.to,
+            TransactionType::Dispute | TransactionType::Resolve | TransactionType::Chargeback => {
+                self.transactions
+                    .get(&transaction.id)
+                    .and_then(|transfer| transfer.counterparty)
+            }
+            _ => None,
+        }
     }
 
-    fn add_to_transactions(
-        &mut self,
-        transaction: &Transaction,
-    ) -> TransactionResult<&mut TransactionLite> {
-        return match self.transactions.entry(transaction.id) {
-            Entry::Occupied(_) => Err(TransactionError::already_exists()),
-            Entry::Vacant(transactions) => Ok(transactions.insert(TransactionLite::new(
-                transaction.t_type.clone(),
-                transaction.amount()?,
-            ))),
-        };
+    /*
+     * The events of the counterparty's leg for the events of the source account, decided before
+     * either leg is applied so that both apply or neither does. Once decided the events are
+     * certain to apply
+     */
+    pub fn counterpart(&self, events: &[AccountEvent]) -> TransactionResult<Vec<AccountEvent>> {
+        if self.locked {
+            return Err(TransactionError::locked());
+        }
+
+        let mut counterpart = Vec::new();
+        for event in events {
+            match *event {
+                AccountEvent::TransferredOut { id, amount } => {
+                    counterpart.push(AccountEvent::TransferredIn { id, amount })
+                }
+                AccountEvent::TransferDisputed { id, amount } => {
+                    counterpart.push(AccountEvent::DisputeOpened { id, amount })
+                }
+                AccountEvent::TransferResolved { id, amount } => {
+                    counterpart.push(AccountEvent::DisputeResolved { id, amount })
+                }
+                AccountEvent::TransferReversed { id, amount } => {
+                    counterpart.push(AccountEvent::ChargedBack { id, amount });
+                    counterpart.push(AccountEvent::Locked);
+                }
+                _ => {}
+            }
+        }
+
+        /* Checked against the balances alone, which is all the counterparty's events change */
+        self.balances().apply_all(&counterpart)?;
+
+        Ok(counterpart)
     }
 
-    fn deposit(&mut self, transaction: Transaction) -> TransactionResult<()> {
-        let amount = self.add_to_transactions(&transaction)?.amount;
+    /* Rebuilds an account from its events, e.g. as of a point in time */
+    pub fn replay<'a, I>(client_id: ClientId, events: I) -> TransactionResult<Self>
+    where
+        I: IntoIterator<Item = &'a AccountEvent>,
+    {
+        let mut account = Account::new(client_id);
+        for event in events {
+            account.apply(event)?;
+        }
 
-        self.total += amount;
-        self.available += amount;
+        Ok(account)
+    }
+
+    /* Applies a single event, checking for overflow before any state is changed */
+    pub fn apply(&mut self, event: &AccountEvent) -> TransactionResult<()> {
+        match *event {
+            AccountEvent::Deposited { id, amount } => {
+                let available = checked_add(self.available, amount)?;
+                self.total = checked_add(self.total, amount)?;
+                self.available = available;
+                self.transactions
+                    .insert(id, TransactionLite::new(TransactionType::Deposit, amount));
+            }
+            AccountEvent::Withdrawn { id, amount } => {
+                let available = checked_sub(self.available, amount)?;
+                self.total = checked_sub(self.total, amount)?;
+                self.available = available;
+                self.transactions.insert(
+                    id,
+                    TransactionLite::new(TransactionType::Withdrawal, amount),
+                );
+            }
+            AccountEvent::DisputeOpened { id, amount } => {
+                let held = checked_add(self.held, amount)?;
+                self.available = checked_sub(self.available, amount)?;
+                self.held = held;
+                self.set_disputed(id, true);
+            }
+            AccountEvent::DisputeResolved { id, amount } => {
+                let held = checked_sub(self.held, amount)?;
+                self.available = checked_add(self.available, amount)?;
+                self.held = held;
+                self.set_disputed(id, false);
+            }
+            AccountEvent::ChargedBack { id, amount } => {
+                let held = checked_sub(self.held, amount)?;
+                self.total = checked_sub(self.total, amount)?;
+                self.held = held;
+                self.set_disputed(id, false);
+            }
+            AccountEvent::Locked => self.locked = true,
+            AccountEvent::Authorized { id, amount } => {
+                let available = checked_sub(self.available, amount)?;
+                let held = checked_add(self.held, amount)?;
+                self.authorized = checked_add(self.authorized, amount)?;
+                self.available = available;
+                self.held = held;
+                self.transactions
+                    .insert(id, TransactionLite::new(TransactionType::Authorize, amount));
+            }
+            AccountEvent::Captured { id, amount } => {
+                let held = checked_sub(self.held, amount)?;
+                let total = checked_sub(self.total, amount)?;
+                self.authorized = checked_sub(self.authorized, amount)?;
+                self.held = held;
+                self.total = total;
+                self.release(id, amount);
+            }
+            AccountEvent::Voided { id, amount } => {
+                let held = checked_sub(self.held, amount)?;
+                let available = checked_add(self.available, amount)?;
+                self.authorized = checked_sub(self.authorized, amount)?;
+                self.held = held;
+                self.available = available;
+                self.release(id, amount);
+            }
+            AccountEvent::TransferredOut { id, amount } => {
+                let available = checked_sub(self.available, amount)?;
+                self.total = checked_sub(self.total, amount)?;
+                self.available = available;
+                self.transactions
+                    .insert(id, TransactionLite::new(TransactionType::Transfer, amount));
+            }
+            AccountEvent::TransferredIn { amount, .. } => {
+                let available = checked_add(self.available, amount)?;
+                self.total = checked_add(self.total, amount)?;
+                self.available = available;
+            }
+            /* The deposit keeps what remains unrefunded, which is all a dispute can then hold */
+            AccountEvent::Refunded { id, amount } => {
+                let available = checked_sub(self.available, amount)?;
+                self.total = checked_sub(self.total, amount)?;
+                self.available = available;
+                self.release(id, amount);
+            }
+            AccountEvent::FeeCharged { amount, .. } => {
+                let available = checked_sub(self.available, amount)?;
+                let total = checked_sub(self.total, amount)?;
+                self.fees = checked_add(self.fees, amount)?;
+                self.available = available;
+                self.total = total;
+            }
+            AccountEvent::InterestAccrued { id, amount } => {
+                let available = checked_add(self.available, amount)?;
+                self.total = checked_add(self.total, amount)?;
+                self.available = available;
+                self.transactions
+                    .insert(id, TransactionLite::new(TransactionType::Interest, amount));
+            }
+            AccountEvent::TransferDisputed { id, .. } => self.set_disputed(id, true),
+            AccountEvent::TransferResolved { id, .. } => self.set_disputed(id, false),
+            /* A reversed transfer keeps no amount, so it can not be reversed again */
+            AccountEvent::TransferReversed { id, amount } => {
+                let available = checked_add(self.available, amount)?;
+                self.total = checked_add(self.total, amount)?;
+                self.available = available;
+                self.set_disputed(id, false);
+                self.release(id, amount);
+            }
+        }
+        self.update_overdraft();
 
         Ok(())
     }
 
-    fn withdrawal(&mut self, transaction: Transaction) -> TransactionResult<()> {
-        let amount = transaction.amount()?;
-        if amount > self.available {
-            return Err(TransactionError::negative_balance());
+    fn set_disputed(&mut self, id: TransactionId, disputed: bool) {
+        if let Some(transaction) = self.transactions.get_mut(&id) {
+            transaction.disputed = disputed;
+        }
+    }
+
+    /*
+     * What remains of an authorization, of a refunded deposit, or of a transfer once reversed, is
+     * kept as its amount, so that none is settled twice
+     */
+    fn release(&mut self, id: TransactionId, amount: Decimal) {
+        if let Some(transaction) = self.transactions.get_mut(&id) {
+            transaction.amount -= amount;
+        }
+    }
+
+    fn unique_id(&self, transaction: &Transaction) -> TransactionResult<TransactionId> {
+        if self.transactions.contains_key(&transaction.id) {
+            return Err(TransactionError::already_exists());
         }
 
-        self.add_to_transactions(&transaction)?;
+        Ok(transaction.id)
+    }
 
-        self.available -= amount;
-        self.total -= amount;
+    fn deposit(&self, transaction: &Transaction) -> TransactionResult<AccountEvent> {
+        let amount = positive(transaction.amount()?)?;
 
-        Ok(())
+        Ok(AccountEvent::Deposited {
+            id: self.unique_id(transaction)?,
+            amount,
+        })
     }
 
-    fn dispute(&mut self, transaction: Transaction) -> TransactionResult<()> {
-        let mut disputed_transaction = self
+    fn withdrawal(&self, transaction: &Transaction) -> TransactionResult<AccountEvent> {
+        let amount = positive(transaction.amount()?)?;
+        if amount > self.spendable() {
+            return Err(TransactionError::negative_balance());
+        }
+
+        Ok(AccountEvent::Withdrawn {
+            id: self.unique_id(transaction)?,
+            amount,
+        })
+    }
+
+    fn dispute(&self, transaction: &Transaction) -> TransactionResult<AccountEvent> {
+        let disputed_transaction = self
             .transactions
-            .get_mut(&transaction.id)
+            .get(&transaction.id)
             .ok_or_else(TransactionError::does_not_exist)?;
 
         if disputed_transaction.disputed {
             return Err(TransactionError::already_disputed());
         }
 
-        if disputed_transaction.t_type == TransactionType::Withdrawal {
-            return Err(TransactionError::no_withdrawal_disputes());
+        let amount = disputed_transaction.amount;
+        if disputed_transaction.t_type == TransactionType::Transfer {
+            if amount.is_zero() {
+                return Err(TransactionError::already_reversed());
+            }
+
+            /* The funds are held by the counterparty, which checks them against its own total */
+            return Ok(AccountEvent::TransferDisputed {
+                id: transaction.id,
+                amount,
+            });
         }
 
-        let amount = disputed_transaction.amount;
-        if amount > self.total {
-            return Err(TransactionError::fraudulent_dispute());
+        if disputed_transaction.t_type == TransactionType::Interest {
+            return Err(TransactionError::not_disputable());
         }
 
-        disputed_transaction.disputed = true;
-        self.held += amount;
-        self.available -= amount;
+        /* Authorizations are withdrawals that have not settled yet */
+        if disputed_transaction.t_type != TransactionType::Deposit {
+            return Err(TransactionError::no_withdrawal_disputes());
+        }
 
-        Ok(())
+        /* Nothing of a fully refunded deposit is left to hold */
+        if amount.is_zero() {
+            return Err(TransactionError::already_reversed());
+        }
+
+        /* Held even when the funds have since been spent, leaving the account in debt */
+        Ok(AccountEvent::DisputeOpened {
+            id: transaction.id,
+            amount,
+        })
     }
 
-    fn get_disputed_transaction(&mut self, id: &u32) -> TransactionResult<&mut TransactionLite> {
+    fn get_disputed_transaction(&self, id: &TransactionId) -> TransactionResult<&TransactionLite> {
         let disputed_transaction = self
             .transactions
-            .get_mut(id)
+            .get(id)
             .ok_or_else(TransactionError::does_not_exist)?;
         disputed_transaction.disputed_or_err()?;
 
         Ok(disputed_transaction)
     }
 
-    fn resolve(&mut self, transaction: Transaction) -> TransactionResult<()> {
-        let disputed_transaction = self.get_disputed_transaction(&transaction.id)?;
-        disputed_transaction.disputed = false;
+    fn resolve(&self, transaction: &Transaction) -> TransactionResult<AccountEvent> {
+        let (id, disputed) = (
+            transaction.id,
+            self.get_disputed_transaction(&transaction.id)?,
+        );
+        Ok(match disputed.t_type {
+            TransactionType::Transfer => AccountEvent::TransferResolved {
+                id,
+                amount: disputed.amount,
+            },
+            _ => AccountEvent::DisputeResolved {
+                id,
+                amount: disputed.amount,
+            },
+        })
+    }
 
-        let amount = disputed_transaction.amount;
-        self.held -= amount;
-        self.available += amount;
+    /* A reversed transfer is credited back, it is the counterparty's account that is locked */
+    fn chargeback(&self, transaction: &Transaction) -> TransactionResult<Vec<AccountEvent>> {
+        let (id, disputed) = (
+            transaction.id,
+            self.get_disputed_transaction(&transaction.id)?,
+        );
+        Ok(match disputed.t_type {
+            TransactionType::Transfer => vec![AccountEvent::TransferReversed {
+                id,
+                amount: disputed.amount,
+            }],
+            _ => vec![
+                AccountEvent::ChargedBack {
+                    id,
+                    amount: disputed.amount,
+                },
+                AccountEvent::Locked,
+            ],
+        })
+    }
 
-        Ok(())
+    fn authorize(&self, transaction: &Transaction) -> TransactionResult<AccountEvent> {
+        let amount = positive(transaction.amount()?)?;
+        if amount > self.spendable() {
+            return Err(TransactionError::negative_balance());
+        }
+
+        Ok(AccountEvent::Authorized {
+            id: self.unique_id(transaction)?,
+            amount,
+        })
+    }
+
+    /* The amount still held by an authorization, which must not be fully captured or voided */
+    fn get_authorization(&self, id: &TransactionId) -> TransactionResult<Decimal> {
+        let authorization = self
+            .transactions
+            .get(id)
+            .ok_or_else(TransactionError::does_not_exist)?;
+
+        if authorization.t_type != TransactionType::Authorize || authorization.amount.is_zero() {
+            return Err(TransactionError::not_authorized());
+        }
+
+        Ok(authorization.amount)
     }
 
-    fn chargeback(&mut self, transaction: Transaction) -> TransactionResult<()> {
-        let disputed_transaction = self.get_disputed_transaction(&transaction.id)?;
-        disputed_transaction.disputed = false;
+    fn capture(&self, transaction: &Transaction) -> TransactionResult<AccountEvent> {
+        let remaining = self.get_authorization(&transaction.id)?;
+        let amount = positive(transaction.amount.unwrap_or(remaining))?;
+        if amount > remaining {
+            return Err(TransactionError::exceeds_authorization());
+        }
 
-        let amount = disputed_transaction.amount;
-        self.held -= amount;
-        self.total -= amount;
-        self.locked = true;
+        Ok(AccountEvent::Captured {
+            id: transaction.id,
+            amount,
+        })
+    }
 
-        Ok(())
+    fn void(&self, transaction: &Transaction) -> TransactionResult<AccountEvent> {
+        Ok(AccountEvent::Voided {
+            id: transaction.id,
+            amount: self.get_authorization(&transaction.id)?,
+        })
+    }
+
+    fn transfer(&self, transaction: &Transaction) -> TransactionResult<AccountEvent> {
+        if transaction.to.is_none() || transaction.to == Some(self.client_id) {
+            return Err(TransactionError::invalid_transfer());
+        }
+
+        let amount = positive(transaction.amount()?)?;
+        if amount > self.spendable() {
+            return Err(TransactionError::negative_balance());
+        }
+
+        Ok(AccountEvent::TransferredOut {
+            id: self.unique_id(transaction)?,
+            amount,
+        })
+    }
+
+    /* A disputed deposit is refunded once the dispute is resolved, or not at all */
+    fn refund(&self, transaction: &Transaction) -> TransactionResult<AccountEvent> {
+        let deposit = self
+            .transactions
+            .get(&transaction.id)
+            .ok_or_else(TransactionError::does_not_exist)?;
+
+        if deposit.t_type != TransactionType::Deposit
+            || deposit.disputed
+            || deposit.amount.is_zero()
+        {
+            return Err(TransactionError::not_refundable());
+        }
+
+        let amount = positive(transaction.amount.unwrap_or(deposit.amount))?;
+        if amount > deposit.amount {
+            return Err(TransactionError::exceeds_refundable());
+        }
+        if amount > self.spendable() {
+            return Err(TransactionError::negative_balance());
+        }
+
+        Ok(AccountEvent::Refunded {
+            id: transaction.id,
+            amount,
+        })
+    }
+
+    fn interest(&self, transaction: &Transaction) -> TransactionResult<AccountEvent> {
+        let amount = positive(transaction.amount()?)?;
+
+        Ok(AccountEvent::InterestAccrued {
+            id: self.unique_id(transaction)?,
+            amount,
+        })
+    }
+
+    pub fn client_id(&self) -> ClientId {
+        self.client_id
+    }
+
+    pub fn transactions(&self) -> impl Iterator<Item = (&TransactionId, &TransactionLite)> {
+        self.transactions.iter()
+    }
+
+    pub fn transaction(&self, id: &TransactionId) -> Option<&TransactionLite> {
+        self.transactions.get(id)
     }
 
     pub fn available(&self) -> &Decimal {
@@ -169,12 +684,36 @@ impl Account {
     pub fn total(&self) -> &Decimal {
         &self.total
     }
+
+    pub fn locked(&self) -> bool {
+        self.locked
+    }
+
+    pub fn authorized(&self) -> &Decimal {
+        &self.authorized
+    }
+
+    pub fn fees(&self) -> &Decimal {
+        &self.fees
+    }
+
+    pub fn overdraft_limit(&self) -> &Decimal {
+        &self.overdraft_limit
+    }
+
+    pub fn overdraft(&self) -> &Decimal {
+        &self.overdraft
+    }
+
+    pub fn debt(&self) -> &Decimal {
+        &self.debt
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    /* TODO: Test multiple values */
     use super::*;
+    use crate::id::{client, tx};
     use crate::transaction::transaction_error::TransactionErrorKind;
     use rust_decimal::prelude::Zero;
     use std::str::FromStr;
@@ -185,7 +724,7 @@ mod tests {
 
     #[test]
     fn test_new() {
-        let account = Account::new(1);
+        let account = Account::new(client(1));
 
         assert_eq!(Decimal::zero(), account.available);
         assert_eq!(Decimal::zero(), account.total);
@@ -196,9 +735,9 @@ mod tests {
     #[test]
     fn test_deposit() {
         let deposit_value = decimal_str("1");
-        let mut account = Account::new(1);
+        let mut account = Account::new(client(1));
         account
-            .transact(Transaction::new_deposit(1, 1, deposit_value))
+            .transact(Transaction::new_deposit(client(1), tx(1), deposit_value))
             .unwrap();
 
         assert_eq!(deposit_value, account.available);
@@ -210,14 +749,18 @@ mod tests {
     #[test]
     fn test_valid_withdrawal() {
         let deposit_value = decimal_str("2");
-        let mut account = Account::new(1);
+        let mut account = Account::new(client(1));
         account
-            .transact(Transaction::new_deposit(1, 1, deposit_value))
+            .transact(Transaction::new_deposit(client(1), tx(1), deposit_value))
             .unwrap();
 
         let withdrawal_value = decimal_str("1");
         account
-            .transact(Transaction::new_withdrawal(1, 2, withdrawal_value))
+            .transact(Transaction::new_withdrawal(
+                client(1),
+                tx(2),
+                withdrawal_value,
+            ))
             .unwrap();
 
         assert_eq!(deposit_value - withdrawal_value, account.available);
@@ -226,7 +769,11 @@ mod tests {
         assert!(!account.locked);
 
         account
-            .transact(Transaction::new_withdrawal(1, 3, withdrawal_value))
+            .transact(Transaction::new_withdrawal(
+                client(1),
+                tx(3),
+                withdrawal_value,
+            ))
             .unwrap();
 
         assert_eq!(Decimal::zero(), account.available);
@@ -238,14 +785,18 @@ mod tests {
     #[test]
     fn test_negative_balance_withdrawal() {
         let deposit_value = decimal_str("2");
-        let mut account = Account::new(1);
+        let mut account = Account::new(client(1));
         account
-            .transact(Transaction::new_deposit(1, 1, deposit_value))
+            .transact(Transaction::new_deposit(client(1), tx(1), deposit_value))
             .unwrap();
 
         let withdrawal_value = decimal_str("3");
         let err = account
-            .transact(Transaction::new_withdrawal(1, 2, withdrawal_value))
+            .transact(Transaction::new_withdrawal(
+                client(1),
+                tx(2),
+                withdrawal_value,
+            ))
             .unwrap_err();
 
         assert_eq!(&TransactionErrorKind::NegativeBalance, err.kind());
@@ -258,20 +809,24 @@ mod tests {
     #[test]
     fn test_resolve_dispute() {
         let deposit_value = decimal_str("2");
-        let mut account = Account::new(1);
+        let mut account = Account::new(client(1));
         account
-            .transact(Transaction::new_deposit(1, 1, deposit_value))
+            .transact(Transaction::new_deposit(client(1), tx(1), deposit_value))
+            .unwrap();
+        account
+            .transact(Transaction::new_dispute(client(1), tx(1)))
             .unwrap();
-        account.transact(Transaction::new_dispute(1, 1)).unwrap();
 
         assert_eq!(Decimal::zero(), account.available);
         assert_eq!(deposit_value, account.total);
         assert_eq!(deposit_value, account.held);
         assert!(!account.locked);
 
-        account.transact(Transaction::new_resolve(1, 1)).unwrap();
+        account
+            .transact(Transaction::new_resolve(client(1), tx(1)))
+            .unwrap();
 
-        assert!(!account.transactions.get(&1).unwrap().disputed);
+        assert!(!account.transactions.get(&tx(1)).unwrap().disputed);
         assert_eq!(deposit_value, account.available);
         assert_eq!(deposit_value, account.total);
         assert_eq!(Decimal::zero(), account.held);
@@ -281,12 +836,16 @@ mod tests {
     #[test]
     fn test_chargeback() {
         let deposit_value = decimal_str("2");
-        let mut account = Account::new(1);
+        let mut account = Account::new(client(1));
+        account
+            .transact(Transaction::new_deposit(client(1), tx(1), deposit_value))
+            .unwrap();
+        account
+            .transact(Transaction::new_dispute(client(1), tx(1)))
+            .unwrap();
         account
-            .transact(Transaction::new_deposit(1, 1, deposit_value))
+            .transact(Transaction::new_chargeback(client(1), tx(1)))
             .unwrap();
-        account.transact(Transaction::new_dispute(1, 1)).unwrap();
-        account.transact(Transaction::new_chargeback(1, 1)).unwrap();
 
         assert_eq!(Decimal::zero(), account.available);
         assert_eq!(Decimal::zero(), account.total);
@@ -297,13 +856,15 @@ mod tests {
     #[test]
     fn test_already_disputed_dispute() {
         let deposit_value = decimal_str("2");
-        let mut account = Account::new(1);
+        let mut account = Account::new(client(1));
         account
-            .transact(Transaction::new_deposit(1, 1, deposit_value))
+            .transact(Transaction::new_deposit(client(1), tx(1), deposit_value))
+            .unwrap();
+        account
+            .transact(Transaction::new_dispute(client(1), tx(1)))
             .unwrap();
-        account.transact(Transaction::new_dispute(1, 1)).unwrap();
         let err = account
-            .transact(Transaction::new_dispute(1, 1))
+            .transact(Transaction::new_dispute(client(1), tx(1)))
             .unwrap_err();
 
         assert_eq!(&TransactionErrorKind::AlreadyDisputed, err.kind());
@@ -317,15 +878,19 @@ mod tests {
     fn test_withdrawal_dispute_failure() {
         let deposit_value = decimal_str("2");
         let withdrawal_value = decimal_str("1");
-        let mut account = Account::new(1);
+        let mut account = Account::new(client(1));
         account
-            .transact(Transaction::new_deposit(1, 1, deposit_value))
+            .transact(Transaction::new_deposit(client(1), tx(1), deposit_value))
             .unwrap();
         account
-            .transact(Transaction::new_withdrawal(1, 2, withdrawal_value))
+            .transact(Transaction::new_withdrawal(
+                client(1),
+                tx(2),
+                withdrawal_value,
+            ))
             .unwrap();
         let err = account
-            .transact(Transaction::new_dispute(1, 2))
+            .transact(Transaction::new_dispute(client(1), tx(2)))
             .unwrap_err();
 
         assert_eq!(&TransactionErrorKind::NoWithdrawalDisputes, err.kind());
@@ -338,17 +903,17 @@ mod tests {
     #[test]
     fn test_undisputed_resolution_failure() {
         let deposit_value = decimal_str("2");
-        let mut account = Account::new(1);
+        let mut account = Account::new(client(1));
         account
-            .transact(Transaction::new_deposit(1, 1, deposit_value))
+            .transact(Transaction::new_deposit(client(1), tx(1), deposit_value))
             .unwrap();
         let err = account
-            .transact(Transaction::new_chargeback(1, 1))
+            .transact(Transaction::new_chargeback(client(1), tx(1)))
             .unwrap_err();
         assert_eq!(&TransactionErrorKind::NotDisputed, err.kind());
 
         let err = account
-            .transact(Transaction::new_resolve(1, 1))
+            .transact(Transaction::new_resolve(client(1), tx(1)))
             .unwrap_err();
         assert_eq!(&TransactionErrorKind::NotDisputed, err.kind());
 
@@ -359,42 +924,404 @@ mod tests {
     }
 
     #[test]
-    fn test_fraudulent_dispute() {
-        let deposit_value = decimal_str("2");
-        let withdrawal_value = decimal_str("1");
-        let mut account = Account::new(1);
+    fn test_dispute_into_debt() {
+        let mut account = Account::new(client(1));
+        account.set_overdraft_limit(decimal_str("1"));
+        account
+            .transact(Transaction::new_deposit(client(1), tx(1), decimal_str("2")))
+            .unwrap();
+        account
+            .transact(Transaction::new_withdrawal(
+                client(1),
+                tx(2),
+                decimal_str("1.5"),
+            ))
+            .unwrap();
         account
-            .transact(Transaction::new_deposit(1, 1, deposit_value))
+            .transact(Transaction::new_dispute(client(1), tx(1)))
             .unwrap();
+
+        assert_eq!(decimal_str("-1.5"), account.available);
+        assert_eq!(decimal_str("0.5"), account.total);
+        assert_eq!(decimal_str("1"), account.overdraft);
+        assert_eq!(decimal_str("0.5"), account.debt);
+
+        account
+            .transact(Transaction::new_chargeback(client(1), tx(1)))
+            .unwrap();
+        assert_eq!(decimal_str("-1.5"), account.total);
+        assert_eq!(decimal_str("0.5"), account.debt);
+        assert!(account.locked);
+    }
+
+    #[test]
+    fn test_overdraft() {
+        let mut account = Account::new(client(1));
+        account.set_overdraft_limit(decimal_str("10"));
         account
-            .transact(Transaction::new_withdrawal(1, 2, withdrawal_value))
+            .transact(Transaction::new_deposit(client(1), tx(1), decimal_str("5")))
+            .unwrap();
+        account
+            .transact(Transaction::new_withdrawal(
+                client(1),
+                tx(2),
+                decimal_str("12"),
+            ))
             .unwrap();
         let err = account
-            .transact(Transaction::new_dispute(1, 1))
+            .transact(Transaction::new_withdrawal(
+                client(1),
+                tx(3),
+                decimal_str("3.0001"),
+            ))
             .unwrap_err();
 
-        assert_eq!(&TransactionErrorKind::FraudulentDispute, err.kind());
-        assert_eq!(deposit_value - withdrawal_value, account.available);
-        assert_eq!(deposit_value - withdrawal_value, account.total);
+        assert_eq!(&TransactionErrorKind::NegativeBalance, err.kind());
+        assert_eq!(decimal_str("-7"), account.available);
+        assert_eq!(decimal_str("7"), account.overdraft);
+        assert_eq!(Decimal::zero(), account.debt);
+
+        /* Lowering the limit below what is used leaves the rest as debt */
+        account.set_overdraft_limit(decimal_str("4"));
+        assert_eq!(decimal_str("4"), account.overdraft);
+        assert_eq!(decimal_str("3"), account.debt);
+        account
+            .transact(Transaction::new_deposit(client(1), tx(4), decimal_str("7")))
+            .unwrap();
+        assert_eq!(Decimal::zero(), account.overdraft);
+        assert_eq!(Decimal::zero(), account.debt);
+    }
+
+    #[test]
+    fn test_authorize_and_capture() {
+        let mut account = Account::new(client(1));
+        account
+            .transact(Transaction::new_deposit(
+                client(1),
+                tx(1),
+                decimal_str("10"),
+            ))
+            .unwrap();
+        account
+            .transact(Transaction::new_authorize(
+                client(1),
+                tx(2),
+                decimal_str("6"),
+            ))
+            .unwrap();
+
+        assert_eq!(decimal_str("4"), account.available);
+        assert_eq!(decimal_str("6"), account.held);
+        assert_eq!(decimal_str("6"), account.authorized);
+        assert_eq!(decimal_str("10"), account.total);
+
+        account
+            .transact(Transaction::new_capture(
+                client(1),
+                tx(2),
+                Some(decimal_str("2")),
+            ))
+            .unwrap();
+        assert_eq!(decimal_str("4"), account.held);
+        assert_eq!(decimal_str("4"), account.authorized);
+        assert_eq!(decimal_str("8"), account.total);
+
+        let err = account
+            .transact(Transaction::new_capture(
+                client(1),
+                tx(2),
+                Some(decimal_str("5")),
+            ))
+            .unwrap_err();
+        assert_eq!(&TransactionErrorKind::ExceedsAuthorization, err.kind());
+
+        /* Without an amount the rest is captured and the authorization is spent */
+        account
+            .transact(Transaction::new_capture(client(1), tx(2), None))
+            .unwrap();
+        assert_eq!(decimal_str("4"), account.available);
         assert_eq!(Decimal::zero(), account.held);
-        assert!(!account.locked); // TODO: we would most likely want to lock an account pending investigation here?
+        assert_eq!(Decimal::zero(), account.authorized);
+        assert_eq!(decimal_str("4"), account.total);
+
+        let err = account
+            .transact(Transaction::new_void(client(1), tx(2)))
+            .unwrap_err();
+        assert_eq!(&TransactionErrorKind::NotAuthorized, err.kind());
+        let err = account
+            .transact(Transaction::new_authorize(
+                client(1),
+                tx(2),
+                decimal_str("1"),
+            ))
+            .unwrap_err();
+        assert_eq!(&TransactionErrorKind::AlreadyExists, err.kind());
+    }
+
+    #[test]
+    fn test_void_alongside_dispute() {
+        let mut account = Account::new(client(1));
+        account
+            .transact(Transaction::new_deposit(client(1), tx(1), decimal_str("3")))
+            .unwrap();
+        account
+            .transact(Transaction::new_deposit(client(1), tx(2), decimal_str("5")))
+            .unwrap();
+        account
+            .transact(Transaction::new_authorize(
+                client(1),
+                tx(3),
+                decimal_str("4"),
+            ))
+            .unwrap();
+        account
+            .transact(Transaction::new_dispute(client(1), tx(1)))
+            .unwrap();
+
+        assert_eq!(decimal_str("1"), account.available);
+        assert_eq!(decimal_str("7"), account.held);
+        assert_eq!(decimal_str("4"), account.authorized);
+
+        let err = account
+            .transact(Transaction::new_dispute(client(1), tx(3)))
+            .unwrap_err();
+        assert_eq!(&TransactionErrorKind::NoWithdrawalDisputes, err.kind());
+        let err = account
+            .transact(Transaction::new_authorize(
+                client(1),
+                tx(4),
+                decimal_str("2"),
+            ))
+            .unwrap_err();
+        assert_eq!(&TransactionErrorKind::NegativeBalance, err.kind());
+        let err = account
+            .transact(Transaction::new_capture(client(1), tx(1), None))
+            .unwrap_err();
+        assert_eq!(&TransactionErrorKind::NotAuthorized, err.kind());
+
+        account
+            .transact(Transaction::new_void(client(1), tx(3)))
+            .unwrap();
+        assert_eq!(decimal_str("5"), account.available);
+        assert_eq!(decimal_str("3"), account.held);
+        assert_eq!(Decimal::zero(), account.authorized);
+        assert_eq!(decimal_str("8"), account.total);
+    }
+
+    #[test]
+    fn test_non_positive_amount() {
+        let mut account = Account::new(client(1));
+        for transaction in [
+            Transaction::new_deposit(client(1), tx(1), decimal_str("-5")),
+            Transaction::new_deposit(client(1), tx(1), decimal_str("0")),
+            Transaction::new_withdrawal(client(1), tx(2), decimal_str("-5")),
+        ] {
+            let err = account.transact(transaction).unwrap_err();
+            assert_eq!(&TransactionErrorKind::NonPositiveAmount, err.kind());
+        }
+
+        assert_eq!(Decimal::zero(), account.total);
+        assert!(account.transactions.is_empty());
+    }
+
+    #[test]
+    fn test_non_positive_authorization() {
+        let mut account = Account::new(client(1));
+        account
+            .transact(Transaction::new_deposit(
+                client(1),
+                tx(1),
+                decimal_str("10"),
+            ))
+            .unwrap();
+
+        for amount in ["-5", "0"] {
+            let err = account
+                .transact(Transaction::new_authorize(
+                    client(1),
+                    tx(2),
+                    decimal_str(amount),
+                ))
+                .unwrap_err();
+            assert_eq!(&TransactionErrorKind::NonPositiveAmount, err.kind());
+        }
+
+        account
+            .transact(Transaction::new_authorize(
+                client(1),
+                tx(2),
+                decimal_str("5"),
+            ))
+            .unwrap();
+        for amount in ["-100", "0"] {
+            let err = account
+                .transact(Transaction::new_capture(
+                    client(1),
+                    tx(2),
+                    Some(decimal_str(amount)),
+                ))
+                .unwrap_err();
+            assert_eq!(&TransactionErrorKind::NonPositiveAmount, err.kind());
+        }
+        assert_eq!(decimal_str("5"), account.held);
+        assert_eq!(decimal_str("5"), account.authorized);
+        assert_eq!(decimal_str("10"), account.total);
+    }
+
+    #[test]
+    fn test_refund() {
+        let mut account = Account::new(client(1));
+        account
+            .transact(Transaction::new_deposit(
+                client(1),
+                tx(1),
+                decimal_str("10"),
+            ))
+            .unwrap();
+        account
+            .transact(Transaction::new_refund(
+                client(1),
+                tx(1),
+                Some(decimal_str("4")),
+            ))
+            .unwrap();
+
+        assert_eq!(decimal_str("6"), account.available);
+        assert_eq!(decimal_str("6"), account.total);
+        assert_eq!(
+            decimal_str("6"),
+            account.transactions.get(&tx(1)).unwrap().amount
+        );
+
+        let err = account
+            .transact(Transaction::new_refund(
+                client(1),
+                tx(1),
+                Some(decimal_str("7")),
+            ))
+            .unwrap_err();
+        assert_eq!(&TransactionErrorKind::ExceedsRefundable, err.kind());
+        for amount in ["-100", "0"] {
+            let err = account
+                .transact(Transaction::new_refund(
+                    client(1),
+                    tx(1),
+                    Some(decimal_str(amount)),
+                ))
+                .unwrap_err();
+            assert_eq!(&TransactionErrorKind::NonPositiveAmount, err.kind());
+        }
+        assert_eq!(decimal_str("6"), account.total);
+        assert_eq!(
+            decimal_str("6"),
+            account.transactions.get(&tx(1)).unwrap().amount
+        );
+
+        /* Only the unrefunded remainder is held, and can be charged back */
+        account
+            .transact(Transaction::new_dispute(client(1), tx(1)))
+            .unwrap();
+        assert_eq!(decimal_str("6"), account.held);
+        let err = account
+            .transact(Transaction::new_refund(client(1), tx(1), None))
+            .unwrap_err();
+        assert_eq!(&TransactionErrorKind::NotRefundable, err.kind());
+
+        account
+            .transact(Transaction::new_resolve(client(1), tx(1)))
+            .unwrap();
+        account
+            .transact(Transaction::new_refund(client(1), tx(1), None))
+            .unwrap();
+        assert_eq!(Decimal::zero(), account.total);
+        let err = account
+            .transact(Transaction::new_refund(client(1), tx(1), None))
+            .unwrap_err();
+        assert_eq!(&TransactionErrorKind::NotRefundable, err.kind());
+    }
+
+    #[test]
+    fn test_dispute_of_refunded_deposit() {
+        let mut account = Account::new(client(1));
+        account
+            .transact(Transaction::new_deposit(client(1), tx(1), decimal_str("5")))
+            .unwrap();
+        account
+            .transact(Transaction::new_refund(client(1), tx(1), None))
+            .unwrap();
+
+        let err = account
+            .transact(Transaction::new_dispute(client(1), tx(1)))
+            .unwrap_err();
+        assert_eq!(&TransactionErrorKind::AlreadyReversed, err.kind());
+        let err = account
+            .transact(Transaction::new_chargeback(client(1), tx(1)))
+            .unwrap_err();
+        assert_eq!(&TransactionErrorKind::NotDisputed, err.kind());
+        assert!(!account.locked);
+        assert_eq!(Decimal::zero(), account.total);
+    }
+
+    #[test]
+    fn test_refund_of_withdrawn_funds() {
+        let mut account = Account::new(client(1));
+        account
+            .transact(Transaction::new_deposit(client(1), tx(1), decimal_str("5")))
+            .unwrap();
+        account
+            .transact(Transaction::new_withdrawal(
+                client(1),
+                tx(2),
+                decimal_str("4"),
+            ))
+            .unwrap();
+
+        let err = account
+            .transact(Transaction::new_refund(
+                client(1),
+                tx(1),
+                Some(decimal_str("2")),
+            ))
+            .unwrap_err();
+        assert_eq!(&TransactionErrorKind::NegativeBalance, err.kind());
+        let err = account
+            .transact(Transaction::new_refund(client(1), tx(2), None))
+            .unwrap_err();
+        assert_eq!(&TransactionErrorKind::NotRefundable, err.kind());
+        assert_eq!(decimal_str("1"), account.total);
+    }
+
+    #[test]
+    fn test_deposit_overflow() {
+        let mut account = Account::new(client(1));
+        account
+            .transact(Transaction::new_deposit(client(1), tx(1), Decimal::MAX))
+            .unwrap();
+        let err = account
+            .transact(Transaction::new_deposit(client(1), tx(2), Decimal::MAX))
+            .unwrap_err();
+
+        assert_eq!(&TransactionErrorKind::Overflow, err.kind());
+        assert!(account.transactions.get(&tx(2)).is_none());
+        assert_eq!(Decimal::MAX, account.available);
+        assert_eq!(Decimal::MAX, account.total);
     }
 
     #[test]
     fn test_transaction_does_not_exist() {
-        let mut account = Account::new(1);
+        let mut account = Account::new(client(1));
         let err = account
-            .transact(Transaction::new_dispute(1, 1))
+            .transact(Transaction::new_dispute(client(1), tx(1)))
             .unwrap_err();
         assert_eq!(&TransactionErrorKind::DoesNotExist, err.kind());
 
         let err = account
-            .transact(Transaction::new_resolve(1, 1))
+            .transact(Transaction::new_resolve(client(1), tx(1)))
             .unwrap_err();
         assert_eq!(&TransactionErrorKind::DoesNotExist, err.kind());
 
         let err = account
-            .transact(Transaction::new_chargeback(1, 1))
+            .transact(Transaction::new_chargeback(client(1), tx(1)))
             .unwrap_err();
         assert_eq!(&TransactionErrorKind::DoesNotExist, err.kind());
 
@@ -404,3 +1331,144 @@ mod tests {
         assert!(!account.locked);
     }
 }
+
+#[cfg(test)]
+mod proptests {
+    use super::*;
+    use crate::id::{client, tx};
+    use proptest::prelude::*;
+
+    const CLIENT_ID: u64 = 1;
+
+    /*
+     * Ids are drawn from a small range so that disputes, resolves and chargebacks regularly hit
+     * existing transactions, other clients and missing, zero and negative amounts cover the
+     * invalid cases
+     */
+    fn amount() -> impl Strategy<Value = Option<Decimal>> {
+        prop_oneof![
+            8 => (1i64..1_000_000).prop_map(|value| Some(Decimal::new(value, 4))),
+            1 => (-1_000_000i64..=0).prop_map(|value| Some(Decimal::new(value, 4))),
+            1 => Just(None),
+        ]
+    }
+
+    fn transaction() -> impl Strategy<Value = Transaction> {
+        (
+            prop_oneof![
+                Just(TransactionType::Deposit),
+                Just(TransactionType::Withdrawal),
+                Just(TransactionType::Dispute),
+                Just(TransactionType::Resolve),
+                Just(TransactionType::Chargeback),
+                Just(TransactionType::Authorize),
+                Just(TransactionType::Capture),
+                Just(TransactionType::Void),
+                Just(TransactionType::Transfer),
+                Just(TransactionType::Refund),
+            ],
+            prop_oneof![19 => Just(CLIENT_ID), 1 => Just(CLIENT_ID + 1)],
+            0u64..16,
+            amount(),
+            prop_oneof![8 => Just(Some(CLIENT_ID + 1)), 1 => Just(Some(CLIENT_ID)), 1 => Just(None)],
+        )
+            .prop_map(|(t_type, client_id, id, amount, to)| Transaction {
+                t_type,
+                client_id: client(client_id),
+                id: tx(id),
+                amount,
+                to: to.map(client),
+                timestamp: None,
+            })
+    }
+
+    fn transactions() -> impl Strategy<Value = Vec<Transaction>> {
+        prop::collection::vec(transaction(), 0..64)
+    }
+
+    type State = (
+        Decimal,
+        Decimal,
+        Decimal,
+        bool,
+        Vec<(TransactionId, bool, Decimal)>,
+    );
+
+    fn state(account: &Account) -> State {
+        let mut transactions: Vec<(TransactionId, bool, Decimal)> = account
+            .transactions
+            .iter()
+            .map(|(id, transaction)| (*id, transaction.disputed, transaction.amount))
+            .collect();
+        transactions.sort_unstable();
+
+        (
+            account.available,
+            account.held,
+            account.total,
+            account.locked,
+            transactions,
+        )
+    }
+
+    fn replay(transactions: &[Transaction]) -> Account {
+        let mut account = Account::new(client(CLIENT_ID));
+        for transaction in transactions {
+            let _ = account.transact(transaction.clone());
+        }
+
+        account
+    }
+
+    proptest! {
+        #[test]
+        fn test_invariants_hold_after_every_step(transactions in transactions()) {
+            let mut account = Account::new(client(CLIENT_ID));
+            for transaction in transactions {
+                let before = state(&account);
+                let t_type = transaction.t_type.clone();
+                let non_positive = matches!(transaction.amount, Some(amount) if amount <= Decimal::ZERO)
+                    && t_type != TransactionType::Dispute
+                    && t_type != TransactionType::Resolve
+                    && t_type != TransactionType::Chargeback
+                    && t_type != TransactionType::Void;
+                let result = account.transact(transaction);
+
+                prop_assert_eq!(account.total, account.available + account.held);
+                prop_assert!(account.held >= Decimal::ZERO);
+                prop_assert!(account.authorized >= Decimal::ZERO);
+                prop_assert!(account.authorized <= account.held);
+                if t_type == TransactionType::Withdrawal && result.is_ok() {
+                    prop_assert!(account.available >= Decimal::ZERO);
+                }
+                if non_positive {
+                    prop_assert!(result.is_err());
+                    prop_assert_eq!(&before, &state(&account));
+                }
+                if before.3 {
+                    prop_assert!(result.is_err());
+                    prop_assert_eq!(&before, &state(&account));
+                }
+            }
+        }
+
+        #[test]
+        fn test_replay_is_deterministic(transactions in transactions()) {
+            prop_assert_eq!(state(&replay(&transactions)), state(&replay(&transactions)));
+        }
+
+        #[test]
+        fn test_events_rebuild_account(transactions in transactions()) {
+            let mut account = Account::new(client(CLIENT_ID));
+            let mut events = Vec::new();
+            for transaction in transactions {
+                if let Ok(transaction_events) = account.transact(transaction) {
+                    events.extend(transaction_events);
+                }
+            }
+
+            let replayed = Account::replay(client(CLIENT_ID), &events).unwrap();
+            prop_assert_eq!(state(&account), state(&replayed));
+        }
+    }
+}
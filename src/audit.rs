@@ -0,0 +1,387 @@
+use crate::account::TransactionResult;
+use crate::id::{ClientId, TransactionId};
+use crate::storage::storage_error;
+use crate::transaction::Transaction;
+use csv::{Writer, WriterBuilder};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::Path;
+
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/*
+ * One line of the audit log, amount is kept as written so that the hash can be recomputed from
+ * the file exactly, to is the destination of a transfer and result is accepted or the kind of
+ * error the transaction was rejected with
+ */
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AuditEntry {
+    pub entry: u64,
+    #[serde(rename = "type")]
+    pub t_type: String,
+    #[serde(rename = "client")]
+    pub client_id: ClientId,
+    #[serde(rename = "tx")]
+    pub id: TransactionId,
+    pub amount: String,
+    pub to: Option<ClientId>,
+    pub result: String,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl AuditEntry {
+    fn new(
+        entry: u64,
+        transaction: &Transaction,
+        result: &TransactionResult<()>,
+        prev_hash: String,
+    ) -> Self {
+        let mut audit_entry = Self {
+            entry,
+            t_type: transaction.t_type.as_str().to_string(),
+            client_id: transaction.client_id,
+            id: transaction.id,
+            amount: transaction
+                .amount
+                .map(|amount| amount.to_string())
+                .unwrap_or_default(),
+            to: transaction.to,
+            result: match result {
+                Ok(()) => "accepted".to_string(),
+                Err(err) => format!("{:?}", err.kind()),
+            },
+            prev_hash,
+            hash: String::new(),
+        };
+        audit_entry.hash = audit_entry.compute_hash();
+
+        audit_entry
+    }
+
+    /*
+     * Hash of every other field, which chains it to the previous entry through prev_hash. Each
+     * field is prefixed with its length, as string ids may contain any separator, and a missing
+     * destination is hashed apart from an empty one
+     */
+    pub fn compute_hash(&self) -> String {
+        let fields = [
+            Some(self.entry.to_string()),
+            Some(self.t_type.clone()),
+            Some(self.client_id.to_string()),
+            Some(self.id.to_string()),
+            Some(self.amount.clone()),
+            self.to.map(|to| to.to_string()),
+            Some(self.result.clone()),
+            Some(self.prev_hash.clone()),
+        ];
+        let mut hasher = Sha256::new();
+        for field in &fields {
+            match field {
+                Some(field) => hasher.update(format!("{}:{}", field.len(), field)),
+                None => hasher.update("-"),
+            }
+        }
+
+        hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct AuditSummary {
+    pub entries: u64,
+    pub last_hash: String,
+}
+
+impl AuditSummary {
+    fn genesis() -> Self {
+        Self {
+            entries: 0,
+            last_hash: GENESIS_HASH.to_string(),
+        }
+    }
+}
+
+/* The first entry whose link to the chain is broken, entries are numbered from 0 */
+#[derive(Debug, PartialEq)]
+pub struct AuditBreak {
+    pub entry: u64,
+    pub reason: String,
+}
+
+impl Error for AuditBreak {}
+
+impl Display for AuditBreak {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "entry {}: {}", self.entry, self.reason)
+    }
+}
+
+/*
+ * A modified entry no longer matches its own hash, a removed or reordered entry breaks the
+ * prev_hash link (and the numbering) of the entry that follows it, removing entries from the end
+ * can only be detected by comparing last_hash with a copy kept elsewhere
+ */
+pub fn verify_audit_log<R>(reader: R) -> Result<AuditSummary, AuditBreak>
+where
+    R: io::Read,
+{
+    let mut summary = AuditSummary::genesis();
+
+    for audit_entry in csv::Reader::from_reader(reader).deserialize() {
+        let broken = |reason: String| AuditBreak {
+            entry: summary.entries,
+            reason,
+        };
+        let audit_entry: AuditEntry =
+            audit_entry.map_err(|err| broken(format!("malformed entry, {}", err)))?;
+
+        if audit_entry.hash != audit_entry.compute_hash() {
+            return Err(broken("contents do not match its hash".to_string()));
+        }
+        if audit_entry.prev_hash != summary.last_hash {
+            return Err(broken(
+                "previous hash does not match the preceding entry".to_string(),
+            ));
+        }
+        if audit_entry.entry != summary.entries {
+            return Err(broken(format!("numbered {}", audit_entry.entry)));
+        }
+
+        summary.entries += 1;
+        summary.last_hash = audit_entry.hash;
+    }
+
+    Ok(summary)
+}
+
+pub struct AuditLog {
+    writer: Writer<Box<dyn io::Write + Send>>,
+    entries: u64,
+    last_hash: String,
+}
+
+impl AuditLog {
+    pub fn new<W>(writer: W) -> Self
+    where
+        W: io::Write + Send + 'static,
+    {
+        AuditLog::continue_chain(Box::new(writer), AuditSummary::genesis())
+    }
+
+    /* Appends to an existing log, which must verify, or creates a new one */
+    pub fn open<P>(path: P) -> Result<Self, Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+    {
+        let summary = match File::open(&path) {
+            Ok(file) => verify_audit_log(file)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => AuditSummary::genesis(),
+            Err(err) => return Err(Box::new(err)),
+        };
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(AuditLog::continue_chain(Box::new(file), summary))
+    }
+
+    fn continue_chain(writer: Box<dyn io::Write + Send>, summary: AuditSummary) -> Self {
+        Self {
+            writer: WriterBuilder::new()
+                .has_headers(summary.entries == 0)
+                .from_writer(writer),
+            entries: summary.entries,
+            last_hash: summary.last_hash,
+        }
+    }
+
+    /* Entries are flushed as they are written so that the log is complete if the process dies */
+    pub fn append(
+        &mut self,
+        transaction: &Transaction,
+        result: &TransactionResult<()>,
+    ) -> TransactionResult<()> {
+        let audit_entry =
+            AuditEntry::new(self.entries, transaction, result, self.last_hash.clone());
+        self.writer.serialize(&audit_entry).map_err(storage_error)?;
+        self.writer.flush().map_err(storage_error)?;
+
+        self.entries += 1;
+        self.last_hash = audit_entry.hash;
+
+        Ok(())
+    }
+
+    pub fn last_hash(&self) -> &str {
+        &self.last_hash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::Bank;
+    use crate::dispute::{DisputeAction, DisputePolicy};
+    use crate::id::{client, tx};
+    use rust_decimal::Decimal;
+    use std::fs::{read_to_string, remove_file, write};
+    use std::str::FromStr;
+
+    fn decimal_str(decimal: &str) -> Decimal {
+        Decimal::from_str(decimal).unwrap()
+    }
+
+    fn write_log(file_name: &str) {
+        let _ = remove_file(file_name);
+        let mut bank = Bank::new().with_audit_log(AuditLog::open(file_name).unwrap());
+        bank.transact(Transaction::new_deposit(client(1), tx(1), decimal_str("2")))
+            .unwrap();
+        bank.transact(Transaction::new_withdrawal(
+            client(1),
+            tx(2),
+            decimal_str("3"),
+        ))
+        .unwrap_err();
+        bank.transact(Transaction::new_dispute(client(1), tx(1)))
+            .unwrap();
+        bank.transact(Transaction::new_resolve(client(1), tx(1)))
+            .unwrap();
+        bank.transact(Transaction::new_transfer(
+            client(1),
+            tx(3),
+            client(2),
+            decimal_str("1"),
+        ))
+        .unwrap();
+    }
+
+    fn tamper(file_name: &str, edit: fn(&mut Vec<String>)) -> AuditBreak {
+        let mut lines: Vec<String> = read_to_string(file_name)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect();
+        edit(&mut lines);
+
+        verify_audit_log(lines.join("\n").as_bytes()).unwrap_err()
+    }
+
+    #[test]
+    fn test_verify() {
+        let file_name = "audit_verify_test.csv";
+        write_log(file_name);
+
+        let summary = verify_audit_log(File::open(file_name).unwrap()).unwrap();
+        assert_eq!(5, summary.entries);
+        let log = read_to_string(file_name).unwrap();
+        assert!(log.contains(",NegativeBalance,"));
+        assert!(log.contains(&format!(
+            "\n4,transfer,{},{},1,{},accepted,",
+            client(1),
+            tx(3),
+            client(2)
+        )));
+
+        let mut audit_log = AuditLog::open(file_name).unwrap();
+        assert_eq!(summary.last_hash, audit_log.last_hash());
+        audit_log
+            .append(&Transaction::new_dispute(client(1), tx(1)), &Ok(()))
+            .unwrap();
+        drop(audit_log);
+        assert_eq!(
+            6,
+            verify_audit_log(File::open(file_name).unwrap())
+                .unwrap()
+                .entries
+        );
+
+        remove_file(file_name).unwrap();
+    }
+
+    #[test]
+    fn test_hash_separates_fields() {
+        let audit_entry = |result: &str, prev_hash: &str| AuditEntry {
+            entry: 0,
+            t_type: "deposit".to_string(),
+            client_id: client(1),
+            id: tx(1),
+            amount: "1".to_string(),
+            to: None,
+            result: result.to_string(),
+            prev_hash: prev_hash.to_string(),
+            hash: String::new(),
+        };
+
+        assert_ne!(
+            audit_entry("accepted|a", "b").compute_hash(),
+            audit_entry("accepted", "a|b").compute_hash()
+        );
+    }
+
+    #[test]
+    fn test_audits_dispute_policy() {
+        let file_name = "audit_policy_test.csv";
+        let _ = remove_file(file_name);
+        let mut bank = Bank::new()
+            .with_dispute_policy(DisputePolicy::new(0, DisputeAction::Chargeback))
+            .with_audit_log(AuditLog::open(file_name).unwrap());
+        bank.transact(Transaction::new_deposit(client(1), tx(1), decimal_str("2")))
+            .unwrap();
+        bank.transact(Transaction::new_dispute(client(1), tx(1)))
+            .unwrap();
+        bank.transact(Transaction::new_deposit(client(2), tx(2), decimal_str("1")))
+            .unwrap();
+        drop(bank);
+
+        let log = read_to_string(file_name).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(5, lines.len());
+        assert!(lines[3].starts_with(&format!("2,chargeback,{},{},,,accepted,", client(1), tx(1))));
+        assert!(lines[4].starts_with(&format!("3,deposit,{},{},1,,accepted,", client(2), tx(2))));
+        assert_eq!(
+            4,
+            verify_audit_log(File::open(file_name).unwrap())
+                .unwrap()
+                .entries
+        );
+
+        remove_file(file_name).unwrap();
+    }
+
+    #[test]
+    fn test_detects_tampering() {
+        let file_name = "audit_tamper_test.csv";
+        write_log(file_name);
+
+        let modified = tamper(file_name, |lines| {
+            lines[1] = lines[1].replacen(&format!(",{},2,", tx(1)), &format!(",{},20,", tx(1)), 1);
+        });
+        assert_eq!(0, modified.entry);
+
+        let removed = tamper(file_name, |lines| {
+            lines.remove(2);
+        });
+        assert_eq!(1, removed.entry);
+
+        let reordered = tamper(file_name, |lines| lines.swap(2, 3));
+        assert_eq!(1, reordered.entry);
+
+        let redirected = tamper(file_name, |lines| {
+            let to = format!(",1,{},", client(2));
+            lines[5] = lines[5].replacen(&to, &format!(",1,{},", client(3)), 1);
+        });
+        assert_eq!(4, redirected.entry);
+
+        write(file_name, "not,an,audit,log\n1,2,3,4\n").unwrap();
+        assert!(AuditLog::open(file_name).is_err());
+
+        remove_file(file_name).unwrap();
+    }
+}
@@ -1,46 +1,1783 @@
-use crate::account::Account;
+use crate::account::{Account, TransactionResult};
+use crate::audit::AuditLog;
+use crate::clock::{Clock, SystemClock};
+use crate::dispute::{DisputeAction, DisputePolicy, OpenDispute};
+use crate::event::{AccountEvent, AsOf, Event};
+use crate::fee::FeeSchedule;
+use crate::id::{ClientId, Id, TransactionId};
+use crate::interest::{Date, InterestPolicy};
+use crate::overdraft::OverdraftLimits;
+use crate::schedule::StandingOrders;
+use crate::storage::memory::MemoryStorage;
+use crate::storage::Storage;
+use crate::transaction::transaction_error::{TransactionError, TransactionErrorKind};
+use crate::transaction::transaction_type::TransactionType;
 use crate::transaction::Transaction;
-use ahash::AHashMap;
+use rust_decimal::Decimal;
+use std::borrow::Cow;
 
-#[derive(Default)]
-pub struct Bank {
-    accounts: AHashMap<u16, Account>,
-    transactions: AHashMap<u32, u16>,
+const MAX_ATTEMPTS: u32 = 10;
+/* The transactions the bank makes itself are numbered from here unless set */
+pub const DEFAULT_BANK_TX: u64 = 3_000_000_000;
+
+/*
+ * Notified of the outcome of every transaction after it has been committed, with the new state
+ * of every account it touched when it was applied, its own first, and of the events committed
+ * with it. The resolves, chargebacks
+ * and voids the bank makes itself are notified ahead of the transaction that triggered them, an
+ * error is returned from transact
+ */
+pub trait TransactionListener: Send {
+    fn on_transaction(
+        &mut self,
+        transaction: &Transaction,
+        result: &TransactionResult<()>,
+        accounts: &[Account],
+    ) -> TransactionResult<()>;
+
+    fn on_events(&mut self, _events: &[Event]) -> TransactionResult<()> {
+        Ok(())
+    }
+}
+
+pub struct Bank<S = MemoryStorage> {
+    storage: S,
+    clock: Box<dyn Clock>,
+    dispute_policy: Option<DisputePolicy>,
+    /* Authorizations older than this many input positions are voided */
+    authorization_expiry: Option<u64>,
+    fee_schedule: Option<FeeSchedule>,
+    overdraft_limits: Option<OverdraftLimits>,
+    standing_orders: Option<StandingOrders>,
+    /* The first id of the transactions the bank makes, input ids from here on are rejected */
+    bank_tx: u64,
+    audit_log: Option<AuditLog>,
+    listeners: Vec<Box<dyn TransactionListener>>,
+    recorded: Vec<Event>,
+    /* The balances of the accounts the transaction settled, only kept for the listeners */
+    settled: Vec<Account>,
+    automatic: Vec<Automatic>,
+}
+
+/* Progress committed with a transaction, so that it is applied exactly once */
+#[derive(Clone, Copy)]
+enum Progress<'a> {
+    /* The record at an offset of the partition of a topic */
+    Consumed(&'a str, u32, u64),
+    /* A day's interest, only recorded when it was posted */
+    Accrued(Date),
+    /* The payment of the standing order at an index due at a time */
+    Paid(u64, u64),
+}
+
+/* A transaction the bank made itself while applying another, kept until it has been audited */
+struct Automatic {
+    transaction: Transaction,
+    result: TransactionResult<()>,
+    events: Vec<Event>,
+    /* The accounts as this transaction left them, only kept for the listeners */
+    accounts: Vec<Account>,
+}
+
+impl Default for Bank {
+    fn default() -> Self {
+        Bank::with_storage(MemoryStorage::new())
+    }
 }
 
 impl Bank {
     pub fn new() -> Self {
         Bank::default()
     }
+}
+
+impl<S> Bank<S>
+where
+    S: Storage,
+{
+    pub fn with_storage(storage: S) -> Self {
+        Self {
+            storage,
+            clock: Box::new(SystemClock),
+            dispute_policy: None,
+            authorization_expiry: None,
+            fee_schedule: None,
+            overdraft_limits: None,
+            standing_orders: None,
+            bank_tx: DEFAULT_BANK_TX,
+            audit_log: None,
+            listeners: Vec::new(),
+            recorded: Vec::new(),
+            settled: Vec::new(),
+            automatic: Vec::new(),
+        }
+    }
+
+    /* Timestamps the recorded events, the system clock by default */
+    pub fn with_clock<C>(mut self, clock: C) -> Self
+    where
+        C: Clock + 'static,
+    {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    pub fn with_dispute_policy(mut self, dispute_policy: DisputePolicy) -> Self {
+        self.dispute_policy = Some(dispute_policy);
+        self
+    }
+
+    pub fn with_authorization_expiry(mut self, max_age: u64) -> Self {
+        self.authorization_expiry = Some(max_age);
+        self
+    }
+
+    pub fn with_fee_schedule(mut self, fee_schedule: FeeSchedule) -> Self {
+        self.fee_schedule = Some(fee_schedule);
+        self
+    }
+
+    /* Each account takes its limit from its next transaction on */
+    pub fn with_overdraft_limits(mut self, overdraft_limits: OverdraftLimits) -> Self {
+        self.overdraft_limits = Some(overdraft_limits);
+        self
+    }
+
+    pub fn with_standing_orders(mut self, standing_orders: StandingOrders) -> Self {
+        self.standing_orders = Some(standing_orders);
+        self
+    }
+
+    /*
+     * Numbers the transactions the bank makes from first_tx, which must be a transaction id, every
+     * process sharing the storage must number them from the same one
+     */
+    pub fn with_bank_tx(mut self, first_tx: u64) -> TransactionResult<Self> {
+        if TransactionId::from_sequence(first_tx).is_none() {
+            return Err(TransactionError::from(
+                "The first id of the bank's transactions is not a transaction id",
+            ));
+        }
+
+        self.bank_tx = first_tx;
+        Ok(self)
+    }
+
+    pub fn with_audit_log(mut self, audit_log: AuditLog) -> Self {
+        self.audit_log = Some(audit_log);
+        self
+    }
+
+    pub fn with_listener<L>(mut self, listener: L) -> Self
+    where
+        L: TransactionListener + 'static,
+    {
+        self.listeners.push(Box::new(listener));
+        self
+    }
+
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    pub fn now(&self) -> u64 {
+        self.clock.now()
+    }
+
+    pub fn account(&self, client_id: ClientId) -> TransactionResult<Option<Cow<'_, Account>>> {
+        self.storage.account(client_id)
+    }
+
+    pub fn accounts(&self) -> TransactionResult<Vec<Cow<'_, Account>>> {
+        self.storage.accounts()
+    }
+
+    pub fn open_disputes(&self) -> TransactionResult<Vec<OpenDispute>> {
+        self.storage.open_disputes()
+    }
+
+    /* Every fee charged, credited to the bank */
+    pub fn revenue(&self) -> TransactionResult<Decimal> {
+        self.storage.revenue()
+    }
+
+    /* Every event recorded for the client, in the order they were recorded */
+    pub fn events(&self, client_id: ClientId) -> TransactionResult<Cow<'_, [Event]>> {
+        self.storage.events(client_id)
+    }
+
+    /*
+     * Balances replayed from the event log, None if the client had no events by then, with the
+     * client's overdraft limit splitting a negative balance into overdraft and debt
+     */
+    pub fn account_at(
+        &self,
+        client_id: ClientId,
+        as_of: AsOf,
+    ) -> TransactionResult<Option<Account>> {
+        let events = self.storage.events(client_id)?;
+        let events: Vec<&AccountEvent> = events
+            .iter()
+            .filter(|event| as_of.includes(event))
+            .map(|event| &event.event)
+            .collect();
+        if events.is_empty() {
+            return Ok(None);
+        }
+
+        let mut account = Account::replay(client_id, events)?;
+        if let Some(limits) = self.overdraft_limits.as_ref() {
+            account.set_overdraft_limit(limits.limit(client_id));
+        }
+
+        Ok(Some(account))
+    }
+
+    fn record(
+        &mut self,
+        client_id: ClientId,
+        position: u64,
+        events: Vec<AccountEvent>,
+    ) -> TransactionResult<()> {
+        let timestamp = self.clock.now();
+        let events: Vec<Event> = events
+            .into_iter()
+            .map(|event| Event::new(position, timestamp, client_id, event))
+            .collect();
+        self.storage.append_events(&events)?;
+
+        /* Only kept until the listeners have been notified */
+        if !self.listeners.is_empty() {
+            self.recorded.extend(events);
+        }
+
+        Ok(())
+    }
+
+    fn reserved_transaction_id(&self, transaction: &Transaction) -> bool {
+        matches!(transaction.id.sequence(), Some(sequence) if sequence >= self.bank_tx)
+    }
+
+    /* The id of the next transaction the bank makes, None once they run past the transaction ids */
+    fn next_bank_tx(&mut self) -> TransactionResult<Option<TransactionId>> {
+        let sequence = self.storage.next_bank_sequence()?;
+        Ok(self
+            .bank_tx
+            .checked_add(sequence)
+            .and_then(TransactionId::from_sequence))
+    }
+
+    fn duplicate_transaction_id(&mut self, transaction: &Transaction) -> TransactionResult<bool> {
+        Ok(self
+            .storage
+            .get_or_insert_client_id(transaction.id, transaction.client_id)?
+            != transaction.client_id)
+    }
+
+    fn apply_dispute_policy(&mut self, position: u64) -> TransactionResult<()> {
+        let policy = match self.dispute_policy {
+            Some(policy) => policy,
+            None => return Ok(()),
+        };
+
+        let cutoff = position.saturating_sub(policy.max_age);
+        while let Some((client_id, id)) = self.storage.pop_expired_dispute(cutoff)? {
+            let transaction = match policy.action {
+                DisputeAction::Resolve => Transaction::new_resolve(client_id, id),
+                DisputeAction::Chargeback => Transaction::new_chargeback(client_id, id),
+            };
+
+            /*
+             * A failure here comes from the account having since been locked, which is never
+             * undone, so rather than retrying the dispute is dropped from the report, its funds
+             * stay held on the locked account
+             */
+            self.transact_automatically(transaction, position)?;
+            self.storage.close_dispute(id)?;
+        }
+
+        Ok(())
+    }
+
+    fn expire_authorizations(&mut self, position: u64) -> TransactionResult<()> {
+        let max_age = match self.authorization_expiry {
+            Some(max_age) => max_age,
+            None => return Ok(()),
+        };
+
+        let cutoff = position.saturating_sub(max_age);
+        while let Some((client_id, id)) = self.storage.pop_expired_authorization(cutoff)? {
+            /* A locked account keeps its hold, as with disputes the void is not retried */
+            if self.transact_automatically(Transaction::new_void(client_id, id), position)? {
+                self.storage.close_authorization(id)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /*
+     * Applies a transaction the bank made itself, which is audited and given to the listeners
+     * ahead of the one being applied, returns whether it was accepted
+     */
+    fn transact_automatically(
+        &mut self,
+        transaction: Transaction,
+        position: u64,
+    ) -> TransactionResult<bool> {
+        let result = match self.transact_accounts(&transaction, position) {
+            Err(err) if err.kind().is_storage() => return Err(err),
+            result => result,
+        };
+        let accepted = result.is_ok();
+        let accounts = Bank::<S>::settled_accounts(&result, &mut self.settled);
+        self.automatic.push(Automatic {
+            transaction,
+            result,
+            events: std::mem::take(&mut self.recorded),
+            accounts,
+        });
+
+        Ok(accepted)
+    }
+
+    fn track_dispute(&mut self, transaction: &Transaction, position: u64) -> TransactionResult<()> {
+        match transaction.t_type {
+            TransactionType::Dispute => {
+                self.storage
+                    .open_dispute(transaction.client_id, transaction.id, position)
+            }
+            TransactionType::Resolve | TransactionType::Chargeback => {
+                self.storage.close_dispute(transaction.id)
+            }
+            TransactionType::Authorize => {
+                self.storage
+                    .open_authorization(transaction.client_id, transaction.id, position)
+            }
+            TransactionType::Capture | TransactionType::Void => {
+                let remaining = self
+                    .storage
+                    .account_mut(transaction.client_id, transaction.id)?
+                    .transaction(&transaction.id)
+                    .map(|authorization| authorization.amount)
+                    .unwrap_or_default();
+                if remaining.is_zero() {
+                    self.storage.close_authorization(transaction.id)?;
+                }
+
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /*
+     * Applies the transaction to its account and, for transfers and their disputes, to the
+     * counterparty's account, both legs are decided before either is applied
+     */
+    fn transact_accounts(
+        &mut self,
+        transaction: &Transaction,
+        position: u64,
+    ) -> TransactionResult<()> {
+        let (client_id, id) = (transaction.client_id, transaction.id);
+        let account = self.storage.account_mut(client_id, id)?;
+        if let Some(limits) = self.overdraft_limits.as_ref() {
+            account.set_overdraft_limit(limits.limit(client_id));
+        }
+        let mut events = account.decide(transaction)?;
+        let fee = self
+            .fee_schedule
+            .as_ref()
+            .and_then(|schedule| schedule.fee(client_id, &transaction.t_type));
+        if let Some(fee) = fee {
+            events.extend(account.decide_fee(transaction, &events, fee)?);
+        }
+
+        let counterpart = match account.counterparty(transaction) {
+            Some(counterparty) => {
+                let account = self.storage.account_mut(counterparty, id)?;
+                if let Some(limits) = self.overdraft_limits.as_ref() {
+                    account.set_overdraft_limit(limits.limit(counterparty));
+                }
+                let counterpart = account.counterpart(&events)?;
+                Some((counterparty, counterpart))
+            }
+            None => None,
+        };
+
+        let account = self.storage.account_mut(client_id, id)?;
+        account.settle(transaction, &events)?;
+        if !self.listeners.is_empty() {
+            self.settled.push(account.balances());
+        }
+        let revenue = events
+            .iter()
+            .filter_map(|event| match event {
+                AccountEvent::FeeCharged { amount, .. } => Some(*amount),
+                _ => None,
+            })
+            .sum::<Decimal>();
+        if !revenue.is_zero() {
+            self.storage.credit_revenue(revenue)?;
+        }
+        self.record(client_id, position, events)?;
+
+        if let Some((counterparty, events)) = counterpart {
+            let account = self.storage.account_mut(counterparty, id)?;
+            account.settle(transaction, &events)?;
+            if !self.listeners.is_empty() {
+                self.settled.push(account.balances());
+            }
+            self.record(counterparty, position, events)?;
+        }
+
+        Ok(())
+    }
+
+    fn apply(&mut self, transaction: &Transaction, by_bank: bool) -> TransactionResult<()> {
+        let position = self.storage.next_position()?;
+        self.apply_dispute_policy(position)?;
+        self.expire_authorizations(position)?;
+
+        /* Rejected before its id is claimed, so that it never takes one the bank will use */
+        if !by_bank && self.reserved_transaction_id(transaction) {
+            return Err(TransactionError::reserved_id());
+        }
+        if self.duplicate_transaction_id(transaction)? {
+            return Err(TransactionError::incorrect_client());
+        }
+
+        self.transact_accounts(transaction, position)?;
+
+        self.track_dispute(transaction, position)
+    }
+
+    /*
+     * The offset after a consumed record, or the day of interest posted, is stored in the same
+     * storage transaction as its changes, so that it is applied exactly once
+     */
+    fn apply_atomically(
+        &mut self,
+        transaction: &Transaction,
+        progress: Option<Progress>,
+    ) -> TransactionResult<()> {
+        self.recorded.clear();
+        self.settled.clear();
+        self.automatic.clear();
+        self.storage.begin()?;
+
+        let by_bank = matches!(
+            progress,
+            Some(Progress::Accrued(_)) | Some(Progress::Paid(..))
+        );
+        let result = match (self.apply(transaction, by_bank), progress) {
+            (Err(err), _) if err.kind().is_storage() => Err(err),
+            (result, Some(Progress::Consumed(topic, partition, offset))) => self
+                .storage
+                .set_consumer_offset(topic, partition, offset + 1)
+                .and(result),
+            (Ok(()), Some(Progress::Accrued(date))) => self
+                .storage
+                .set_interest_accrued(transaction.client_id, date),
+            (Ok(()), Some(Progress::Paid(index, due))) => {
+                self.storage.set_standing_order_paid(index, due)
+            }
+            (result, _) => result,
+        };
+
+        match result {
+            Err(err) if err.kind().is_storage() => {
+                self.storage.rollback()?;
+                Err(err)
+            }
+            result => match self.storage.commit() {
+                Ok(()) => result,
+                Err(err) => {
+                    self.storage.rollback()?;
+                    Err(err)
+                }
+            },
+        }
+    }
+
+    fn notify(
+        listeners: &mut [Box<dyn TransactionListener>],
+        transaction: &Transaction,
+        result: &TransactionResult<()>,
+        events: &[Event],
+        accounts: &[Account],
+    ) -> TransactionResult<()> {
+        for listener in listeners.iter_mut() {
+            listener.on_events(events)?;
+            listener.on_transaction(transaction, result, accounts)?;
+        }
+
+        Ok(())
+    }
+
+    /* The balances the transaction left its accounts with, its own first as it is settled first */
+    fn settled_accounts(
+        result: &TransactionResult<()>,
+        settled: &mut Vec<Account>,
+    ) -> Vec<Account> {
+        let settled = std::mem::take(settled);
+        match result {
+            Ok(()) => settled,
+            Err(_) => Vec::new(),
+        }
+    }
+
+    fn transact_with_retries(
+        &mut self,
+        transaction: &Transaction,
+        progress: Option<Progress>,
+    ) -> TransactionResult<()> {
+        let mut attempts = 1;
+        loop {
+            match self.apply_atomically(transaction, progress) {
+                Err(err) if err.kind() == &TransactionErrorKind::Conflict => {
+                    if attempts == MAX_ATTEMPTS {
+                        return Err(err);
+                    }
+                    attempts += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn complete(
+        &mut self,
+        transaction: &Transaction,
+        result: TransactionResult<()>,
+    ) -> TransactionResult<()> {
+        /* Whatever was recorded before a storage failure was rolled back */
+        let recorded = std::mem::take(&mut self.recorded);
+        let automatic = std::mem::take(&mut self.automatic);
+        let (automatic, recorded) = match &result {
+            Err(err) if err.kind().is_storage() => (Vec::new(), Vec::new()),
+            _ => (automatic, recorded),
+        };
+
+        for automatic in automatic {
+            if let Some(audit_log) = self.audit_log.as_mut() {
+                audit_log.append(&automatic.transaction, &automatic.result)?;
+            }
+            Bank::<S>::notify(
+                &mut self.listeners,
+                &automatic.transaction,
+                &automatic.result,
+                &automatic.events,
+                &automatic.accounts,
+            )?;
+        }
+
+        if let Some(audit_log) = self.audit_log.as_mut() {
+            audit_log.append(transaction, &result)?;
+        }
+        if !self.listeners.is_empty() {
+            let accounts = Bank::<S>::settled_accounts(&result, &mut self.settled);
+            Bank::<S>::notify(
+                &mut self.listeners,
+                transaction,
+                &result,
+                &recorded,
+                &accounts,
+            )?;
+        }
+
+        result
+    }
+
+    /*
+     * Rejected transactions are still committed, as they advance the input position and claim
+     * the transaction id, only storage failures are rolled back, and retried when they conflicted
+     * with another process sharing the storage, every outcome is written to the audit log and
+     * given to the listeners
+     */
+    pub fn transact(&mut self, transaction: Transaction) -> TransactionResult<()> {
+        if let Some(timestamp) = transaction.timestamp {
+            self.advance_to(timestamp)?;
+        }
+
+        let result = self.transact_with_retries(&transaction, None);
+
+        self.complete(&transaction, result)
+    }
+
+    /*
+     * Advances the clock and pays the standing orders due by now, in milliseconds since the unix
+     * epoch, as withdrawals, one that is rejected (e.g. for lack of funds or a locked account) is
+     * skipped and, as any rejected transaction, written to the audit log and given to the listeners.
+     * Each accepted payment is stored with its changes, so on restart orders resume after their last
+     * accepted one, retrying those rejected since
+     */
+    pub fn advance_to(&mut self, now: u64) -> TransactionResult<()> {
+        self.clock.advance_to(now);
+        let storage = &self.storage;
+        match self.standing_orders.as_mut() {
+            Some(orders) => orders.resume(|index| storage.standing_order_paid(index))?,
+            None => return Ok(()),
+        }
+
+        while let Some((index, due)) = self
+            .standing_orders
+            .as_ref()
+            .and_then(|orders| orders.next_due(now))
+        {
+            let id = match self.next_bank_tx()? {
+                Some(id) => id,
+                None => break,
+            };
+            let transaction = match self
+                .standing_orders
+                .as_mut()
+                .and_then(|orders| orders.pay(index, id))
+            {
+                Some(transaction) => transaction,
+                None => break,
+            };
+
+            let result =
+                self.transact_with_retries(&transaction, Some(Progress::Paid(index as u64, due)));
+            match self.complete(&transaction, result) {
+                Err(err) if err.kind().is_storage() => return Err(err),
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /*
+     * Posts a day's interest on the available balance of every account that is not locked, as
+     * interest transactions numbered as the bank's own. The day is stored with each account's
+     * interest, so accounts that were already paid for it (or a later day) are skipped when it is
+     * run again. A rejected payment, as any rejected transaction, is written to the audit log and
+     * given to the listeners, and is paid by running it again, only storage failures stop it
+     */
+    pub fn accrue_interest(
+        &mut self,
+        policy: &InterestPolicy,
+        date: Date,
+    ) -> TransactionResult<()> {
+        let balances: Vec<(ClientId, Decimal)> = self
+            .storage
+            .accounts()?
+            .iter()
+            .filter(|account| !account.locked())
+            .map(|account| (account.client_id(), *account.available()))
+            .collect();
+
+        for (client_id, available) in balances {
+            if matches!(self.storage.interest_accrued(client_id)?, Some(accrued) if accrued >= date)
+            {
+                continue;
+            }
+            let interest = policy.interest(available, date)?;
+            if interest.is_zero() {
+                continue;
+            }
+
+            let id = self
+                .next_bank_tx()?
+                .ok_or_else(TransactionError::overflow)?;
+            let transaction = Transaction::new_interest(client_id, id, interest);
+            let result = self.transact_with_retries(&transaction, Some(Progress::Accrued(date)));
+            match self.complete(&transaction, result) {
+                Err(err) if err.kind().is_storage() => return Err(err),
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /*
+     * Transacts a record consumed at offset of the partition of a topic, committing the offset
+     * with it, returns Ok(false) without applying it when the offset has already been consumed,
+     * as happens when a log is redelivered after a restart. A malformed record (None) only
+     * advances the offset. Each partition must only be consumed by one bank at a time
+     */
+    pub fn consume(
+        &mut self,
+        topic: &str,
+        partition: u32,
+        offset: u64,
+        transaction: Option<Transaction>,
+    ) -> TransactionResult<bool> {
+        if offset < self.storage.consumer_offset(topic, partition)? {
+            return Ok(false);
+        }
+
+        if let Some(timestamp) = transaction
+            .as_ref()
+            .and_then(|transaction| transaction.timestamp)
+        {
+            self.advance_to(timestamp)?;
+        }
+
+        match transaction {
+            Some(transaction) => {
+                let result = self.transact_with_retries(
+                    &transaction,
+                    Some(Progress::Consumed(topic, partition, offset)),
+                );
+                self.complete(&transaction, result).map(|()| true)
+            }
+            None => {
+                self.storage.begin()?;
+                match self
+                    .storage
+                    .set_consumer_offset(topic, partition, offset + 1)
+                {
+                    Ok(()) => self.storage.commit()?,
+                    Err(err) => {
+                        self.storage.rollback()?;
+                        return Err(err);
+                    }
+                }
+
+                Ok(true)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::id::{client, tx};
+    use rust_decimal::Decimal;
+    use std::str::FromStr;
+    use std::sync::{Arc, Mutex};
+
+    fn decimal_str(decimal: &str) -> Decimal {
+        Decimal::from_str(decimal).unwrap()
+    }
+
+    fn logged_bank() -> Bank {
+        Bank::with_storage(MemoryStorage::with_event_log())
+    }
+
+    fn padding(bank: &mut Bank, client_id: u64, ids: std::ops::Range<u64>) {
+        for id in ids {
+            bank.transact(Transaction::new_deposit(
+                client(client_id),
+                tx(id),
+                decimal_str("0.1"),
+            ))
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_duplicate_transaction_id() {
+        let mut bank = Bank::new();
+        bank.transact(Transaction::new_deposit(client(1), tx(1), decimal_str("2")))
+            .unwrap();
+        let err = bank
+            .transact(Transaction::new_deposit(client(2), tx(1), decimal_str("2")))
+            .unwrap_err();
+
+        assert_eq!(&TransactionErrorKind::IncorrectClient, err.kind());
+        assert!(bank.account(client(2)).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_open_disputes() {
+        let mut bank = Bank::new();
+        bank.transact(Transaction::new_deposit(client(1), tx(1), decimal_str("2")))
+            .unwrap();
+        bank.transact(Transaction::new_deposit(client(2), tx(2), decimal_str("3")))
+            .unwrap();
+        bank.transact(Transaction::new_dispute(client(1), tx(1)))
+            .unwrap();
+        bank.transact(Transaction::new_dispute(client(2), tx(2)))
+            .unwrap();
+        bank.transact(Transaction::new_resolve(client(2), tx(2)))
+            .unwrap();
+        padding(&mut bank, 3, 3..5);
+
+        assert_eq!(
+            vec![OpenDispute {
+                client_id: client(1),
+                id: tx(1),
+                amount: decimal_str("2"),
+                opened: 2,
+                age: 5,
+            }],
+            bank.open_disputes().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_redisputed_age() {
+        let mut bank = Bank::new();
+        bank.transact(Transaction::new_deposit(client(1), tx(1), decimal_str("2")))
+            .unwrap();
+        bank.transact(Transaction::new_dispute(client(1), tx(1)))
+            .unwrap();
+        bank.transact(Transaction::new_resolve(client(1), tx(1)))
+            .unwrap();
+        bank.transact(Transaction::new_dispute(client(1), tx(1)))
+            .unwrap();
+
+        let disputes = bank.open_disputes().unwrap();
+        assert_eq!(1, disputes.len());
+        assert_eq!(3, disputes[0].opened);
+        assert_eq!(1, disputes[0].age);
+    }
+
+    #[test]
+    fn test_auto_resolve_policy() {
+        let mut bank =
+            Bank::new().with_dispute_policy(DisputePolicy::new(2, DisputeAction::Resolve));
+        bank.transact(Transaction::new_deposit(client(1), tx(1), decimal_str("2")))
+            .unwrap();
+        bank.transact(Transaction::new_dispute(client(1), tx(1)))
+            .unwrap();
+        padding(&mut bank, 2, 2..4);
+
+        let account = bank.account(client(1)).unwrap().unwrap();
+        assert!(account.transaction(&tx(1)).unwrap().disputed);
+        assert_eq!(1, bank.open_disputes().unwrap().len());
+
+        padding(&mut bank, 2, 4..5);
+
+        let account = bank.account(client(1)).unwrap().unwrap();
+        assert!(!account.transaction(&tx(1)).unwrap().disputed);
+        assert_eq!(&decimal_str("2"), account.available());
+        assert!(bank.open_disputes().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_authorization_expiry() {
+        let mut bank = Bank::new().with_authorization_expiry(2);
+        bank.transact(Transaction::new_deposit(client(1), tx(1), decimal_str("5")))
+            .unwrap();
+        bank.transact(Transaction::new_authorize(
+            client(1),
+            tx(2),
+            decimal_str("3"),
+        ))
+        .unwrap();
+        bank.transact(Transaction::new_authorize(
+            client(1),
+            tx(3),
+            decimal_str("1"),
+        ))
+        .unwrap();
+        bank.transact(Transaction::new_capture(client(1), tx(3), None))
+            .unwrap();
+
+        let account = bank.account(client(1)).unwrap().unwrap();
+        assert_eq!(&decimal_str("3"), account.authorized());
+        assert_eq!(&decimal_str("1"), account.available());
+
+        padding(&mut bank, 2, 4..5);
+
+        let account = bank.account(client(1)).unwrap().unwrap();
+        assert_eq!(&Decimal::new(0, 0), account.authorized());
+        assert_eq!(&Decimal::new(0, 0), account.held());
+        assert_eq!(&decimal_str("4"), account.available());
+        assert_eq!(&decimal_str("4"), account.total());
+        let err = bank
+            .transact(Transaction::new_capture(client(1), tx(2), None))
+            .unwrap_err();
+        assert_eq!(&TransactionErrorKind::NotAuthorized, err.kind());
+    }
+
+    #[test]
+    fn test_transfer() {
+        let mut bank = Bank::new();
+        bank.transact(Transaction::new_deposit(client(1), tx(1), decimal_str("5")))
+            .unwrap();
+        bank.transact(Transaction::new_transfer(
+            client(1),
+            tx(2),
+            client(2),
+            decimal_str("3"),
+        ))
+        .unwrap();
+
+        let err = bank
+            .transact(Transaction::new_transfer(
+                client(1),
+                tx(3),
+                client(2),
+                decimal_str("3"),
+            ))
+            .unwrap_err();
+        assert_eq!(&TransactionErrorKind::NegativeBalance, err.kind());
+        let err = bank
+            .transact(Transaction::new_transfer(
+                client(1),
+                tx(4),
+                client(1),
+                decimal_str("1"),
+            ))
+            .unwrap_err();
+        assert_eq!(&TransactionErrorKind::InvalidTransfer, err.kind());
+
+        let source = bank.account(client(1)).unwrap().unwrap();
+        assert_eq!(&decimal_str("2"), source.total());
+        let destination = bank.account(client(2)).unwrap().unwrap();
+        assert_eq!(&decimal_str("3"), destination.available());
+        assert_eq!(&decimal_str("3"), destination.total());
+
+        /* A transfer to a locked account is rejected without debiting the source */
+        bank.transact(Transaction::new_deposit(client(3), tx(5), decimal_str("1")))
+            .unwrap();
+        bank.transact(Transaction::new_dispute(client(3), tx(5)))
+            .unwrap();
+        bank.transact(Transaction::new_chargeback(client(3), tx(5)))
+            .unwrap();
+        let err = bank
+            .transact(Transaction::new_transfer(
+                client(1),
+                tx(6),
+                client(3),
+                decimal_str("1"),
+            ))
+            .unwrap_err();
+        assert_eq!(&TransactionErrorKind::Locked, err.kind());
+        assert_eq!(
+            &decimal_str("2"),
+            bank.account(client(1)).unwrap().unwrap().total()
+        );
+        assert!(bank
+            .account(client(1))
+            .unwrap()
+            .unwrap()
+            .transaction(&tx(6))
+            .is_none());
+    }
+
+    /* A negative transfer would take funds from the destination, which never agreed to it */
+    #[test]
+    fn test_non_positive_transfer() {
+        let mut bank = Bank::new();
+        bank.transact(Transaction::new_deposit(
+            client(1),
+            tx(1),
+            decimal_str("10"),
+        ))
+        .unwrap();
+
+        for (id, amount) in [(2, "-50"), (3, "0")] {
+            let err = bank
+                .transact(Transaction::new_transfer(
+                    client(1),
+                    tx(id),
+                    client(2),
+                    decimal_str(amount),
+                ))
+                .unwrap_err();
+            assert_eq!(&TransactionErrorKind::NonPositiveAmount, err.kind());
+        }
+
+        assert_eq!(
+            &decimal_str("10"),
+            bank.account(client(1)).unwrap().unwrap().total()
+        );
+        assert!(bank.account(client(2)).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_transfer_dispute() {
+        let mut bank = logged_bank();
+        bank.transact(Transaction::new_deposit(client(1), tx(1), decimal_str("5")))
+            .unwrap();
+        bank.transact(Transaction::new_transfer(
+            client(1),
+            tx(2),
+            client(2),
+            decimal_str("3"),
+        ))
+        .unwrap();
+
+        /* Only the source can dispute, the funds are held by the destination */
+        let err = bank
+            .transact(Transaction::new_dispute(client(2), tx(2)))
+            .unwrap_err();
+        assert_eq!(&TransactionErrorKind::IncorrectClient, err.kind());
+        bank.transact(Transaction::new_dispute(client(1), tx(2)))
+            .unwrap();
+        let destination = bank.account(client(2)).unwrap().unwrap();
+        assert_eq!(&decimal_str("0"), destination.available());
+        assert_eq!(&decimal_str("3"), destination.held());
+        assert_eq!(1, bank.open_disputes().unwrap().len());
+
+        bank.transact(Transaction::new_resolve(client(1), tx(2)))
+            .unwrap();
+        assert_eq!(
+            &decimal_str("3"),
+            bank.account(client(2)).unwrap().unwrap().available()
+        );
+
+        bank.transact(Transaction::new_dispute(client(1), tx(2)))
+            .unwrap();
+        bank.transact(Transaction::new_chargeback(client(1), tx(2)))
+            .unwrap();
+
+        let source = bank.account(client(1)).unwrap().unwrap();
+        assert_eq!(&decimal_str("5"), source.available());
+        assert_eq!(&decimal_str("5"), source.total());
+        assert!(!source.locked());
+        let destination = bank.account(client(2)).unwrap().unwrap();
+        assert_eq!(&decimal_str("0"), destination.total());
+        assert_eq!(&decimal_str("0"), destination.held());
+        assert!(destination.locked());
+        assert!(bank.open_disputes().unwrap().is_empty());
+
+        let err = bank
+            .transact(Transaction::new_dispute(client(1), tx(2)))
+            .unwrap_err();
+        assert_eq!(&TransactionErrorKind::AlreadyReversed, err.kind());
+
+        /* Both legs are in the event log of their own client */
+        let kinds = |client_id| -> Vec<&str> {
+            bank.storage()
+                .events(client_id)
+                .unwrap()
+                .iter()
+                .map(|event| event.event.kind())
+                .collect()
+        };
+        assert_eq!(
+            vec![
+                "transferred_in",
+                "dispute_opened",
+                "dispute_resolved",
+                "dispute_opened",
+                "charged_back",
+                "locked"
+            ],
+            kinds(client(2))
+        );
+        assert_eq!(Some(&"transfer_reversed"), kinds(client(1)).last());
+    }
+
+    #[test]
+    fn test_fees() {
+        use crate::fee::{Fee, FeeSchedule};
+
+        let schedule = FeeSchedule::new()
+            .with_fee(
+                TransactionType::Withdrawal,
+                Fee::tiered(vec![
+                    (Decimal::ZERO, Fee::Flat(decimal_str("0.5"))),
+                    (decimal_str("100"), Fee::Percentage(decimal_str("1"))),
+                ]),
+            )
+            .with_fee(TransactionType::Chargeback, Fee::Flat(decimal_str("15")))
+            .with_client_fee(
+                client(2),
+                TransactionType::Withdrawal,
+                Fee::Flat(Decimal::ZERO),
+            );
+        let mut bank = logged_bank().with_fee_schedule(schedule);
+        bank.transact(Transaction::new_deposit(
+            client(1),
+            tx(1),
+            decimal_str("300"),
+        ))
+        .unwrap();
+        bank.transact(Transaction::new_withdrawal(
+            client(1),
+            tx(2),
+            decimal_str("10"),
+        ))
+        .unwrap();
+        bank.transact(Transaction::new_withdrawal(
+            client(1),
+            tx(3),
+            decimal_str("200"),
+        ))
+        .unwrap();
+
+        /* The fee has to be covered along with the withdrawal */
+        let err = bank
+            .transact(Transaction::new_withdrawal(
+                client(1),
+                tx(4),
+                decimal_str("87.5"),
+            ))
+            .unwrap_err();
+        assert_eq!(&TransactionErrorKind::NegativeBalance, err.kind());
+
+        let account = bank.account(client(1)).unwrap().unwrap();
+        assert_eq!(&decimal_str("87.5"), account.available());
+        assert_eq!(&decimal_str("2.5"), account.fees());
+        assert!(account.transaction(&tx(4)).is_none());
+
+        bank.transact(Transaction::new_deposit(
+            client(2),
+            tx(5),
+            decimal_str("10"),
+        ))
+        .unwrap();
+        bank.transact(Transaction::new_withdrawal(
+            client(2),
+            tx(6),
+            decimal_str("10"),
+        ))
+        .unwrap();
+        bank.transact(Transaction::new_deposit(
+            client(3),
+            tx(7),
+            decimal_str("10"),
+        ))
+        .unwrap();
+        bank.transact(Transaction::new_dispute(client(3), tx(7)))
+            .unwrap();
+        bank.transact(Transaction::new_chargeback(client(3), tx(7)))
+            .unwrap();
+
+        assert_eq!(
+            &Decimal::ZERO,
+            bank.account(client(2)).unwrap().unwrap().fees()
+        );
+        let charged_back = bank.account(client(3)).unwrap().unwrap();
+        assert_eq!(&decimal_str("-15"), charged_back.available());
+        assert_eq!(&decimal_str("-15"), charged_back.total());
+        assert!(charged_back.locked());
+        assert_eq!(decimal_str("17.5"), bank.revenue().unwrap());
+
+        let events = bank.storage().events(client(3)).unwrap();
+        assert_eq!(
+            Some(&AccountEvent::FeeCharged {
+                id: tx(7),
+                amount: decimal_str("15")
+            }),
+            events.last().map(|event| &event.event)
+        );
+    }
+
+    #[test]
+    fn test_accrue_interest() {
+        use crate::interest::DayCount;
+
+        let policy = InterestPolicy::new(DayCount::Actual360)
+            .with_rate(Decimal::ZERO, decimal_str("3.6"))
+            .with_rate(decimal_str("1000"), decimal_str("7.2"));
+        let mut bank = logged_bank();
+        bank.transact(Transaction::new_deposit(
+            client(1),
+            tx(1),
+            decimal_str("100"),
+        ))
+        .unwrap();
+        bank.transact(Transaction::new_deposit(
+            client(2),
+            tx(2),
+            decimal_str("1000"),
+        ))
+        .unwrap();
+        bank.transact(Transaction::new_deposit(
+            client(3),
+            tx(3),
+            decimal_str("100"),
+        ))
+        .unwrap();
+        bank.transact(Transaction::new_dispute(client(3), tx(3)))
+            .unwrap();
+        bank.transact(Transaction::new_chargeback(client(3), tx(3)))
+            .unwrap();
+
+        let date = "2023-06-01".parse().unwrap();
+        bank.accrue_interest(&policy, date).unwrap();
+
+        let account = bank.account(client(1)).unwrap().unwrap();
+        assert_eq!(&decimal_str("100.01"), account.available());
+        assert_eq!(&decimal_str("100.01"), account.total());
+        assert_eq!(
+            &decimal_str("1000.2"),
+            bank.account(client(2)).unwrap().unwrap().available()
+        );
+        assert_eq!(
+            &Decimal::ZERO,
+            bank.account(client(3)).unwrap().unwrap().total()
+        );
+
+        let interest = account
+            .transactions()
+            .find(|(_, tx)| tx.t_type == TransactionType::Interest);
+        let id = *interest.unwrap().0;
+        /* Clients 1 and 2 accrue, in the order their accounts are stored */
+        assert!([tx(DEFAULT_BANK_TX), tx(DEFAULT_BANK_TX + 1)].contains(&id));
+        /* The dispute could not be applied, nor can input refer to the bank's transactions */
+        let err = account
+            .decide(&Transaction::new_dispute(client(1), id))
+            .unwrap_err();
+        assert_eq!(&TransactionErrorKind::NotDisputable, err.kind());
+        let err = bank
+            .transact(Transaction::new_dispute(client(1), id))
+            .unwrap_err();
+        assert_eq!(&TransactionErrorKind::ReservedId, err.kind());
+        let events = bank.storage().events(client(1)).unwrap();
+        assert_eq!(
+            Some(&AccountEvent::InterestAccrued {
+                id,
+                amount: decimal_str("0.01")
+            }),
+            events.last().map(|event| &event.event)
+        );
+    }
+
+    #[test]
+    fn test_accrue_interest_once_a_day() {
+        use crate::interest::DayCount;
+
+        let policy =
+            InterestPolicy::new(DayCount::Actual360).with_rate(Decimal::ZERO, decimal_str("3.6"));
+        let total = |bank: &Bank| -> Decimal {
+            bank.accounts()
+                .unwrap()
+                .iter()
+                .map(|account| *account.total())
+                .sum()
+        };
+        let mut bank = Bank::new();
+        bank.transact(Transaction::new_deposit(
+            client(1),
+            tx(1),
+            decimal_str("100"),
+        ))
+        .unwrap();
+        bank.transact(Transaction::new_deposit(
+            client(2),
+            tx(2),
+            decimal_str("100"),
+        ))
+        .unwrap();
+
+        let date: Date = "2023-06-01".parse().unwrap();
+        bank.accrue_interest(&policy, date).unwrap();
+        assert_eq!(decimal_str("200.02"), total(&bank));
+        bank.accrue_interest(&policy, date).unwrap();
+        assert_eq!(decimal_str("200.02"), total(&bank));
+
+        bank.accrue_interest(&policy, date.next()).unwrap();
+        assert_eq!(decimal_str("200.04"), total(&bank));
+        /* Each posting took the next of the bank's ids */
+        let account = bank.account(client(1)).unwrap().unwrap();
+        assert_eq!(
+            2,
+            account
+                .transactions()
+                .filter(|(id, _)| id.sequence() >= Some(DEFAULT_BANK_TX))
+                .count()
+        );
+    }
+
+    #[test]
+    fn test_overdraft_limits() {
+        let limits = OverdraftLimits::new()
+            .with_default(decimal_str("1"))
+            .with_limit(client(2), decimal_str("100"));
+        let mut bank = Bank::new().with_overdraft_limits(limits);
+        bank.transact(Transaction::new_deposit(client(1), tx(1), decimal_str("5")))
+            .unwrap();
+        let err = bank
+            .transact(Transaction::new_withdrawal(
+                client(1),
+                tx(2),
+                decimal_str("7"),
+            ))
+            .unwrap_err();
+        assert_eq!(&TransactionErrorKind::NegativeBalance, err.kind());
+        bank.transact(Transaction::new_withdrawal(
+            client(1),
+            tx(3),
+            decimal_str("6"),
+        ))
+        .unwrap();
+
+        /* A transfer may be funded by the overdraft too */
+        bank.transact(Transaction::new_transfer(
+            client(2),
+            tx(4),
+            client(1),
+            decimal_str("50"),
+        ))
+        .unwrap();
+        let source = bank.account(client(2)).unwrap().unwrap();
+        assert_eq!(&decimal_str("-50"), source.available());
+        assert_eq!(&decimal_str("50"), source.overdraft());
+        assert_eq!(&Decimal::ZERO, source.debt());
+        assert_eq!(
+            &decimal_str("49"),
+            bank.account(client(1)).unwrap().unwrap().available()
+        );
+    }
+
+    #[test]
+    fn test_standing_orders() {
+        use crate::schedule::StandingOrder;
+
+        const DAY: u64 = 86_400_000;
+        let order = |client_id| StandingOrder {
+            client_id,
+            amount: decimal_str("10"),
+            schedule: "every 1d".parse().unwrap(),
+            start: DAY,
+            end: Some(3 * DAY),
+        };
+        let orders = StandingOrders::new()
+            .with_order(order(client(1)))
+            .with_order(order(client(2)));
+        let mut bank = Bank::new()
+            .with_bank_tx(1_000)
+            .unwrap()
+            .with_standing_orders(orders);
+        let at = |transaction: Transaction, timestamp| Transaction {
+            timestamp: Some(timestamp),
+            ..transaction
+        };
+
+        bank.transact(at(
+            Transaction::new_deposit(client(1), tx(1), decimal_str("15")),
+            0,
+        ))
+        .unwrap();
+        bank.transact(at(
+            Transaction::new_deposit(client(2), tx(2), decimal_str("50")),
+            0,
+        ))
+        .unwrap();
+        bank.transact(Transaction::new_dispute(client(2), tx(2)))
+            .unwrap();
+        bank.transact(Transaction::new_chargeback(client(2), tx(2)))
+            .unwrap();
+        /* Pays client 1 on the first day, the second is short of funds and client 2 is locked */
+        bank.transact(at(
+            Transaction::new_deposit(client(3), tx(3), decimal_str("1")),
+            2 * DAY,
+        ))
+        .unwrap();
+
+        let account = bank.account(client(1)).unwrap().unwrap();
+        assert_eq!(&decimal_str("5"), account.available());
+        assert!(account.transaction(&tx(1_000)).is_some());
+        assert!(account.transaction(&tx(1_002)).is_none());
+        assert_eq!(
+            &Decimal::ZERO,
+            bank.account(client(2)).unwrap().unwrap().total()
+        );
+        /* Only accepted payments are stored */
+        assert_eq!(Some(DAY), bank.storage().standing_order_paid(0).unwrap());
+        assert_eq!(None, bank.storage().standing_order_paid(1).unwrap());
+
+        /* Nothing is paid after the end */
+        bank.transact(Transaction::new_deposit(
+            client(1),
+            tx(4),
+            decimal_str("100"),
+        ))
+        .unwrap();
+        bank.advance_to(10 * DAY).unwrap();
+        assert_eq!(
+            &decimal_str("95"),
+            bank.account(client(1)).unwrap().unwrap().available()
+        );
+    }
+
+    #[test]
+    fn test_bank_tx_is_reserved() {
+        use crate::schedule::StandingOrder;
+
+        let orders = StandingOrders::new().with_order(StandingOrder {
+            client_id: client(1),
+            amount: decimal_str("10"),
+            schedule: "every 1d".parse().unwrap(),
+            start: 1_000,
+            end: None,
+        });
+        let mut bank = Bank::new()
+            .with_bank_tx(1_000)
+            .unwrap()
+            .with_standing_orders(orders);
+
+        let err = bank
+            .transact(Transaction::new_deposit(
+                client(1),
+                tx(1_000),
+                decimal_str("100"),
+            ))
+            .unwrap_err();
+        assert_eq!(&TransactionErrorKind::ReservedId, err.kind());
+        bank.transact(Transaction::new_deposit(
+            client(1),
+            tx(999),
+            decimal_str("100"),
+        ))
+        .unwrap();
+
+        /* The payment takes the id the input could not */
+        bank.advance_to(1_000).unwrap();
+        let account = bank.account(client(1)).unwrap().unwrap();
+        assert_eq!(&decimal_str("90"), account.available());
+        assert!(account.transaction(&tx(1_000)).is_some());
+        assert_eq!(Some(1_000), bank.storage().standing_order_paid(0).unwrap());
+    }
+
+    #[test]
+    fn test_auto_chargeback_policy() {
+        let mut bank =
+            Bank::new().with_dispute_policy(DisputePolicy::new(0, DisputeAction::Chargeback));
+        bank.transact(Transaction::new_deposit(client(1), tx(1), decimal_str("2")))
+            .unwrap();
+        bank.transact(Transaction::new_dispute(client(1), tx(1)))
+            .unwrap();
+        padding(&mut bank, 2, 2..3);
+
+        let account = bank.account(client(1)).unwrap().unwrap();
+        assert_eq!(&Decimal::new(0, 0), account.total());
+        assert!(bank.open_disputes().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_policy_drops_disputes_of_locked_accounts() {
+        let mut bank =
+            Bank::new().with_dispute_policy(DisputePolicy::new(3, DisputeAction::Resolve));
+        bank.transact(Transaction::new_deposit(client(1), tx(1), decimal_str("2")))
+            .unwrap();
+        bank.transact(Transaction::new_deposit(client(1), tx(2), decimal_str("3")))
+            .unwrap();
+        bank.transact(Transaction::new_dispute(client(1), tx(1)))
+            .unwrap();
+        bank.transact(Transaction::new_dispute(client(1), tx(2)))
+            .unwrap();
+        bank.transact(Transaction::new_chargeback(client(1), tx(2)))
+            .unwrap();
+        padding(&mut bank, 2, 5..8);
+
+        let account = bank.account(client(1)).unwrap().unwrap();
+        assert!(account.locked());
+        assert!(account.transaction(&tx(1)).unwrap().disputed);
+        assert!(bank.open_disputes().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_policy_ignores_resolved_disputes() {
+        let mut bank =
+            Bank::new().with_dispute_policy(DisputePolicy::new(1, DisputeAction::Chargeback));
+        bank.transact(Transaction::new_deposit(client(1), tx(1), decimal_str("2")))
+            .unwrap();
+        bank.transact(Transaction::new_dispute(client(1), tx(1)))
+            .unwrap();
+        bank.transact(Transaction::new_resolve(client(1), tx(1)))
+            .unwrap();
+        padding(&mut bank, 2, 2..5);
+
+        let account = bank.account(client(1)).unwrap().unwrap();
+        assert_eq!(&decimal_str("2"), account.total());
+        assert_eq!(&decimal_str("2"), account.available());
+    }
+
+    #[test]
+    fn test_event_log_is_opt_in() {
+        let mut bank = Bank::new();
+        bank.transact(Transaction::new_deposit(client(1), tx(1), decimal_str("5")))
+            .unwrap();
+
+        assert!(bank.events(client(1)).is_err());
+        assert!(bank.account_at(client(1), AsOf::Sequence(0)).is_err());
+        assert_eq!(
+            &decimal_str("5"),
+            bank.account(client(1)).unwrap().unwrap().available()
+        );
+    }
+
+    #[test]
+    fn test_account_at() {
+        let mut bank = logged_bank();
+        bank.transact(Transaction::new_deposit(client(1), tx(1), decimal_str("2")))
+            .unwrap();
+        bank.transact(Transaction::new_deposit(client(2), tx(2), decimal_str("3")))
+            .unwrap();
+        bank.transact(Transaction::new_withdrawal(
+            client(1),
+            tx(3),
+            decimal_str("5"),
+        ))
+        .unwrap_err();
+        bank.transact(Transaction::new_dispute(client(1), tx(1)))
+            .unwrap();
+        bank.transact(Transaction::new_chargeback(client(1), tx(1)))
+            .unwrap();
+
+        assert!(bank
+            .account_at(client(2), AsOf::Sequence(0))
+            .unwrap()
+            .is_none());
+
+        let account = bank
+            .account_at(client(1), AsOf::Sequence(2))
+            .unwrap()
+            .unwrap();
+        assert_eq!(&decimal_str("2"), account.available());
+        assert!(!account.locked());
+
+        let account = bank
+            .account_at(client(1), AsOf::Sequence(3))
+            .unwrap()
+            .unwrap();
+        assert_eq!(&decimal_str("0"), account.available());
+        assert_eq!(&decimal_str("2"), account.held());
+        assert!(account.transaction(&tx(1)).unwrap().disputed);
+
+        let account = bank
+            .account_at(client(1), AsOf::Sequence(4))
+            .unwrap()
+            .unwrap();
+        let current = bank.account(client(1)).unwrap().unwrap();
+        assert_eq!(current.total(), account.total());
+        assert_eq!(current.held(), account.held());
+        assert!(account.locked());
+        assert_eq!(
+            account.total(),
+            bank.account_at(client(1), AsOf::Timestamp(u64::MAX))
+                .unwrap()
+                .unwrap()
+                .total()
+        );
+        assert!(bank
+            .account_at(client(1), AsOf::Timestamp(0))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_account_at_with_overdraft_limit() {
+        let limits = OverdraftLimits::new().with_limit(client(1), decimal_str("50"));
+        let mut bank = logged_bank().with_overdraft_limits(limits);
+        bank.transact(Transaction::new_deposit(
+            client(1),
+            tx(1),
+            decimal_str("10"),
+        ))
+        .unwrap();
+        bank.transact(Transaction::new_withdrawal(
+            client(1),
+            tx(2),
+            decimal_str("50"),
+        ))
+        .unwrap();
+
+        let replayed = bank
+            .account_at(client(1), AsOf::Timestamp(u64::MAX))
+            .unwrap()
+            .unwrap();
+        let live = bank.account(client(1)).unwrap().unwrap();
+        assert_eq!(&decimal_str("40"), replayed.overdraft());
+        assert_eq!(&Decimal::ZERO, replayed.debt());
+        assert_eq!(live.overdraft(), replayed.overdraft());
+        assert_eq!(live.debt(), replayed.debt());
+    }
+
+    #[test]
+    fn test_simulated_clock() {
+        use crate::clock::SimulatedClock;
+
+        let mut bank = logged_bank().with_clock(SimulatedClock::new(1_000));
+        let at = |transaction: Transaction, timestamp| Transaction {
+            timestamp: Some(timestamp),
+            ..transaction
+        };
+
+        bank.transact(Transaction::new_deposit(client(1), tx(1), decimal_str("2")))
+            .unwrap();
+        bank.transact(at(
+            Transaction::new_deposit(client(1), tx(2), decimal_str("3")),
+            5_000,
+        ))
+        .unwrap();
+        /* An earlier timestamp does not move the clock back */
+        bank.transact(at(
+            Transaction::new_deposit(client(1), tx(3), decimal_str("4")),
+            2_000,
+        ))
+        .unwrap();
+        bank.advance_to(9_000).unwrap();
+        bank.transact(Transaction::new_withdrawal(
+            client(1),
+            tx(4),
+            decimal_str("1"),
+        ))
+        .unwrap();
+
+        assert_eq!(9_000, bank.now());
+        let timestamps: Vec<u64> = bank
+            .storage()
+            .events(client(1))
+            .unwrap()
+            .iter()
+            .map(|event| event.timestamp)
+            .collect();
+        assert_eq!(vec![1_000, 5_000, 5_000, 9_000], timestamps);
+
+        let account = bank
+            .account_at(client(1), AsOf::Timestamp(4_999))
+            .unwrap()
+            .unwrap();
+        assert_eq!(&decimal_str("2"), account.total());
+        let account = bank
+            .account_at(client(1), AsOf::Timestamp(5_000))
+            .unwrap()
+            .unwrap();
+        assert_eq!(&decimal_str("9"), account.total());
+    }
 
-    pub fn accounts(&self) -> &AHashMap<u16, Account> {
-        &self.accounts
+    #[test]
+    fn test_policy_events_are_recorded() {
+        let mut bank =
+            logged_bank().with_dispute_policy(DisputePolicy::new(0, DisputeAction::Chargeback));
+        bank.transact(Transaction::new_deposit(client(1), tx(1), decimal_str("2")))
+            .unwrap();
+        bank.transact(Transaction::new_dispute(client(1), tx(1)))
+            .unwrap();
+        padding(&mut bank, 2, 2..3);
+
+        let events = bank.storage().events(client(1)).unwrap();
+        assert_eq!(4, events.len());
+        assert_eq!(2, events[2].sequence);
+        assert_eq!(
+            AccountEvent::ChargedBack {
+                id: tx(1),
+                amount: decimal_str("2")
+            },
+            events[2].event
+        );
+        assert_eq!(AccountEvent::Locked, events[3].event);
     }
 
-    fn get_or_insert_client_id(&mut self, transaction: &Transaction) -> u16 {
-        *self
-            .transactions
-            .entry(transaction.id)
-            .or_insert_with(|| transaction.client_id)
+    /* Transaction id, whether it was applied and the available balance after it */
+    type Notification = (TransactionId, bool, Option<Decimal>);
+
+    struct Recorder(Arc<Mutex<Vec<Notification>>>);
+
+    impl TransactionListener for Recorder {
+        fn on_transaction(
+            &mut self,
+            transaction: &Transaction,
+            result: &TransactionResult<()>,
+            accounts: &[Account],
+        ) -> TransactionResult<()> {
+            self.0.lock().unwrap().push((
+                transaction.id,
+                result.is_ok(),
+                accounts.first().map(|account| *account.available()),
+            ));
+
+            Ok(())
+        }
     }
 
-    fn duplicate_transaction_id(&mut self, transaction: &Transaction) -> bool {
-        self.get_or_insert_client_id(transaction) != transaction.client_id
+    #[test]
+    fn test_policy_is_notified() {
+        let notifications = Arc::new(Mutex::new(Vec::new()));
+        let mut bank = Bank::new()
+            .with_dispute_policy(DisputePolicy::new(1, DisputeAction::Resolve))
+            .with_authorization_expiry(1)
+            .with_listener(Recorder(notifications.clone()));
+        bank.transact(Transaction::new_deposit(client(1), tx(1), decimal_str("5")))
+            .unwrap();
+        bank.transact(Transaction::new_dispute(client(1), tx(1)))
+            .unwrap();
+        bank.transact(Transaction::new_deposit(client(2), tx(2), decimal_str("5")))
+            .unwrap();
+        bank.transact(Transaction::new_authorize(
+            client(2),
+            tx(3),
+            decimal_str("2"),
+        ))
+        .unwrap();
+        padding(&mut bank, 3, 4..6);
+
+        assert_eq!(
+            vec![
+                (tx(1), true, Some(decimal_str("5"))),
+                (tx(1), true, Some(decimal_str("0"))),
+                (tx(2), true, Some(decimal_str("5"))),
+                (tx(1), true, Some(decimal_str("5"))),
+                (tx(3), true, Some(decimal_str("3"))),
+                (tx(4), true, Some(decimal_str("0.1"))),
+                (tx(3), true, Some(decimal_str("5"))),
+                (tx(5), true, Some(decimal_str("0.2"))),
+            ],
+            *notifications.lock().unwrap()
+        );
     }
 
-    pub fn transact(&mut self, transaction: Transaction) {
-        /*
-         *   TODO: ignore result, should most likely implement a file system log here to output
-         *   any errors, rather than ignoring them as STDOUT is used to output the csv
-         */
-        if self.duplicate_transaction_id(&transaction) {
-            return;
+    #[test]
+    fn test_listener() {
+        let notifications = Arc::new(Mutex::new(Vec::new()));
+        let mut bank = Bank::new().with_listener(Recorder(notifications.clone()));
+        bank.transact(Transaction::new_deposit(client(1), tx(1), decimal_str("2")))
+            .unwrap();
+        bank.transact(Transaction::new_withdrawal(
+            client(1),
+            tx(2),
+            decimal_str("3"),
+        ))
+        .unwrap_err();
+
+        assert_eq!(
+            vec![(tx(1), true, Some(decimal_str("2"))), (tx(2), false, None)],
+            *notifications.lock().unwrap()
+        );
+    }
+
+    /* The client and available balance of every account a transaction touched */
+    type Touches = Vec<(ClientId, Decimal)>;
+
+    struct Touched(Arc<Mutex<Vec<Touches>>>);
+
+    impl TransactionListener for Touched {
+        fn on_transaction(
+            &mut self,
+            _transaction: &Transaction,
+            _result: &TransactionResult<()>,
+            accounts: &[Account],
+        ) -> TransactionResult<()> {
+            self.0.lock().unwrap().push(
+                accounts
+                    .iter()
+                    .map(|account| (account.client_id(), *account.available()))
+                    .collect(),
+            );
+
+            Ok(())
         }
+    }
+
+    #[test]
+    fn test_listener_is_given_every_account_touched() {
+        let notifications = Arc::new(Mutex::new(Vec::new()));
+        let mut bank = Bank::new().with_listener(Touched(notifications.clone()));
+        bank.transact(Transaction::new_deposit(client(1), tx(1), decimal_str("5")))
+            .unwrap();
+        bank.transact(Transaction::new_transfer(
+            client(1),
+            tx(2),
+            client(2),
+            decimal_str("2"),
+        ))
+        .unwrap();
+        bank.transact(Transaction::new_dispute(client(1), tx(2)))
+            .unwrap();
 
-        let _ = self
-            .accounts
-            .entry(transaction.client_id)
-            .or_insert_with(|| Account::new(transaction.client_id))
-            .transact(transaction);
+        let notifications = notifications.lock().unwrap();
+        assert_eq!(vec![(client(1), decimal_str("5"))], notifications[0]);
+        assert_eq!(
+            vec![(client(1), decimal_str("3")), (client(2), decimal_str("2"))],
+            notifications[1]
+        );
+        assert_eq!(2, notifications[2].len());
+        assert_eq!(client(2), notifications[2][1].0);
     }
 }
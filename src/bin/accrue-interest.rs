@@ -0,0 +1,67 @@
+/* Accounts are only kept between runs by the storage features, without them this only errors */
+#![cfg_attr(
+    not(any(feature = "sqlite", feature = "postgres")),
+    allow(dead_code, unused_imports)
+)]
+
+use payments::audit::AuditLog;
+use payments::bank::Bank;
+use payments::interest::{Date, DayCount, InterestPolicy};
+use payments::output::output_bank;
+#[cfg(feature = "postgres")]
+use payments::storage::postgres::PostgresStorage;
+#[cfg(feature = "sqlite")]
+use payments::storage::sqlite::SqliteStorage;
+use payments::storage::Storage;
+use std::error::Error;
+use std::{env, io};
+
+const USAGE: &str = "usage: accrue-interest RATES DATE";
+const DEFAULT_DAY_COUNT: &str = "actual/365";
+
+/*
+ * Posts a day's interest to the accounts in storage that were not yet paid for it, writing them
+ * to STDOUT
+ */
+fn run<S>(mut bank: Bank<S>) -> Result<(), Box<dyn Error>>
+where
+    S: Storage,
+{
+    if let Some(file_path) = env::var_os("PAYMENTS_AUDIT_LOG") {
+        bank = bank.with_audit_log(AuditLog::open(file_path)?);
+    }
+
+    /* The same as the bank's other processes, so that interest never takes an input id */
+    if let Ok(first_tx) = env::var("PAYMENTS_BANK_TX") {
+        bank = bank.with_bank_tx(first_tx.parse()?)?;
+    }
+
+    let day_count: DayCount = env::var("PAYMENTS_DAY_COUNT")
+        .unwrap_or_else(|_| DEFAULT_DAY_COUNT.to_string())
+        .parse()?;
+    let rates = env::args_os().nth(1).ok_or(USAGE)?;
+    let date: Date = env::args().nth(2).ok_or(USAGE)?.parse()?;
+
+    let policy = InterestPolicy::open(rates, day_count)?;
+    bank.accrue_interest(&policy, date)?;
+
+    output_bank(&bank, io::stdout())?;
+
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    #[cfg(feature = "postgres")]
+    if let Ok(params) = env::var("PAYMENTS_POSTGRES") {
+        return run(Bank::with_storage(PostgresStorage::connect(&params)?));
+    }
+
+    #[cfg(feature = "sqlite")]
+    if let Some(file_path) = env::var_os("PAYMENTS_SQLITE") {
+        return run(Bank::with_storage(SqliteStorage::open(file_path)?));
+    }
+
+    Err(From::from(
+        "accounts are only kept between runs with PAYMENTS_SQLITE or PAYMENTS_POSTGRES",
+    ))
+}
@@ -0,0 +1,82 @@
+/* Offsets are only kept between runs by the storage features, without them this only errors */
+#![cfg_attr(
+    not(any(feature = "sqlite", feature = "postgres")),
+    allow(dead_code, unused_imports)
+)]
+
+use payments::audit::AuditLog;
+use payments::bank::Bank;
+use payments::consumer::file::FileConsumer;
+#[cfg(feature = "kafka")]
+use payments::consumer::kafka::KafkaConsumer;
+use payments::consumer::{consume, resume, Consumer};
+use payments::output::output_bank;
+#[cfg(feature = "postgres")]
+use payments::storage::postgres::PostgresStorage;
+#[cfg(feature = "sqlite")]
+use payments::storage::sqlite::SqliteStorage;
+use payments::storage::Storage;
+use std::error::Error;
+use std::time::Duration;
+use std::{env, io};
+
+const DEFAULT_LOG_DIR: &str = "log";
+#[cfg(feature = "kafka")]
+const DEFAULT_GROUP: &str = "payments";
+const POLL_TIMEOUT: Duration = Duration::from_millis(500);
+
+/* Consumes forever, writing the accounts to STDOUT each time it catches up with the log */
+fn run<S, C>(mut bank: Bank<S>, mut consumer: C) -> Result<(), Box<dyn Error>>
+where
+    S: Storage,
+    C: Consumer,
+{
+    resume(&bank, &mut consumer)?;
+
+    loop {
+        if consume(&mut bank, &mut consumer, POLL_TIMEOUT)? > 0 {
+            output_bank(&bank, io::stdout())?;
+        }
+    }
+}
+
+fn with_consumer<S>(mut bank: Bank<S>) -> Result<(), Box<dyn Error>>
+where
+    S: Storage,
+{
+    if let Some(file_path) = env::var_os("PAYMENTS_AUDIT_LOG") {
+        bank = bank.with_audit_log(AuditLog::open(file_path)?);
+    }
+
+    let topic = env::args()
+        .nth(1)
+        .ok_or("expect a topic, but found no arguments")?;
+
+    #[cfg(feature = "kafka")]
+    if let Ok(brokers) = env::var("PAYMENTS_KAFKA") {
+        let group = env::var("PAYMENTS_KAFKA_GROUP").unwrap_or_else(|_| DEFAULT_GROUP.to_string());
+        return run(bank, KafkaConsumer::connect(&brokers, &group, &topic)?);
+    }
+
+    let dir = env::args()
+        .nth(2)
+        .unwrap_or_else(|| DEFAULT_LOG_DIR.to_string());
+    run(bank, FileConsumer::open(dir, &topic))
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    #[cfg(feature = "postgres")]
+    if let Ok(params) = env::var("PAYMENTS_POSTGRES") {
+        return with_consumer(Bank::with_storage(PostgresStorage::connect(&params)?));
+    }
+
+    #[cfg(feature = "sqlite")]
+    if let Some(file_path) = env::var_os("PAYMENTS_SQLITE") {
+        return with_consumer(Bank::with_storage(SqliteStorage::open(file_path)?));
+    }
+
+    /* Without stored offsets every restart would apply the whole log again */
+    Err(From::from(
+        "offsets are only kept between runs with PAYMENTS_SQLITE or PAYMENTS_POSTGRES",
+    ))
+}
@@ -0,0 +1,80 @@
+use payments::generator::{Generator, TypeMix, WorkloadConfig};
+use std::error::Error;
+use std::fs::File;
+use std::str::FromStr;
+use std::{env, io};
+
+const USAGE: &str = "usage: generate [--clients N] [--transactions N] \
+[--mix DEPOSIT:WITHDRAWAL:DISPUTE] [--chargeback-ratio RATIO] [--malformed-rate RATE] \
+[--seed N] [--expected FILE]";
+
+fn parse<T>(flag: &str, value: Option<String>) -> Result<T, Box<dyn Error>>
+where
+    T: FromStr,
+{
+    value
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| From::from(format!("invalid value for {}\n{}", flag, USAGE)))
+}
+
+fn parse_mix(value: Option<String>) -> Result<TypeMix, Box<dyn Error>> {
+    let weights = value
+        .unwrap_or_default()
+        .split(':')
+        .map(|weight| weight.parse().ok())
+        .collect::<Option<Vec<u32>>>();
+
+    match weights.as_deref() {
+        Some(&[deposit, withdrawal, dispute]) if deposit + withdrawal + dispute > 0 => {
+            Ok(TypeMix {
+                deposit,
+                withdrawal,
+                dispute,
+            })
+        }
+        _ => Err(From::from(format!("invalid value for --mix\n{}", USAGE))),
+    }
+}
+
+fn parse_args() -> Result<(WorkloadConfig, Option<String>), Box<dyn Error>> {
+    let mut config = WorkloadConfig::default();
+    let mut expected = None;
+    let mut args = env::args().skip(1);
+
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--clients" => config.clients = parse(&flag, args.next())?,
+            "--transactions" => config.transactions = parse(&flag, args.next())?,
+            "--mix" => config.mix = parse_mix(args.next())?,
+            "--chargeback-ratio" => config.chargeback_ratio = parse(&flag, args.next())?,
+            "--malformed-rate" => config.malformed_rate = parse(&flag, args.next())?,
+            "--seed" => config.seed = parse(&flag, args.next())?,
+            "--expected" => expected = Some(parse(&flag, args.next())?),
+            _ => return Err(From::from(format!("unknown argument {}\n{}", flag, USAGE))),
+        }
+    }
+
+    if config.clients == 0 {
+        return Err(From::from("--clients must be greater than 0"));
+    }
+    if !(0.0..=1.0).contains(&config.chargeback_ratio)
+        || !(0.0..=1.0).contains(&config.malformed_rate)
+    {
+        return Err(From::from("ratios and rates must be between 0 and 1"));
+    }
+
+    Ok((config, expected))
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let (config, expected) = parse_args()?;
+
+    let mut generator = Generator::new(config);
+    generator.write(io::stdout())?;
+
+    if let Some(file_path) = expected {
+        generator.write_expected(File::create(file_path)?)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,25 @@
+use payments::audit::verify_audit_log;
+use std::env;
+use std::error::Error;
+use std::fs::File;
+use std::process;
+
+const USAGE: &str = "usage: verify-audit FILE";
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let file_path = env::args_os().nth(1).ok_or(USAGE)?;
+
+    match verify_audit_log(File::open(file_path)?) {
+        Ok(summary) => {
+            println!(
+                "ok: {} entries, last hash {}",
+                summary.entries, summary.last_hash
+            );
+            Ok(())
+        }
+        Err(broken) => {
+            eprintln!("broken chain at {}", broken);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,457 @@
+use crate::dispute::DisputeAction;
+use crate::event::AsOf;
+use crate::id::ClientId;
+use crate::ingest::InputFormat;
+use crate::validate::ValidationConfig;
+use serde::Deserialize;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+pub const USAGE: &str = "usage: payments [OPTIONS] COMMAND
+
+commands:
+  process FILE [OPEN_DISPUTES]  apply FILE and write every account's balances
+  validate FILE                 apply FILE without writing balances, reporting rejected rows
+  replay (--sequence N | --timestamp MS) [FILE]
+                                write every account's balances as of an input position or time
+  statement CLIENT [FILE]       write every event for CLIENT with its balances after each
+  serve [ADDRESS]               apply transactions sent over TCP
+  serve-grpc [ADDRESS]          apply transactions sent over gRPC, with the grpc feature
+
+options:
+  --config PATH       TOML file of settings, which the options override
+  --format csv|tsv    input format
+  --output PATH       write to PATH instead of STDOUT
+  --rejections PATH   write every rejected or malformed input row to PATH
+  --threads N         serve at most N connections at once
+  --samples N         rejected rows of each kind reported by validate
+  --max-rejected N    rejected rows validate allows
+  --max-malformed N   malformed rows validate allows
+  -h, --help          print this help";
+
+fn parse<T>(flag: &str, value: Option<String>) -> Result<T, Box<dyn Error>>
+where
+    T: FromStr,
+{
+    value
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| From::from(format!("invalid value for {}\n{}", flag, USAGE)))
+}
+
+fn parse_env<T>(name: &str) -> Result<Option<T>, Box<dyn Error>>
+where
+    T: FromStr,
+{
+    match env::var(name) {
+        Ok(value) => match value.parse() {
+            Ok(value) => Ok(Some(value)),
+            Err(_) => Err(From::from(format!("invalid value for {}", name))),
+        },
+        Err(_) => Ok(None),
+    }
+}
+
+#[derive(Clone, Default, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ValidateSettings {
+    pub samples: Option<usize>,
+    pub max_rejected: Option<u64>,
+    pub max_malformed: Option<u64>,
+}
+
+/*
+ * Settings for every command, read from the PAYMENTS_ environment variables, then the config file
+ * and then the command line options, each overriding the ones before it
+ */
+#[derive(Clone, Default, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    pub format: Option<InputFormat>,
+    pub output: Option<PathBuf>,
+    pub rejections: Option<PathBuf>,
+    pub threads: Option<usize>,
+    pub address: Option<String>,
+    /* The address of the WebSocket feed the servers publish account changes to */
+    pub websocket: Option<String>,
+    pub sqlite: Option<PathBuf>,
+    pub postgres: Option<String>,
+    pub audit_log: Option<PathBuf>,
+    pub simulated_clock: Option<u64>,
+    pub authorization_expiry: Option<u64>,
+    pub dispute_max_age: Option<u64>,
+    pub dispute_action: Option<DisputeAction>,
+    pub fee_schedule: Option<PathBuf>,
+    pub overdraft_limits: Option<PathBuf>,
+    pub standing_orders: Option<PathBuf>,
+    pub bank_tx: Option<u64>,
+    pub validate: ValidateSettings,
+}
+
+impl Config {
+    pub fn open<P>(path: P) -> Result<Self, Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+    {
+        Ok(toml::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /* The storage and feed variables are only read when they are built in, as before */
+    pub fn from_env() -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            #[cfg(feature = "websocket")]
+            websocket: env::var("PAYMENTS_WEBSOCKET").ok(),
+            #[cfg(feature = "sqlite")]
+            sqlite: env::var_os("PAYMENTS_SQLITE").map(PathBuf::from),
+            #[cfg(feature = "postgres")]
+            postgres: env::var("PAYMENTS_POSTGRES").ok(),
+            audit_log: env::var_os("PAYMENTS_AUDIT_LOG").map(PathBuf::from),
+            simulated_clock: parse_env("PAYMENTS_SIMULATED_CLOCK")?,
+            authorization_expiry: parse_env("PAYMENTS_AUTHORIZATION_EXPIRY")?,
+            dispute_max_age: parse_env("PAYMENTS_DISPUTE_MAX_AGE")?,
+            dispute_action: parse_env("PAYMENTS_DISPUTE_ACTION")?,
+            fee_schedule: env::var_os("PAYMENTS_FEE_SCHEDULE").map(PathBuf::from),
+            overdraft_limits: env::var_os("PAYMENTS_OVERDRAFT_LIMITS").map(PathBuf::from),
+            standing_orders: env::var_os("PAYMENTS_STANDING_ORDERS").map(PathBuf::from),
+            bank_tx: parse_env("PAYMENTS_BANK_TX")?,
+            ..Self::default()
+        })
+    }
+
+    /* Settings of other take precedence over those of self */
+    pub fn merge(self, other: Config) -> Self {
+        Self {
+            format: other.format.or(self.format),
+            output: other.output.or(self.output),
+            rejections: other.rejections.or(self.rejections),
+            threads: other.threads.or(self.threads),
+            address: other.address.or(self.address),
+            websocket: other.websocket.or(self.websocket),
+            sqlite: other.sqlite.or(self.sqlite),
+            postgres: other.postgres.or(self.postgres),
+            audit_log: other.audit_log.or(self.audit_log),
+            simulated_clock: other.simulated_clock.or(self.simulated_clock),
+            authorization_expiry: other.authorization_expiry.or(self.authorization_expiry),
+            dispute_max_age: other.dispute_max_age.or(self.dispute_max_age),
+            dispute_action: other.dispute_action.or(self.dispute_action),
+            fee_schedule: other.fee_schedule.or(self.fee_schedule),
+            overdraft_limits: other.overdraft_limits.or(self.overdraft_limits),
+            standing_orders: other.standing_orders.or(self.standing_orders),
+            bank_tx: other.bank_tx.or(self.bank_tx),
+            validate: ValidateSettings {
+                samples: other.validate.samples.or(self.validate.samples),
+                max_rejected: other.validate.max_rejected.or(self.validate.max_rejected),
+                max_malformed: other.validate.max_malformed.or(self.validate.max_malformed),
+            },
+        }
+    }
+
+    pub fn validation(&self) -> ValidationConfig {
+        let defaults = ValidationConfig::default();
+        ValidationConfig {
+            samples: self.validate.samples.unwrap_or(defaults.samples),
+            max_rejected: self.validate.max_rejected.unwrap_or(defaults.max_rejected),
+            max_malformed: self
+                .validate
+                .max_malformed
+                .unwrap_or(defaults.max_malformed),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    /* Validation checks the input against a bank in memory, it never opens storage */
+    Validate { input: PathBuf },
+    Bank(BankCommand),
+    Help,
+}
+
+/* The commands run against the configured storage */
+#[derive(Debug, PartialEq)]
+pub enum BankCommand {
+    Process {
+        input: PathBuf,
+        open_disputes: Option<PathBuf>,
+    },
+    Replay {
+        as_of: AsOf,
+        input: Option<PathBuf>,
+    },
+    Statement {
+        client_id: ClientId,
+        input: Option<PathBuf>,
+    },
+    Serve,
+    ServeGrpc,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Args {
+    pub command: Command,
+    pub config_file: Option<PathBuf>,
+    /* The settings given as options */
+    pub options: Config,
+}
+
+impl Args {
+    /*
+     * Options may come before or after the command. A lone file without a command is processed, as
+     * it was before there were commands, anything more must start with one so that a mistyped
+     * command is not taken for a file
+     */
+    pub fn parse<I>(args: I) -> Result<Self, Box<dyn Error>>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut config_file = None;
+        let mut options = Config::default();
+        let mut as_of = None;
+        let mut positional = Vec::new();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--help" | "-h" => {
+                    return Ok(Self {
+                        command: Command::Help,
+                        config_file,
+                        options,
+                    })
+                }
+                "--config" => config_file = Some(parse(&arg, args.next())?),
+                "--format" => options.format = Some(parse(&arg, args.next())?),
+                "--output" => options.output = Some(parse(&arg, args.next())?),
+                "--rejections" => options.rejections = Some(parse(&arg, args.next())?),
+                "--threads" => match parse(&arg, args.next())? {
+                    0 => {
+                        return Err(From::from(format!(
+                            "threads must be greater than 0\n{}",
+                            USAGE
+                        )))
+                    }
+                    threads => options.threads = Some(threads),
+                },
+                "--samples" => options.validate.samples = Some(parse(&arg, args.next())?),
+                "--max-rejected" => options.validate.max_rejected = Some(parse(&arg, args.next())?),
+                "--max-malformed" => {
+                    options.validate.max_malformed = Some(parse(&arg, args.next())?)
+                }
+                "--sequence" => as_of = Some(AsOf::Sequence(parse(&arg, args.next())?)),
+                "--timestamp" => as_of = Some(AsOf::Timestamp(parse(&arg, args.next())?)),
+                _ if arg.starts_with("--") => {
+                    return Err(From::from(format!("unknown option {}\n{}", arg, USAGE)))
+                }
+                _ => positional.push(arg),
+            }
+        }
+
+        let command = match positional.first().map(String::as_str) {
+            Some("process" | "validate" | "replay" | "statement" | "serve" | "serve-grpc") => {
+                positional.remove(0)
+            }
+            Some(command) if positional.len() > 1 => {
+                return Err(From::from(format!(
+                    "unknown command {}\n{}",
+                    command, USAGE
+                )))
+            }
+            _ => "process".to_string(),
+        };
+        let mut positional = positional.into_iter();
+
+        let command = match command.as_str() {
+            "process" => Command::Bank(BankCommand::Process {
+                input: positional.next().ok_or(USAGE)?.into(),
+                open_disputes: positional.next().map(PathBuf::from),
+            }),
+            "validate" => Command::Validate {
+                input: positional.next().ok_or(USAGE)?.into(),
+            },
+            "replay" => Command::Bank(BankCommand::Replay {
+                as_of: as_of
+                    .take()
+                    .ok_or_else(|| format!("replay needs --sequence or --timestamp\n{}", USAGE))?,
+                input: positional.next().map(PathBuf::from),
+            }),
+            "statement" => Command::Bank(BankCommand::Statement {
+                client_id: parse("CLIENT", positional.next())?,
+                input: positional.next().map(PathBuf::from),
+            }),
+            "serve" => {
+                options.address = positional.next();
+                Command::Bank(BankCommand::Serve)
+            }
+            _ => {
+                options.address = positional.next();
+                Command::Bank(BankCommand::ServeGrpc)
+            }
+        };
+
+        if let Some(arg) = positional.next() {
+            return Err(From::from(format!(
+                "unexpected argument {}\n{}",
+                arg, USAGE
+            )));
+        }
+        if as_of.is_some() {
+            return Err(From::from(format!(
+                "--sequence and --timestamp are only for replay\n{}",
+                USAGE
+            )));
+        }
+
+        Ok(Self {
+            command,
+            config_file,
+            options,
+        })
+    }
+
+    /* The environment, overridden by the config file, overridden by the options */
+    pub fn config(&self) -> Result<Config, Box<dyn Error>> {
+        let mut config = Config::from_env()?;
+        if let Some(path) = &self.config_file {
+            config = config.merge(Config::open(path)?);
+        }
+        let config = config.merge(self.options.clone());
+
+        if config.threads == Some(0) {
+            return Err(From::from("threads must be greater than 0"));
+        }
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::id::client;
+
+    fn args(args: &str) -> Result<Args, Box<dyn Error>> {
+        Args::parse(args.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn test_commands() {
+        assert_eq!(
+            Command::Bank(BankCommand::Process {
+                input: "test.csv".into(),
+                open_disputes: None
+            }),
+            args("test.csv --format tsv").unwrap().command
+        );
+        assert_eq!(
+            Command::Bank(BankCommand::Process {
+                input: "test.csv".into(),
+                open_disputes: Some("disputes.csv".into())
+            }),
+            args("process test.csv disputes.csv").unwrap().command
+        );
+        assert_eq!(
+            Command::Bank(BankCommand::Process {
+                input: "test.csv".into(),
+                open_disputes: None
+            }),
+            args("process test.csv").unwrap().command
+        );
+        assert_eq!(
+            Command::Bank(BankCommand::Replay {
+                as_of: AsOf::Timestamp(1_000),
+                input: Some("test.csv".into())
+            }),
+            args("replay test.csv --timestamp 1000").unwrap().command
+        );
+        assert_eq!(
+            Command::Bank(BankCommand::Statement {
+                client_id: client(7),
+                input: None
+            }),
+            args(&format!("statement {}", client(7))).unwrap().command
+        );
+
+        let serve = args("--threads 4 serve 127.0.0.1:0").unwrap();
+        assert_eq!(Command::Bank(BankCommand::Serve), serve.command);
+        assert_eq!(Some(4), serve.options.threads);
+        assert_eq!(Some("127.0.0.1:0".to_string()), serve.options.address);
+        assert_eq!(
+            Command::Bank(BankCommand::ServeGrpc),
+            args("serve-grpc").unwrap().command
+        );
+
+        assert_eq!(Command::Help, args("--help").unwrap().command);
+        assert_eq!(Command::Help, args("process -h").unwrap().command);
+
+        assert!(args("").is_err());
+        assert!(args("proces test.csv").is_err());
+        assert!(args("test.csv disputes.csv").is_err());
+        assert!(args("replay test.csv").is_err());
+        assert!(args("validate test.csv --sequence 3").is_err());
+        assert!(args("validate test.csv other.csv").is_err());
+        /* Any text is a client id when they are strings */
+        #[cfg(not(feature = "client-id-string"))]
+        assert!(args("statement x").is_err());
+        assert!(args("--threads 0 serve").is_err());
+        assert!(args("process test.csv --format json").is_err());
+        assert!(args("process test.csv --verbose").is_err());
+    }
+
+    #[test]
+    fn test_config_file() {
+        let config: Config = toml::from_str(
+            r#"
+            format = "tsv"
+            output = "accounts.csv"
+            threads = 8
+            fee-schedule = "fees.csv"
+            dispute-max-age = 1000
+            dispute-action = "chargeback"
+            bank-tx = 4000000000
+            websocket = "127.0.0.1:7879"
+
+            [validate]
+            max-rejected = 10
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(Some(InputFormat::Tsv), config.format);
+        assert_eq!(Some(PathBuf::from("fees.csv")), config.fee_schedule);
+        assert_eq!(Some(1_000), config.dispute_max_age);
+        assert_eq!(Some(DisputeAction::Chargeback), config.dispute_action);
+        assert_eq!(Some(4_000_000_000), config.bank_tx);
+        assert_eq!(Some("127.0.0.1:7879".to_string()), config.websocket);
+        assert_eq!(10, config.validation().max_rejected);
+        assert_eq!(5, config.validation().samples);
+
+        assert!(toml::from_str::<Config>("fee_schedule = \"fees.csv\"").is_err());
+    }
+
+    #[test]
+    fn test_options_override_config_file() {
+        let file = Config {
+            format: Some(InputFormat::Tsv),
+            output: Some("accounts.csv".into()),
+            threads: Some(8),
+            validate: ValidateSettings {
+                samples: Some(1),
+                max_rejected: Some(10),
+                ..ValidateSettings::default()
+            },
+            ..Config::default()
+        };
+        let options = args("validate test.csv --output report.txt --max-rejected 3")
+            .unwrap()
+            .options;
+
+        let config = file.merge(options);
+        assert_eq!(Some(InputFormat::Tsv), config.format);
+        assert_eq!(Some(PathBuf::from("report.txt")), config.output);
+        assert_eq!(Some(8), config.threads);
+        assert_eq!(Some(1), config.validate.samples);
+        assert_eq!(Some(3), config.validate.max_rejected);
+    }
+}
@@ -0,0 +1,77 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub(crate) fn timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/*
+ * The bank's time in milliseconds since the unix epoch, used to timestamp the events it records.
+ * Told of the timestamp of every input transaction and every time the bank is advanced
+ */
+pub trait Clock: Send {
+    fn now(&self) -> u64;
+
+    fn advance_to(&mut self, _now: u64) {}
+}
+
+/* Wall clock time, input timestamps are ignored */
+#[derive(Clone, Copy, Default, Debug)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        timestamp()
+    }
+}
+
+/*
+ * Time that only moves when it is advanced, by input timestamps or Bank::advance_to, so that
+ * replaying the same input records the same events. It never moves backwards, an earlier
+ * timestamp leaves it where it is
+ */
+#[derive(Clone, Copy, Default, Debug)]
+pub struct SimulatedClock {
+    now: u64,
+}
+
+impl SimulatedClock {
+    pub fn new(start: u64) -> Self {
+        Self { now: start }
+    }
+}
+
+impl Clock for SimulatedClock {
+    fn now(&self) -> u64 {
+        self.now
+    }
+
+    fn advance_to(&mut self, now: u64) {
+        self.now = self.now.max(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simulated_clock() {
+        let mut clock = SimulatedClock::new(1_000);
+        assert_eq!(1_000, clock.now());
+
+        clock.advance_to(5_000);
+        assert_eq!(5_000, clock.now());
+        clock.advance_to(2_000);
+        assert_eq!(5_000, clock.now());
+    }
+
+    #[test]
+    fn test_system_clock_ignores_advance() {
+        let mut clock = SystemClock;
+        clock.advance_to(u64::MAX);
+        assert!(clock.now() < u64::MAX);
+    }
+}
@@ -0,0 +1,251 @@
+use crate::consumer::{Consumer, Record};
+use crate::id::Id;
+use crate::transaction::Transaction;
+use csv::WriterBuilder;
+use std::cmp;
+use std::error::Error;
+use std::ffi::OsStr;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const LOG_EXTENSION: &str = "log";
+/* How often partitions are checked for new records while polling */
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/* A topic is a directory of <partition>.log files, a record is a line and its offset its number */
+fn partition_path(topic: &Path, partition: u32) -> PathBuf {
+    topic.join(format!("{}.{}", partition, LOG_EXTENSION))
+}
+
+/* Appends transactions to a topic, partitioned by client so that each client's stay in order */
+pub struct FileProducer {
+    partitions: Vec<File>,
+}
+
+impl FileProducer {
+    pub fn create<P>(dir: P, topic: &str, partitions: u32) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let topic = dir.as_ref().join(topic);
+        fs::create_dir_all(&topic)?;
+        let partitions = (0..partitions)
+            .map(|partition| {
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(partition_path(&topic, partition))
+            })
+            .collect::<io::Result<_>>()?;
+
+        Ok(Self { partitions })
+    }
+
+    pub fn send(&mut self, transaction: &Transaction) -> Result<(), Box<dyn Error>> {
+        let mut writer = WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(Vec::new());
+        writer.serialize(transaction)?;
+        let line = writer.into_inner()?;
+
+        /* Written with a single call so that a record is never interleaved with another */
+        let partition = (transaction.client_id.bucket() % self.partitions.len() as u64) as usize;
+        self.partitions[partition].write_all(&line)?;
+
+        Ok(())
+    }
+}
+
+struct PartitionReader {
+    partition: u32,
+    offset: u64,
+    reader: BufReader<File>,
+    line: Vec<u8>,
+}
+
+impl PartitionReader {
+    /* A line still being written is kept until the rest of it arrives */
+    fn read(&mut self) -> io::Result<Option<Record>> {
+        self.reader.read_until(b'\n', &mut self.line)?;
+        if self.line.last() != Some(&b'\n') {
+            return Ok(None);
+        }
+
+        let mut payload = std::mem::take(&mut self.line);
+        payload.pop();
+        let record = Record {
+            partition: self.partition,
+            offset: self.offset,
+            payload,
+        };
+        self.offset += 1;
+
+        Ok(Some(record))
+    }
+}
+
+/* Reads a topic written by FileProducer, for running locally without a broker */
+pub struct FileConsumer {
+    topic: String,
+    dir: PathBuf,
+    readers: Vec<PartitionReader>,
+    next: usize,
+}
+
+impl FileConsumer {
+    pub fn open<P>(dir: P, topic: &str) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self {
+            topic: topic.to_string(),
+            dir: dir.as_ref().join(topic),
+            readers: Vec::new(),
+            next: 0,
+        }
+    }
+}
+
+impl Consumer for FileConsumer {
+    fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    fn partitions(&mut self) -> Result<Vec<u32>, Box<dyn Error>> {
+        let mut partitions = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension() != Some(OsStr::new(LOG_EXTENSION)) {
+                continue;
+            }
+            if let Some(partition) = path.file_stem().and_then(OsStr::to_str) {
+                partitions.push(partition.parse()?);
+            }
+        }
+        partitions.sort_unstable();
+
+        Ok(partitions)
+    }
+
+    fn assign(&mut self, offsets: &[(u32, u64)]) -> Result<(), Box<dyn Error>> {
+        self.readers.clear();
+        self.next = 0;
+
+        for &(partition, offset) in offsets {
+            let mut reader = PartitionReader {
+                partition,
+                offset: 0,
+                reader: BufReader::new(File::open(partition_path(&self.dir, partition))?),
+                line: Vec::new(),
+            };
+            while reader.offset < offset {
+                if reader.read()?.is_none() {
+                    return Err(From::from(format!(
+                        "Partition {} has fewer than {} records",
+                        partition, offset
+                    )));
+                }
+            }
+            self.readers.push(reader);
+        }
+
+        Ok(())
+    }
+
+    /* Partitions are read in turn so that a busy partition does not hold back the others */
+    fn poll(&mut self, timeout: Duration) -> Result<Option<Record>, Box<dyn Error>> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            for _ in 0..self.readers.len() {
+                let reader = self.next;
+                self.next = (self.next + 1) % self.readers.len();
+                if let Some(record) = self.readers[reader].read()? {
+                    return Ok(Some(record));
+                }
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+            thread::sleep(cmp::min(POLL_INTERVAL, deadline - now));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::id::{client, tx};
+    use rust_decimal::Decimal;
+    use std::fs::remove_dir_all;
+
+    #[test]
+    fn test_partitions_and_offsets() {
+        let dir = "file_log_test";
+        let _ = remove_dir_all(dir);
+        let mut producer = FileProducer::create(dir, "transactions", 2).unwrap();
+        /* Partition and offset each record is expected at, the partition depends on the id type */
+        let mut lengths = [0_u64, 0];
+        let mut expected = Vec::new();
+        for id in 0..6 {
+            producer
+                .send(&Transaction::new_deposit(client(id), tx(id), Decimal::ONE))
+                .unwrap();
+            let partition = (client(id).bucket() % 2) as u32;
+            expected.push((
+                partition,
+                lengths[partition as usize],
+                format!("deposit,{},{},1", client(id), tx(id)),
+            ));
+            lengths[partition as usize] += 1;
+        }
+
+        let mut consumer = FileConsumer::open(dir, "transactions");
+        assert_eq!(vec![0, 1], consumer.partitions().unwrap());
+        consumer.assign(&[(0, 1), (1, 0)]).unwrap();
+
+        let mut records = Vec::new();
+        while let Some(record) = consumer.poll(Duration::from_millis(20)).unwrap() {
+            records.push((
+                record.partition,
+                record.offset,
+                String::from_utf8(record.payload).unwrap(),
+            ));
+        }
+        records.sort();
+        expected.retain(|(partition, offset, _)| (*partition, *offset) != (0, 0));
+        expected.sort();
+        assert_eq!(expected, records);
+
+        /* A partially written record is returned once it is complete */
+        let mut partition = OpenOptions::new()
+            .append(true)
+            .open(partition_path(
+                Path::new(dir).join("transactions").as_path(),
+                0,
+            ))
+            .unwrap();
+        partition
+            .write_all(format!("deposit,{},", client(6)).as_bytes())
+            .unwrap();
+        assert!(consumer.poll(Duration::ZERO).unwrap().is_none());
+        partition
+            .write_all(format!("{},1\n", tx(6)).as_bytes())
+            .unwrap();
+        let record = consumer.poll(Duration::ZERO).unwrap().unwrap();
+        assert_eq!((0, lengths[0]), (record.partition, record.offset));
+        assert_eq!(
+            format!("deposit,{},{},1", client(6), tx(6)).into_bytes(),
+            record.payload
+        );
+
+        assert!(consumer.assign(&[(1, lengths[1] + 1)]).is_err());
+
+        remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,85 @@
+use crate::consumer::{Consumer, Record};
+use rdkafka::config::ClientConfig;
+use rdkafka::consumer::{BaseConsumer, Consumer as _};
+use rdkafka::message::Message;
+use rdkafka::{Offset, TopicPartitionList};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::time::Duration;
+
+const METADATA_TIMEOUT: Duration = Duration::from_secs(10);
+
+/*
+ * Consumes a topic from Kafka, or any broker speaking its protocol, partitions are assigned
+ * explicitly from the offsets committed with the bank, so nothing is committed to the broker and
+ * the group id only identifies the consumer
+ */
+pub struct KafkaConsumer {
+    consumer: BaseConsumer,
+    topic: String,
+}
+
+impl KafkaConsumer {
+    pub fn connect(brokers: &str, group: &str, topic: &str) -> Result<Self, Box<dyn Error>> {
+        let consumer = ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .set("group.id", group)
+            .set("enable.auto.commit", "false")
+            .set("enable.auto.offset.store", "false")
+            .create()?;
+
+        Ok(Self {
+            consumer,
+            topic: topic.to_string(),
+        })
+    }
+}
+
+impl Consumer for KafkaConsumer {
+    fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    fn partitions(&mut self) -> Result<Vec<u32>, Box<dyn Error>> {
+        let metadata = self
+            .consumer
+            .fetch_metadata(Some(&self.topic), METADATA_TIMEOUT)?;
+        let mut partitions = Vec::new();
+        for topic in metadata.topics() {
+            if let Some(err) = topic.error() {
+                return Err(From::from(format!("Topic {}: {:?}", self.topic, err)));
+            }
+            for partition in topic.partitions() {
+                partitions.push(u32::try_from(partition.id())?);
+            }
+        }
+
+        Ok(partitions)
+    }
+
+    fn assign(&mut self, offsets: &[(u32, u64)]) -> Result<(), Box<dyn Error>> {
+        let mut assignment = TopicPartitionList::new();
+        for &(partition, offset) in offsets {
+            assignment.add_partition_offset(
+                &self.topic,
+                i32::try_from(partition)?,
+                Offset::Offset(i64::try_from(offset)?),
+            )?;
+        }
+
+        Ok(self.consumer.assign(&assignment)?)
+    }
+
+    fn poll(&mut self, timeout: Duration) -> Result<Option<Record>, Box<dyn Error>> {
+        let message = match self.consumer.poll(timeout) {
+            Some(message) => message?,
+            None => return Ok(None),
+        };
+
+        Ok(Some(Record {
+            partition: u32::try_from(message.partition())?,
+            offset: u64::try_from(message.offset())?,
+            payload: message.payload().unwrap_or_default().to_vec(),
+        }))
+    }
+}
@@ -0,0 +1,219 @@
+pub mod file;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+
+use crate::bank::Bank;
+use crate::storage::Storage;
+use crate::transaction::Transaction;
+use csv::{ReaderBuilder, Trim};
+use std::error::Error;
+use std::time::Duration;
+
+/* A record read from a partition of a log, the payload is a transaction as a line of input CSV */
+#[derive(Clone, PartialEq, Debug)]
+pub struct Record {
+    pub partition: u32,
+    pub offset: u64,
+    pub payload: Vec<u8>,
+}
+
+/*
+ * A partitioned log of transactions, offsets are committed with the bank's state rather than to
+ * the log, so the consumer is always told where to read from
+ */
+pub trait Consumer {
+    fn topic(&self) -> &str;
+
+    fn partitions(&mut self) -> Result<Vec<u32>, Box<dyn Error>>;
+
+    /* Reads each of the partitions from its offset onwards, replacing any previous assignment */
+    fn assign(&mut self, offsets: &[(u32, u64)]) -> Result<(), Box<dyn Error>>;
+
+    /* The next record of any assigned partition, None if none arrived within timeout */
+    fn poll(&mut self, timeout: Duration) -> Result<Option<Record>, Box<dyn Error>>;
+}
+
+/* Columns are in the order of the input file, without a header */
+pub fn decode(payload: &[u8]) -> Option<Transaction> {
+    let mut builder = ReaderBuilder::new();
+    builder.trim(Trim::All).has_headers(false);
+
+    builder.from_reader(payload).deserialize().next()?.ok()
+}
+
+/* Assigns every partition of the topic from the offset committed with the bank's state */
+pub fn resume<S, C>(bank: &Bank<S>, consumer: &mut C) -> Result<(), Box<dyn Error>>
+where
+    S: Storage,
+    C: Consumer,
+{
+    let mut offsets = Vec::new();
+    for partition in consumer.partitions()? {
+        let offset = bank
+            .storage()
+            .consumer_offset(consumer.topic(), partition)?;
+        offsets.push((partition, offset));
+    }
+
+    consumer.assign(&offsets)
+}
+
+/*
+ * Applies records until none arrive within timeout, returning how many were consumed, including
+ * rejected and malformed records, records the bank had already consumed are skipped
+ */
+pub fn consume<S, C>(
+    bank: &mut Bank<S>,
+    consumer: &mut C,
+    timeout: Duration,
+) -> Result<u64, Box<dyn Error>>
+where
+    S: Storage,
+    C: Consumer,
+{
+    let mut consumed = 0;
+    while let Some(record) = consumer.poll(timeout)? {
+        let transaction = decode(&record.payload);
+        match bank.consume(
+            consumer.topic(),
+            record.partition,
+            record.offset,
+            transaction,
+        ) {
+            Ok(false) => {}
+            Err(err) if err.kind().is_storage() => return Err(Box::new(err)),
+            _ => consumed += 1,
+        }
+    }
+
+    Ok(consumed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::file::{FileConsumer, FileProducer};
+    use super::*;
+    use crate::id::{client, tx};
+    use rust_decimal::Decimal;
+    use std::fs::remove_dir_all;
+    use std::str::FromStr;
+
+    const TIMEOUT: Duration = Duration::from_millis(20);
+
+    fn decimal_str(decimal: &str) -> Decimal {
+        Decimal::from_str(decimal).unwrap()
+    }
+
+    fn produce(dir: &str, transactions: &[Transaction]) {
+        let mut producer = FileProducer::create(dir, "transactions", 3).unwrap();
+        for transaction in transactions {
+            producer.send(transaction).unwrap();
+        }
+    }
+
+    fn consumer(dir: &str) -> FileConsumer {
+        FileConsumer::open(dir, "transactions")
+    }
+
+    fn balance<S>(bank: &Bank<S>, client_id: u64) -> Decimal
+    where
+        S: Storage,
+    {
+        *bank.account(client(client_id)).unwrap().unwrap().total()
+    }
+
+    #[test]
+    fn test_decode() {
+        let (c1, tx2) = (client(1), tx(2));
+        let transaction = decode(format!("deposit, {}, {}, 1.5", c1, tx2).as_bytes()).unwrap();
+        assert_eq!((c1, tx2), (transaction.client_id, transaction.id));
+        assert_eq!(Some(decimal_str("1.5")), transaction.amount);
+        assert!(decode(format!("dispute, {}, {},", c1, tx2).as_bytes())
+            .unwrap()
+            .amount
+            .is_none());
+        let transfer = format!("transfer, {}, {}, 1.5, {}", c1, tx2, client(3));
+        assert_eq!(Some(client(3)), decode(transfer.as_bytes()).unwrap().to);
+        assert!(decode(format!("withdraw, {}, {}, 1", c1, tx2).as_bytes()).is_none());
+        /* Only the bank posts interest */
+        assert!(decode(format!("interest, {}, {}, 1", c1, tx2).as_bytes()).is_none());
+    }
+
+    #[test]
+    fn test_exactly_once() {
+        let dir = "consumer_exactly_once_test";
+        let _ = remove_dir_all(dir);
+        produce(
+            dir,
+            &[
+                Transaction::new_deposit(client(1), tx(1), decimal_str("2")),
+                Transaction::new_deposit(client(2), tx(2), decimal_str("3")),
+                Transaction::new_withdrawal(client(1), tx(3), decimal_str("5")),
+                Transaction::new_deposit(client(3), tx(4), decimal_str("1")),
+            ],
+        );
+
+        let mut bank = Bank::new();
+        let mut first = consumer(dir);
+        resume(&bank, &mut first).unwrap();
+        assert_eq!(4, consume(&mut bank, &mut first, TIMEOUT).unwrap());
+
+        /* A log redelivered from the start is not applied again */
+        let mut redelivered = consumer(dir);
+        redelivered.assign(&[(0, 0), (1, 0), (2, 0)]).unwrap();
+        assert_eq!(0, consume(&mut bank, &mut redelivered, TIMEOUT).unwrap());
+
+        produce(
+            dir,
+            &[Transaction::new_deposit(client(1), tx(5), decimal_str("1"))],
+        );
+        let mut resumed = consumer(dir);
+        resume(&bank, &mut resumed).unwrap();
+        assert_eq!(1, consume(&mut bank, &mut resumed, TIMEOUT).unwrap());
+
+        assert_eq!(decimal_str("3"), balance(&bank, 1));
+        assert_eq!(decimal_str("3"), balance(&bank, 2));
+        assert_eq!(decimal_str("1"), balance(&bank, 3));
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_resume_after_restart() {
+        use crate::storage::sqlite::SqliteStorage;
+        use std::fs::remove_file;
+
+        let (dir, database) = ("consumer_restart_test", "consumer_restart_test.db");
+        let delete_database = || {
+            for suffix in ["", "-wal", "-shm"] {
+                let _ = remove_file(format!("{}{}", database, suffix));
+            }
+        };
+        let _ = remove_dir_all(dir);
+        delete_database();
+        let deposits: Vec<Transaction> = (0..20)
+            .map(|id| Transaction::new_deposit(client(id % 4), tx(id), decimal_str("1")))
+            .collect();
+        produce(dir, &deposits[..10]);
+
+        let mut bank = Bank::with_storage(SqliteStorage::open(database).unwrap());
+        let mut first = consumer(dir);
+        resume(&bank, &mut first).unwrap();
+        assert_eq!(10, consume(&mut bank, &mut first, TIMEOUT).unwrap());
+        drop(bank);
+
+        produce(dir, &deposits[10..]);
+        let mut bank = Bank::with_storage(SqliteStorage::open(database).unwrap());
+        let mut second = consumer(dir);
+        resume(&bank, &mut second).unwrap();
+        assert_eq!(10, consume(&mut bank, &mut second, TIMEOUT).unwrap());
+
+        for client_id in 0..4 {
+            assert_eq!(decimal_str("5"), balance(&bank, client_id));
+        }
+
+        remove_dir_all(dir).unwrap();
+        delete_database();
+    }
+}
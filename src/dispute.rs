@@ -0,0 +1,98 @@
+use crate::account::decimal_normalize_serialize;
+use crate::id::{ClientId, TransactionId};
+use ahash::AHashMap;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DisputeAction {
+    Resolve,
+    Chargeback,
+}
+
+impl FromStr for DisputeAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "resolve" => Ok(DisputeAction::Resolve),
+            "chargeback" => Ok(DisputeAction::Chargeback),
+            _ => Err(format!(
+                "unknown dispute action {}, expected resolve or chargeback",
+                s
+            )),
+        }
+    }
+}
+
+/*
+ * Age is measured in input positions, i.e. the number of transactions submitted to the bank
+ * since the dispute was opened
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct DisputePolicy {
+    pub max_age: u64,
+    pub action: DisputeAction,
+}
+
+impl DisputePolicy {
+    pub fn new(max_age: u64, action: DisputeAction) -> Self {
+        Self { max_age, action }
+    }
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+pub struct OpenDispute {
+    #[serde(rename = "client")]
+    pub client_id: ClientId,
+    #[serde(rename = "tx")]
+    pub id: TransactionId,
+    #[serde(serialize_with = "decimal_normalize_serialize")]
+    pub amount: Decimal,
+    pub opened: u64,
+    pub age: u64,
+}
+
+#[derive(Default)]
+pub struct OpenDisputes {
+    disputes: AHashMap<TransactionId, (ClientId, u64)>,
+    expiry: VecDeque<(u64, TransactionId)>,
+}
+
+impl OpenDisputes {
+    pub fn open(&mut self, client_id: ClientId, id: TransactionId, position: u64) {
+        self.disputes.insert(id, (client_id, position));
+        self.expiry.push_back((position, id));
+    }
+
+    pub fn close(&mut self, id: &TransactionId) {
+        self.disputes.remove(id);
+    }
+
+    /*
+     * Positions are monotonic so the expiry queue is always sorted, entries that have since been
+     * closed or re-opened are discarded lazily here
+     */
+    pub fn pop_expired(&mut self, cutoff: u64) -> Option<(ClientId, TransactionId)> {
+        while let Some(&(opened, id)) = self.expiry.front() {
+            if opened >= cutoff {
+                return None;
+            }
+
+            self.expiry.pop_front();
+            match self.disputes.get(&id) {
+                Some(&(client_id, position)) if position == opened => return Some((client_id, id)),
+                _ => continue,
+            }
+        }
+
+        None
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&TransactionId, &(ClientId, u64))> {
+        self.disputes.iter()
+    }
+}
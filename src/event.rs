@@ -0,0 +1,172 @@
+use crate::account::TransactionResult;
+use crate::id::{ClientId, TransactionId};
+use crate::transaction::transaction_error::TransactionError;
+use rust_decimal::Decimal;
+
+/*
+ * Everything that changes an account, an account's state is the fold of its events in order, the
+ * amount is carried on dispute and authorization events too so they apply without looking up the
+ * transaction. A disputed transfer is marked on the source account by the transfer events while
+ * its funds are held at the destination by the dispute events
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub enum AccountEvent {
+    Deposited { id: TransactionId, amount: Decimal },
+    Withdrawn { id: TransactionId, amount: Decimal },
+    DisputeOpened { id: TransactionId, amount: Decimal },
+    DisputeResolved { id: TransactionId, amount: Decimal },
+    ChargedBack { id: TransactionId, amount: Decimal },
+    Locked,
+    Authorized { id: TransactionId, amount: Decimal },
+    Captured { id: TransactionId, amount: Decimal },
+    Voided { id: TransactionId, amount: Decimal },
+    TransferredOut { id: TransactionId, amount: Decimal },
+    TransferredIn { id: TransactionId, amount: Decimal },
+    TransferDisputed { id: TransactionId, amount: Decimal },
+    TransferResolved { id: TransactionId, amount: Decimal },
+    TransferReversed { id: TransactionId, amount: Decimal },
+    Refunded { id: TransactionId, amount: Decimal },
+    FeeCharged { id: TransactionId, amount: Decimal },
+    InterestAccrued { id: TransactionId, amount: Decimal },
+}
+
+impl AccountEvent {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AccountEvent::Deposited { .. } => "deposited",
+            AccountEvent::Withdrawn { .. } => "withdrawn",
+            AccountEvent::DisputeOpened { .. } => "dispute_opened",
+            AccountEvent::DisputeResolved { .. } => "dispute_resolved",
+            AccountEvent::ChargedBack { .. } => "charged_back",
+            AccountEvent::Locked => "locked",
+            AccountEvent::Authorized { .. } => "authorized",
+            AccountEvent::Captured { .. } => "captured",
+            AccountEvent::Voided { .. } => "voided",
+            AccountEvent::TransferredOut { .. } => "transferred_out",
+            AccountEvent::TransferredIn { .. } => "transferred_in",
+            AccountEvent::TransferDisputed { .. } => "transfer_disputed",
+            AccountEvent::TransferResolved { .. } => "transfer_resolved",
+            AccountEvent::TransferReversed { .. } => "transfer_reversed",
+            AccountEvent::Refunded { .. } => "refunded",
+            AccountEvent::FeeCharged { .. } => "fee_charged",
+            AccountEvent::InterestAccrued { .. } => "interest_accrued",
+        }
+    }
+
+    pub fn id(&self) -> Option<TransactionId> {
+        match self {
+            AccountEvent::Deposited { id, .. }
+            | AccountEvent::Withdrawn { id, .. }
+            | AccountEvent::DisputeOpened { id, .. }
+            | AccountEvent::DisputeResolved { id, .. }
+            | AccountEvent::ChargedBack { id, .. }
+            | AccountEvent::Authorized { id, .. }
+            | AccountEvent::Captured { id, .. }
+            | AccountEvent::Voided { id, .. }
+            | AccountEvent::TransferredOut { id, .. }
+            | AccountEvent::TransferredIn { id, .. }
+            | AccountEvent::TransferDisputed { id, .. }
+            | AccountEvent::TransferResolved { id, .. }
+            | AccountEvent::TransferReversed { id, .. }
+            | AccountEvent::Refunded { id, .. }
+            | AccountEvent::FeeCharged { id, .. }
+            | AccountEvent::InterestAccrued { id, .. } => Some(*id),
+            AccountEvent::Locked => None,
+        }
+    }
+
+    pub fn amount(&self) -> Option<Decimal> {
+        match self {
+            AccountEvent::Deposited { amount, .. }
+            | AccountEvent::Withdrawn { amount, .. }
+            | AccountEvent::DisputeOpened { amount, .. }
+            | AccountEvent::DisputeResolved { amount, .. }
+            | AccountEvent::ChargedBack { amount, .. }
+            | AccountEvent::Authorized { amount, .. }
+            | AccountEvent::Captured { amount, .. }
+            | AccountEvent::Voided { amount, .. }
+            | AccountEvent::TransferredOut { amount, .. }
+            | AccountEvent::TransferredIn { amount, .. }
+            | AccountEvent::TransferDisputed { amount, .. }
+            | AccountEvent::TransferResolved { amount, .. }
+            | AccountEvent::TransferReversed { amount, .. }
+            | AccountEvent::Refunded { amount, .. }
+            | AccountEvent::FeeCharged { amount, .. }
+            | AccountEvent::InterestAccrued { amount, .. } => Some(*amount),
+            AccountEvent::Locked => None,
+        }
+    }
+
+    /* Inverse of kind, id and amount, used by storage backends to load events */
+    pub fn from_parts(
+        kind: &str,
+        id: Option<TransactionId>,
+        amount: Option<Decimal>,
+    ) -> TransactionResult<Self> {
+        let missing = || TransactionError::storage("Event is missing its transaction");
+        let event = match kind {
+            "locked" => return Ok(AccountEvent::Locked),
+            _ => (kind, id.ok_or_else(missing)?, amount.ok_or_else(missing)?),
+        };
+
+        match event {
+            ("deposited", id, amount) => Ok(AccountEvent::Deposited { id, amount }),
+            ("withdrawn", id, amount) => Ok(AccountEvent::Withdrawn { id, amount }),
+            ("dispute_opened", id, amount) => Ok(AccountEvent::DisputeOpened { id, amount }),
+            ("dispute_resolved", id, amount) => Ok(AccountEvent::DisputeResolved { id, amount }),
+            ("charged_back", id, amount) => Ok(AccountEvent::ChargedBack { id, amount }),
+            ("authorized", id, amount) => Ok(AccountEvent::Authorized { id, amount }),
+            ("captured", id, amount) => Ok(AccountEvent::Captured { id, amount }),
+            ("voided", id, amount) => Ok(AccountEvent::Voided { id, amount }),
+            ("transferred_out", id, amount) => Ok(AccountEvent::TransferredOut { id, amount }),
+            ("transferred_in", id, amount) => Ok(AccountEvent::TransferredIn { id, amount }),
+            ("transfer_disputed", id, amount) => Ok(AccountEvent::TransferDisputed { id, amount }),
+            ("transfer_resolved", id, amount) => Ok(AccountEvent::TransferResolved { id, amount }),
+            ("transfer_reversed", id, amount) => Ok(AccountEvent::TransferReversed { id, amount }),
+            ("refunded", id, amount) => Ok(AccountEvent::Refunded { id, amount }),
+            ("fee_charged", id, amount) => Ok(AccountEvent::FeeCharged { id, amount }),
+            ("interest_accrued", id, amount) => Ok(AccountEvent::InterestAccrued { id, amount }),
+            _ => Err(TransactionError::storage("Unknown event kind")),
+        }
+    }
+}
+
+/*
+ * An event as recorded by the bank, sequence is the input position of the transaction that
+ * produced it (a chargeback produces two events with the same sequence) and timestamp the time it
+ * was recorded in milliseconds since the unix epoch
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub struct Event {
+    pub sequence: u64,
+    pub timestamp: u64,
+    pub client_id: ClientId,
+    pub event: AccountEvent,
+}
+
+impl Event {
+    pub fn new(sequence: u64, timestamp: u64, client_id: ClientId, event: AccountEvent) -> Self {
+        Self {
+            sequence,
+            timestamp,
+            client_id,
+            event,
+        }
+    }
+}
+
+/* Point in time for account_at, both bounds are inclusive */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AsOf {
+    Sequence(u64),
+    Timestamp(u64),
+}
+
+impl AsOf {
+    pub fn includes(&self, event: &Event) -> bool {
+        match *self {
+            AsOf::Sequence(sequence) => event.sequence <= sequence,
+            AsOf::Timestamp(timestamp) => event.timestamp <= timestamp,
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use crate::account::TransactionResult;
+use crate::id::ClientId;
+use crate::transaction::transaction_error::TransactionError;
+use crate::transaction::transaction_type::TransactionType;
+use ahash::AHashMap;
+use csv::{ReaderBuilder, Trim};
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::Deserialize;
+use std::error::Error;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+/* The tiers read for a type, and client if the fee is an override */
+type FeeRows = AHashMap<(Option<ClientId>, TransactionType), Vec<(Option<Decimal>, Fee)>>;
+
+/* Fees are rounded to the four decimal places of input amounts */
+const FEE_SCALE: u32 = 4;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Fee {
+    Flat(Decimal),
+    /* Percent of the amount, e.g. 1.5 charges 1.5% */
+    Percentage(Decimal),
+    /* The fee of the highest tier whose lower bound the amount reaches, nothing below the first */
+    Tiered(Vec<(Decimal, Fee)>),
+}
+
+impl Fee {
+    pub fn tiered(mut tiers: Vec<(Decimal, Fee)>) -> Self {
+        tiers.sort_by_key(|(from, _)| *from);
+        Fee::Tiered(tiers)
+    }
+
+    /* The fee for a transaction of amount */
+    pub fn charge(&self, amount: Decimal) -> TransactionResult<Decimal> {
+        match self {
+            Fee::Flat(fee) => Ok(*fee),
+            Fee::Percentage(percent) => amount
+                .checked_mul(*percent)
+                .and_then(|fee| fee.checked_div(Decimal::ONE_HUNDRED))
+                .map(|fee| {
+                    fee.round_dp_with_strategy(FEE_SCALE, RoundingStrategy::MidpointAwayFromZero)
+                })
+                .ok_or_else(TransactionError::overflow),
+            Fee::Tiered(tiers) => match tiers.iter().rev().find(|(from, _)| amount >= *from) {
+                Some((_, fee)) => fee.charge(amount),
+                None => Ok(Decimal::ZERO),
+            },
+        }
+    }
+}
+
+/*
+ * Fees by transaction type, charged on the amount the transaction moved (e.g. the disputed amount
+ * for a chargeback), a client's own fee for a type replaces the schedule's, so a zero fee waives it
+ */
+#[derive(Clone, Default, Debug)]
+pub struct FeeSchedule {
+    fees: AHashMap<TransactionType, Fee>,
+    client_fees: AHashMap<(ClientId, TransactionType), Fee>,
+}
+
+/*
+ * One line of a fee schedule file, fee is flat or percentage, several lines for the same type and
+ * client are the tiers of a tiered fee, each starting from its from amount (0 if empty)
+ */
+#[derive(Deserialize)]
+struct FeeRow {
+    #[serde(rename = "type")]
+    t_type: TransactionType,
+    client: Option<ClientId>,
+    fee: String,
+    amount: Decimal,
+    from: Option<Decimal>,
+}
+
+impl FeeSchedule {
+    pub fn new() -> Self {
+        FeeSchedule::default()
+    }
+
+    pub fn with_fee(mut self, t_type: TransactionType, fee: Fee) -> Self {
+        self.fees.insert(t_type, fee);
+        self
+    }
+
+    pub fn with_client_fee(
+        mut self,
+        client_id: ClientId,
+        t_type: TransactionType,
+        fee: Fee,
+    ) -> Self {
+        self.client_fees.insert((client_id, t_type), fee);
+        self
+    }
+
+    pub fn fee(&self, client_id: ClientId, t_type: &TransactionType) -> Option<&Fee> {
+        self.client_fees
+            .get(&(client_id, t_type.clone()))
+            .or_else(|| self.fees.get(t_type))
+    }
+
+    pub fn open<P>(path: P) -> Result<Self, Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+    {
+        FeeSchedule::from_reader(File::open(path)?)
+    }
+
+    pub fn from_reader<R>(reader: R) -> Result<Self, Box<dyn Error>>
+    where
+        R: io::Read,
+    {
+        let mut tiers = FeeRows::new();
+        let mut reader = ReaderBuilder::new().trim(Trim::All).from_reader(reader);
+        for row in reader.deserialize() {
+            let row: FeeRow = row?;
+            let fee = match row.fee.as_str() {
+                "flat" => Fee::Flat(row.amount),
+                "percentage" => Fee::Percentage(row.amount),
+                fee => return Err(From::from(format!("Unknown fee {}", fee))),
+            };
+            tiers
+                .entry((row.client, row.t_type))
+                .or_default()
+                .push((row.from, fee));
+        }
+
+        let mut schedule = FeeSchedule::new();
+        for ((client_id, t_type), mut tiers) in tiers {
+            let fee = match tiers.as_slice() {
+                [(None, _)] => tiers.remove(0).1,
+                _ => Fee::tiered(
+                    tiers
+                        .into_iter()
+                        .map(|(from, fee)| (from.unwrap_or_default(), fee))
+                        .collect(),
+                ),
+            };
+            schedule = match client_id {
+                Some(client_id) => schedule.with_client_fee(client_id, t_type, fee),
+                None => schedule.with_fee(t_type, fee),
+            };
+        }
+
+        Ok(schedule)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::id::client;
+    use crate::transaction::transaction_error::TransactionErrorKind;
+    use std::str::FromStr;
+
+    fn decimal_str(decimal: &str) -> Decimal {
+        Decimal::from_str(decimal).unwrap()
+    }
+
+    #[test]
+    fn test_charge() {
+        assert_eq!(
+            decimal_str("0.5"),
+            Fee::Flat(decimal_str("0.5"))
+                .charge(decimal_str("100"))
+                .unwrap()
+        );
+        assert_eq!(
+            decimal_str("0.0123"),
+            Fee::Percentage(decimal_str("1.5"))
+                .charge(decimal_str("0.8225"))
+                .unwrap()
+        );
+
+        let tiered = Fee::tiered(vec![
+            (decimal_str("1000"), Fee::Percentage(decimal_str("1"))),
+            (decimal_str("10"), Fee::Flat(decimal_str("2"))),
+        ]);
+        assert_eq!(Decimal::ZERO, tiered.charge(decimal_str("5")).unwrap());
+        assert_eq!(decimal_str("2"), tiered.charge(decimal_str("999")).unwrap());
+        assert_eq!(
+            decimal_str("15"),
+            tiered.charge(decimal_str("1500")).unwrap()
+        );
+
+        let err = Fee::Percentage(decimal_str("2"))
+            .charge(Decimal::MAX)
+            .unwrap_err();
+        assert_eq!(&TransactionErrorKind::Overflow, err.kind());
+    }
+
+    #[test]
+    fn test_from_reader() {
+        let schedule = FeeSchedule::from_reader(
+            format!(
+                "type,client,fee,amount,from
+                withdrawal,,flat,0.25,
+                chargeback,,flat,15,
+                chargeback,,percentage,2,1000
+                withdrawal,{},flat,0,",
+                client(7)
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            Some(&Fee::Flat(decimal_str("0.25"))),
+            schedule.fee(client(1), &TransactionType::Withdrawal)
+        );
+        assert_eq!(
+            Some(&Fee::Flat(Decimal::ZERO)),
+            schedule.fee(client(7), &TransactionType::Withdrawal)
+        );
+        assert_eq!(
+            Some(&Fee::Tiered(vec![
+                (Decimal::ZERO, Fee::Flat(decimal_str("15"))),
+                (decimal_str("1000"), Fee::Percentage(decimal_str("2"))),
+            ])),
+            schedule.fee(client(7), &TransactionType::Chargeback)
+        );
+        assert!(schedule.fee(client(1), &TransactionType::Deposit).is_none());
+
+        assert!(FeeSchedule::from_reader(
+            "type,client,fee,amount,from\nwithdrawal,,free,0,".as_bytes()
+        )
+        .is_err());
+    }
+}
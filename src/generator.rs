@@ -0,0 +1,368 @@
+use crate::account::decimal_normalize_serialize;
+use crate::id::{ClientId, Id, TransactionId};
+use crate::transaction::Transaction;
+use ahash::AHashMap;
+use csv::WriterBuilder;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rust_decimal::prelude::RoundingStrategy;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::io;
+
+const HEADERS: [&str; 4] = ["type", "client", "tx", "amount"];
+
+#[derive(Clone, Copy, Debug)]
+pub struct TypeMix {
+    pub deposit: u32,
+    pub withdrawal: u32,
+    pub dispute: u32,
+}
+
+impl Default for TypeMix {
+    fn default() -> Self {
+        Self {
+            deposit: 70,
+            withdrawal: 25,
+            dispute: 5,
+        }
+    }
+}
+
+/*
+ * Every opened dispute is eventually settled, chargeback_ratio is the share of those settled with
+ * a chargeback rather than a resolve, malformed_rate is the share of rows that are garbage
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct WorkloadConfig {
+    pub clients: u16,
+    pub transactions: u32,
+    pub mix: TypeMix,
+    pub chargeback_ratio: f64,
+    pub malformed_rate: f64,
+    pub seed: u64,
+}
+
+impl Default for WorkloadConfig {
+    fn default() -> Self {
+        Self {
+            clients: 100,
+            transactions: 10_000,
+            mix: TypeMix::default(),
+            chargeback_ratio: 0.1,
+            malformed_rate: 0.0,
+            seed: 0,
+        }
+    }
+}
+
+/*
+ * The columns of the accounts output, for a bank without fees, overdrafts or authorizations, so
+ * that only a dispute of spent funds leaves anything other than zero past locked
+ */
+#[derive(Serialize, PartialEq, Debug)]
+pub struct ExpectedAccount {
+    #[serde(rename = "client")]
+    pub client_id: ClientId,
+    #[serde(serialize_with = "decimal_normalize_serialize")]
+    pub available: Decimal,
+    #[serde(serialize_with = "decimal_normalize_serialize")]
+    pub held: Decimal,
+    #[serde(serialize_with = "decimal_normalize_serialize")]
+    pub total: Decimal,
+    pub locked: bool,
+    #[serde(serialize_with = "decimal_normalize_serialize")]
+    pub authorized: Decimal,
+    #[serde(serialize_with = "decimal_normalize_serialize")]
+    pub fees: Decimal,
+    #[serde(serialize_with = "decimal_normalize_serialize")]
+    pub overdraft: Decimal,
+    #[serde(serialize_with = "decimal_normalize_serialize")]
+    pub debt: Decimal,
+}
+
+impl ExpectedAccount {
+    fn new(client_id: u16) -> Self {
+        Self {
+            client_id: client(client_id),
+            available: Decimal::new(0, 4),
+            held: Decimal::new(0, 4),
+            total: Decimal::new(0, 4),
+            locked: false,
+            authorized: Decimal::new(0, 4),
+            fees: Decimal::new(0, 4),
+            overdraft: Decimal::new(0, 4),
+            debt: Decimal::new(0, 4),
+        }
+    }
+
+    /* Without an overdraft limit all of a negative available balance is debt */
+    fn update_debt(&mut self) {
+        self.debt = (-self.available).max(Decimal::new(0, 4));
+    }
+}
+
+/* The model numbers clients and transactions, which every id type can hold */
+fn client(client_id: u16) -> ClientId {
+    ClientId::from_sequence(u64::from(client_id)).expect("client ids hold every u16")
+}
+
+fn tx(id: u32) -> TransactionId {
+    TransactionId::from_sequence(u64::from(id)).expect("transaction ids hold every u32")
+}
+
+enum Row {
+    Transaction(Transaction),
+    Malformed([String; 4]),
+}
+
+/*
+ * The generator keeps its own model of the expected account state, independent of Account, so the
+ * expected output can be used as an oracle for the engine
+ */
+pub struct Generator {
+    config: WorkloadConfig,
+    rng: ChaCha8Rng,
+    next_id: u32,
+    accounts: BTreeMap<u16, ExpectedAccount>,
+    deposits: AHashMap<u32, (u16, Decimal)>,
+    undisputed: Vec<u32>,
+    disputed: Vec<u32>,
+}
+
+impl Generator {
+    pub fn new(config: WorkloadConfig) -> Self {
+        Self {
+            config,
+            rng: ChaCha8Rng::seed_from_u64(config.seed),
+            next_id: 1,
+            accounts: BTreeMap::new(),
+            deposits: AHashMap::new(),
+            undisputed: Vec::new(),
+            disputed: Vec::new(),
+        }
+    }
+
+    pub fn write<W>(&mut self, writer: W) -> Result<(), Box<dyn Error>>
+    where
+        W: io::Write,
+    {
+        let mut writer = WriterBuilder::new().has_headers(false).from_writer(writer);
+        writer.write_record(HEADERS)?;
+        for _ in 0..self.config.transactions {
+            match self.next_row() {
+                Row::Transaction(transaction) => writer.serialize(transaction)?,
+                Row::Malformed(record) => writer.write_record(&record)?,
+            }
+        }
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    pub fn expected(&self) -> impl Iterator<Item = &ExpectedAccount> {
+        self.accounts.values()
+    }
+
+    /*
+     * Written as output_bank writes the accounts, with the same columns and in client order, so
+     * that it is identical to the output of a bank without fees, overdrafts or other rules
+     */
+    pub fn write_expected<W>(&self, writer: W) -> Result<(), Box<dyn Error>>
+    where
+        W: io::Write,
+    {
+        /* Ordered by the id rather than its number, which differ for string ids */
+        let mut accounts: Vec<&ExpectedAccount> = self.expected().collect();
+        accounts.sort_by_key(|account| account.client_id);
+
+        let mut writer = csv::Writer::from_writer(writer);
+        for account in accounts {
+            writer.serialize(account)?;
+        }
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    fn next_id(&mut self) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    fn client_id(&mut self) -> u16 {
+        self.rng.gen_range(1..=self.config.clients)
+    }
+
+    fn amount(&mut self) -> Decimal {
+        Decimal::new(self.rng.gen_range(1..=10_000_000), 4)
+    }
+
+    fn account(&mut self, client_id: u16) -> &mut ExpectedAccount {
+        self.accounts
+            .entry(client_id)
+            .or_insert_with(|| ExpectedAccount::new(client_id))
+    }
+
+    fn next_row(&mut self) -> Row {
+        if self.rng.gen_bool(self.config.malformed_rate) {
+            return self.malformed();
+        }
+
+        let mix = self.config.mix;
+        let settle = if self.disputed.is_empty() {
+            0
+        } else {
+            mix.dispute
+        };
+        let roll = self
+            .rng
+            .gen_range(0..mix.deposit + mix.withdrawal + mix.dispute + settle);
+
+        if roll < mix.deposit {
+            self.deposit()
+        } else if roll < mix.deposit + mix.withdrawal {
+            self.withdrawal()
+        } else if roll < mix.deposit + mix.withdrawal + mix.dispute {
+            self.dispute()
+        } else {
+            self.settle()
+        }
+    }
+
+    fn deposit(&mut self) -> Row {
+        let (client_id, id, amount) = (self.client_id(), self.next_id(), self.amount());
+        let account = self.account(client_id);
+        if !account.locked {
+            account.available += amount;
+            account.total += amount;
+            account.update_debt();
+            self.deposits.insert(id, (client_id, amount));
+            self.undisputed.push(id);
+        }
+
+        Row::Transaction(Transaction::new_deposit(client(client_id), tx(id), amount))
+    }
+
+    fn withdrawal(&mut self) -> Row {
+        let (client_id, id) = (self.client_id(), self.next_id());
+        let available = self.account(client_id).available;
+        let amount = if available > Decimal::ZERO && self.rng.gen_bool(0.8) {
+            (available * Decimal::new(self.rng.gen_range(1..=10_000), 4))
+                .round_dp_with_strategy(4, RoundingStrategy::ToZero)
+        } else {
+            self.amount()
+        };
+
+        let account = self.account(client_id);
+        if !account.locked && amount <= account.available {
+            account.available -= amount;
+            account.total -= amount;
+        }
+
+        Row::Transaction(Transaction::new_withdrawal(
+            client(client_id),
+            tx(id),
+            amount,
+        ))
+    }
+
+    fn dispute(&mut self) -> Row {
+        if self.undisputed.is_empty() {
+            return self.deposit();
+        }
+
+        let index = self.rng.gen_range(0..self.undisputed.len());
+        let id = self.undisputed.swap_remove(index);
+        let (client_id, amount) = self.deposits[&id];
+
+        let account = self.account(client_id);
+        if !account.locked {
+            account.held += amount;
+            account.available -= amount;
+            account.update_debt();
+            self.disputed.push(id);
+        }
+
+        Row::Transaction(Transaction::new_dispute(client(client_id), tx(id)))
+    }
+
+    fn settle(&mut self) -> Row {
+        let index = self.rng.gen_range(0..self.disputed.len());
+        let id = self.disputed.swap_remove(index);
+        let (client_id, amount) = self.deposits[&id];
+        let chargeback = self.rng.gen_bool(self.config.chargeback_ratio);
+
+        let account = self.account(client_id);
+        let locked = account.locked;
+        if !locked {
+            account.held -= amount;
+            if chargeback {
+                account.total -= amount;
+                account.locked = true;
+            } else {
+                account.available += amount;
+            }
+            account.update_debt();
+        }
+
+        if chargeback {
+            Row::Transaction(Transaction::new_chargeback(client(client_id), tx(id)))
+        } else {
+            if !locked {
+                self.undisputed.push(id);
+            }
+            Row::Transaction(Transaction::new_resolve(client(client_id), tx(id)))
+        }
+    }
+
+    fn malformed(&mut self) -> Row {
+        let (number, id, amount) = (self.client_id(), self.next_id(), self.amount());
+        let (client_id, id, amount) = (
+            client(number).to_string(),
+            tx(id).to_string(),
+            amount.to_string(),
+        );
+        let record = match self.rng.gen_range(0..4) {
+            0 => ["foobar".to_owned(), client_id, id, amount],
+            1 => ["deposit".to_owned(), String::new(), id, amount],
+            2 => ["withdrawal".to_owned(), client_id, id, "1.0.0".to_owned()],
+            _ => {
+                /* Parses, but is rejected by the account, which is still opened */
+                self.account(number);
+                ["deposit".to_owned(), client_id, id, String::new()]
+            }
+        };
+
+        Row::Malformed(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generate(config: WorkloadConfig) -> Vec<u8> {
+        let mut output = Vec::new();
+        Generator::new(config).write(&mut output).unwrap();
+        output
+    }
+
+    #[test]
+    fn test_seed_is_reproducible() {
+        let config = WorkloadConfig {
+            transactions: 1_000,
+            malformed_rate: 0.05,
+            ..WorkloadConfig::default()
+        };
+
+        assert_eq!(generate(config), generate(config));
+        assert_ne!(
+            generate(config),
+            generate(WorkloadConfig { seed: 1, ..config })
+        );
+    }
+}
@@ -0,0 +1,389 @@
+/* tonic::Status is large, but it is what every handler has to return */
+#![allow(clippy::result_large_err)]
+
+use crate::account::{Account, TransactionResult};
+use crate::bank::{Bank, TransactionListener};
+use crate::storage::Storage;
+use crate::transaction::transaction_type::TransactionType;
+use crate::transaction::Transaction;
+use proto::payments_server::{Payments, PaymentsServer};
+use proto::{BatchResult, SubmitResult, WatchRequest};
+use rust_decimal::Decimal;
+use std::convert::TryFrom;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
+use tonic::{Request, Response, Status, Streaming};
+
+pub mod proto {
+    tonic::include_proto!("payments");
+}
+
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:50051";
+
+/* Watchers that fall this many updates behind are disconnected */
+const UPDATES_CAPACITY: usize = 1024;
+
+impl From<&Account> for proto::Account {
+    fn from(account: &Account) -> Self {
+        Self {
+            client: u32::from(account.client_id()),
+            available: account.available().normalize().to_string(),
+            held: account.held().normalize().to_string(),
+            total: account.total().normalize().to_string(),
+            locked: account.locked(),
+            authorized: account.authorized().normalize().to_string(),
+            fees: account.fees().normalize().to_string(),
+            overdraft: account.overdraft().normalize().to_string(),
+            debt: account.debt().normalize().to_string(),
+        }
+    }
+}
+
+fn transaction_from_proto(transaction: proto::Transaction) -> Result<Transaction, Status> {
+    let t_type = match proto::TransactionType::from_i32(transaction.r#type) {
+        Some(proto::TransactionType::Deposit) => TransactionType::Deposit,
+        Some(proto::TransactionType::Withdrawal) => TransactionType::Withdrawal,
+        Some(proto::TransactionType::Dispute) => TransactionType::Dispute,
+        Some(proto::TransactionType::Resolve) => TransactionType::Resolve,
+        Some(proto::TransactionType::Chargeback) => TransactionType::Chargeback,
+        Some(proto::TransactionType::Authorize) => TransactionType::Authorize,
+        Some(proto::TransactionType::Capture) => TransactionType::Capture,
+        Some(proto::TransactionType::Void) => TransactionType::Void,
+        Some(proto::TransactionType::Transfer) => TransactionType::Transfer,
+        Some(proto::TransactionType::Refund) => TransactionType::Refund,
+        Some(proto::TransactionType::Unspecified) => {
+            return Err(Status::invalid_argument("Transaction type is required"))
+        }
+        None => return Err(Status::invalid_argument("Unknown transaction type")),
+    };
+    let client_id = u16::try_from(transaction.client)
+        .map_err(|_| Status::invalid_argument("Client id out of range"))?;
+    let amount = transaction
+        .amount
+        .map(|amount| Decimal::from_str(&amount))
+        .transpose()
+        .map_err(|err| Status::invalid_argument(err.to_string()))?;
+    let to = transaction
+        .to
+        .map(u16::try_from)
+        .transpose()
+        .map_err(|_| Status::invalid_argument("Client id out of range"))?;
+
+    Ok(Transaction {
+        t_type,
+        client_id,
+        id: transaction.tx,
+        amount,
+        to,
+        timestamp: transaction.timestamp,
+    })
+}
+
+fn submit_result(result: TransactionResult<()>) -> Result<SubmitResult, Status> {
+    match result {
+        Ok(()) => Ok(SubmitResult {
+            accepted: true,
+            error: String::new(),
+        }),
+        Err(err) if err.kind().is_storage() => Err(Status::internal(err.to_string())),
+        Err(err) => Ok(SubmitResult {
+            accepted: false,
+            error: format!("{:?}", err.kind()),
+        }),
+    }
+}
+
+/*
+ * Broadcasts the balances of every account a transaction touched, including the transactions
+ * the bank makes itself, while the bank is still locked so that updates are sent in the order
+ * they were applied
+ */
+struct UpdatesListener {
+    updates: broadcast::Sender<proto::Account>,
+}
+
+impl TransactionListener for UpdatesListener {
+    fn on_transaction(
+        &mut self,
+        _transaction: &Transaction,
+        _result: &TransactionResult<()>,
+        accounts: &[Account],
+    ) -> TransactionResult<()> {
+        for account in accounts {
+            /* Fails only when nobody is watching */
+            let _ = self.updates.send(proto::Account::from(account));
+        }
+
+        Ok(())
+    }
+}
+
+/* Transactions are applied one at a time on the blocking pool, as storage backends may block */
+pub struct BankService<S> {
+    bank: Arc<Mutex<Bank<S>>>,
+    updates: broadcast::Sender<proto::Account>,
+}
+
+impl<S> BankService<S>
+where
+    S: Storage + Send + 'static,
+{
+    pub fn new(bank: Bank<S>) -> Self {
+        let updates = broadcast::channel(UPDATES_CAPACITY).0;
+        let bank = bank.with_listener(UpdatesListener {
+            updates: updates.clone(),
+        });
+
+        Self {
+            bank: Arc::new(Mutex::new(bank)),
+            updates,
+        }
+    }
+
+    pub fn into_server(self) -> PaymentsServer<Self> {
+        PaymentsServer::new(self)
+    }
+
+    async fn with_bank<T, F>(&self, f: F) -> Result<T, Status>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Bank<S>) -> Result<T, Status> + Send + 'static,
+    {
+        let bank = self.bank.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut bank = bank
+                .lock()
+                .map_err(|_| Status::internal("Bank lock poisoned"))?;
+            f(&mut bank)
+        })
+        .await
+        .map_err(|err| Status::internal(err.to_string()))?
+    }
+
+    async fn submit(&self, transaction: Transaction) -> Result<SubmitResult, Status> {
+        self.with_bank(move |bank| submit_result(bank.transact(transaction)))
+            .await
+    }
+}
+
+fn current_account<S>(bank: &Bank<S>, client_id: u16) -> Result<Option<proto::Account>, Status>
+where
+    S: Storage,
+{
+    Ok(bank
+        .account(client_id)
+        .map_err(|err| Status::internal(err.to_string()))?
+        .map(|account| proto::Account::from(account.as_ref())))
+}
+
+#[tonic::async_trait]
+impl<S> Payments for BankService<S>
+where
+    S: Storage + Send + 'static,
+{
+    async fn submit_transaction(
+        &self,
+        request: Request<proto::Transaction>,
+    ) -> Result<Response<SubmitResult>, Status> {
+        let transaction = transaction_from_proto(request.into_inner())?;
+
+        Ok(Response::new(self.submit(transaction).await?))
+    }
+
+    /* Malformed transactions are counted as rejected rather than failing the batch */
+    async fn submit_batch(
+        &self,
+        request: Request<Streaming<proto::Transaction>>,
+    ) -> Result<Response<BatchResult>, Status> {
+        let mut transactions = request.into_inner();
+        let mut batch = BatchResult::default();
+
+        while let Some(transaction) = transactions.message().await? {
+            let accepted = match transaction_from_proto(transaction) {
+                Ok(transaction) => self.submit(transaction).await?.accepted,
+                Err(_) => false,
+            };
+
+            if accepted {
+                batch.accepted += 1;
+            } else {
+                batch.rejected += 1;
+            }
+        }
+
+        Ok(Response::new(batch))
+    }
+
+    type WatchAccountStream = Pin<Box<dyn Stream<Item = Result<proto::Account, Status>> + Send>>;
+
+    async fn watch_account(
+        &self,
+        request: Request<WatchRequest>,
+    ) -> Result<Response<Self::WatchAccountStream>, Status> {
+        let client = request.into_inner().client;
+        let client_id = u16::try_from(client)
+            .map_err(|_| Status::invalid_argument("Client id out of range"))?;
+
+        /* Subscribed before reading the current balances so that no update is missed */
+        let updates =
+            BroadcastStream::new(self.updates.subscribe()).filter_map(move |update| match update {
+                Ok(account) if account.client == client => Some(Ok(account)),
+                Ok(_) => None,
+                Err(BroadcastStreamRecvError::Lagged(_)) => Some(Err(Status::data_loss(
+                    "Watcher fell behind and updates were dropped",
+                ))),
+            });
+        let current = self
+            .with_bank(move |bank| current_account(bank, client_id))
+            .await?;
+
+        Ok(Response::new(Box::pin(
+            tokio_stream::iter(current.map(Ok)).chain(updates),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proto::payments_client::PaymentsClient;
+    use proto::TransactionType;
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::transport::{Channel, Server};
+
+    async fn client() -> PaymentsClient<Channel> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(
+            Server::builder()
+                .add_service(BankService::new(Bank::new()).into_server())
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+
+        PaymentsClient::connect(format!("http://{}", address))
+            .await
+            .unwrap()
+    }
+
+    fn transaction(
+        t_type: TransactionType,
+        client: u32,
+        tx: u32,
+        amount: Option<&str>,
+    ) -> proto::Transaction {
+        proto::Transaction {
+            r#type: t_type as i32,
+            client,
+            tx,
+            amount: amount.map(str::to_string),
+            to: None,
+            timestamp: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_submit_transaction() {
+        let mut client = client().await;
+        let result = client
+            .submit_transaction(transaction(TransactionType::Deposit, 1, 1, Some("2")))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(result.accepted);
+
+        let result = client
+            .submit_transaction(transaction(TransactionType::Withdrawal, 1, 2, Some("3")))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(!result.accepted);
+        assert_eq!("NegativeBalance", result.error);
+
+        let status = client
+            .submit_transaction(transaction(TransactionType::Deposit, 70_000, 3, Some("1")))
+            .await
+            .unwrap_err();
+        assert_eq!(tonic::Code::InvalidArgument, status.code());
+
+        let status = client
+            .submit_transaction(proto::Transaction {
+                client: 1,
+                tx: 3,
+                amount: Some("1".to_string()),
+                ..proto::Transaction::default()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(tonic::Code::InvalidArgument, status.code());
+    }
+
+    #[tokio::test]
+    async fn test_submit_batch_and_watch() {
+        let mut client = client().await;
+        client
+            .submit_transaction(transaction(TransactionType::Deposit, 1, 1, Some("2")))
+            .await
+            .unwrap();
+
+        let mut watch = client
+            .watch_account(WatchRequest { client: 1 })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!("2", watch.message().await.unwrap().unwrap().available);
+
+        let batch = client
+            .submit_batch(tokio_stream::iter(vec![
+                transaction(TransactionType::Deposit, 2, 2, Some("5")),
+                transaction(TransactionType::Dispute, 1, 1, None),
+                transaction(TransactionType::Deposit, 1, 3, Some("not a number")),
+                transaction(TransactionType::Chargeback, 1, 1, None),
+            ]))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(3, batch.accepted);
+        assert_eq!(1, batch.rejected);
+
+        let disputed = watch.message().await.unwrap().unwrap();
+        assert_eq!("0", disputed.available);
+        assert_eq!("2", disputed.held);
+        let charged_back = watch.message().await.unwrap().unwrap();
+        assert_eq!("0", charged_back.total);
+        assert!(charged_back.locked);
+    }
+
+    #[tokio::test]
+    async fn test_watch_transfer_recipient() {
+        let mut client = client().await;
+        client
+            .submit_transaction(transaction(TransactionType::Deposit, 1, 1, Some("5")))
+            .await
+            .unwrap();
+
+        let mut watch = client
+            .watch_account(WatchRequest { client: 2 })
+            .await
+            .unwrap()
+            .into_inner();
+        let result = client
+            .submit_transaction(proto::Transaction {
+                to: Some(2),
+                ..transaction(TransactionType::Transfer, 1, 2, Some("3"))
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(result.accepted);
+
+        let received = watch.message().await.unwrap().unwrap();
+        assert_eq!(2, received.client);
+        assert_eq!("3", received.available);
+    }
+}
@@ -0,0 +1,391 @@
+use serde::de::{self, DeserializeOwned, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::str::{self, FromStr};
+#[cfg(any(feature = "client-id-uuid", feature = "tx-id-uuid"))]
+use uuid::Uuid;
+
+/*
+ * Client ids are u16 and transaction ids u32 by default, which keeps accounts and the maps of
+ * their transactions compact. Wider ids are chosen by feature, when more than one is enabled for
+ * the same id the widest (string, then uuid, u64 and u32) is used
+ */
+#[cfg(feature = "client-id-string")]
+pub type ClientId = StrId;
+#[cfg(all(feature = "client-id-uuid", not(feature = "client-id-string")))]
+pub type ClientId = Uuid;
+#[cfg(all(
+    feature = "client-id-u64",
+    not(any(feature = "client-id-string", feature = "client-id-uuid"))
+))]
+pub type ClientId = u64;
+#[cfg(all(
+    feature = "client-id-u32",
+    not(any(
+        feature = "client-id-string",
+        feature = "client-id-uuid",
+        feature = "client-id-u64"
+    ))
+))]
+pub type ClientId = u32;
+#[cfg(not(any(
+    feature = "client-id-string",
+    feature = "client-id-uuid",
+    feature = "client-id-u64",
+    feature = "client-id-u32"
+)))]
+pub type ClientId = u16;
+
+#[cfg(feature = "tx-id-string")]
+pub type TransactionId = StrId;
+#[cfg(all(feature = "tx-id-uuid", not(feature = "tx-id-string")))]
+pub type TransactionId = Uuid;
+#[cfg(all(
+    feature = "tx-id-u64",
+    not(any(feature = "tx-id-string", feature = "tx-id-uuid"))
+))]
+pub type TransactionId = u64;
+#[cfg(not(any(
+    feature = "tx-id-string",
+    feature = "tx-id-uuid",
+    feature = "tx-id-u64"
+)))]
+pub type TransactionId = u32;
+
+/* Their protocol and schema hold the default ids */
+#[cfg(all(
+    any(feature = "grpc", feature = "postgres"),
+    any(
+        feature = "client-id-string",
+        feature = "client-id-uuid",
+        feature = "client-id-u64",
+        feature = "client-id-u32",
+        feature = "tx-id-string",
+        feature = "tx-id-uuid",
+        feature = "tx-id-u64"
+    )
+))]
+compile_error!("the grpc and postgres features only support the default u16 and u32 ids");
+
+pub trait Id:
+    Copy
+    + Eq
+    + Hash
+    + Ord
+    + Debug
+    + Display
+    + FromStr
+    + Serialize
+    + DeserializeOwned
+    + Send
+    + Sync
+    + 'static
+{
+    /*
+     * The id for a number of a sequence, for transactions made by the bank and generated
+     * workloads, None when the number is out of range
+     */
+    fn from_sequence(sequence: u64) -> Option<Self>;
+
+    /* The number of the sequence the id is for, None when from_sequence never gives it */
+    fn sequence(&self) -> Option<u64>;
+
+    /* A number for the id that is the same in every process, to spread ids over partitions */
+    fn bucket(&self) -> u64;
+}
+
+impl Id for u16 {
+    fn from_sequence(sequence: u64) -> Option<Self> {
+        u16::try_from(sequence).ok()
+    }
+
+    fn sequence(&self) -> Option<u64> {
+        Some(u64::from(*self))
+    }
+
+    fn bucket(&self) -> u64 {
+        u64::from(*self)
+    }
+}
+
+impl Id for u32 {
+    fn from_sequence(sequence: u64) -> Option<Self> {
+        u32::try_from(sequence).ok()
+    }
+
+    fn sequence(&self) -> Option<u64> {
+        Some(u64::from(*self))
+    }
+
+    fn bucket(&self) -> u64 {
+        u64::from(*self)
+    }
+}
+
+impl Id for u64 {
+    fn from_sequence(sequence: u64) -> Option<Self> {
+        Some(sequence)
+    }
+
+    fn sequence(&self) -> Option<u64> {
+        Some(*self)
+    }
+
+    fn bucket(&self) -> u64 {
+        *self
+    }
+}
+
+/* Numbers are the low bits of an otherwise nil UUID */
+#[cfg(any(feature = "client-id-uuid", feature = "tx-id-uuid"))]
+impl Id for Uuid {
+    fn from_sequence(sequence: u64) -> Option<Self> {
+        Some(Uuid::from_u64_pair(0, sequence))
+    }
+
+    fn sequence(&self) -> Option<u64> {
+        match self.as_u64_pair() {
+            (0, sequence) => Some(sequence),
+            _ => None,
+        }
+    }
+
+    fn bucket(&self) -> u64 {
+        self.as_u64_pair().1
+    }
+}
+
+/* Numbers are written in decimal, buckets are the FNV-1a hash of the bytes */
+impl Id for StrId {
+    fn from_sequence(sequence: u64) -> Option<Self> {
+        sequence.to_string().parse().ok()
+    }
+
+    /* Only the decimal string from_sequence writes, e.g. not "007" */
+    fn sequence(&self) -> Option<u64> {
+        self.as_str()
+            .parse()
+            .ok()
+            .filter(|sequence| StrId::from_sequence(*sequence).as_ref() == Some(self))
+    }
+
+    fn bucket(&self) -> u64 {
+        self.as_str()
+            .bytes()
+            .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+            })
+    }
+}
+
+/* Ids for tests, so that they build and run with every id type */
+#[cfg(test)]
+pub(crate) fn client(sequence: u64) -> ClientId {
+    ClientId::from_sequence(sequence).expect("test client ids are in range")
+}
+
+#[cfg(test)]
+pub(crate) fn tx(sequence: u64) -> TransactionId {
+    TransactionId::from_sequence(sequence).expect("test transaction ids are in range")
+}
+
+const STR_ID_CAPACITY: usize = 64;
+
+/*
+ * A string id of up to 64 bytes, held inline so that it is as cheap to copy and hash as the
+ * integer ids. Stored by SQLite as a blob, which unlike text is never converted to a number
+ */
+#[derive(Clone, Copy)]
+pub struct StrId {
+    len: u8,
+    bytes: [u8; STR_ID_CAPACITY],
+}
+
+impl StrId {
+    pub fn as_str(&self) -> &str {
+        /* Only ever built from a whole str */
+        str::from_utf8(&self.bytes[..self.len as usize]).unwrap_or_default()
+    }
+}
+
+#[derive(Debug)]
+pub struct StrIdError;
+
+impl Display for StrIdError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "ids must be 1 to {} bytes long", STR_ID_CAPACITY)
+    }
+}
+
+impl Error for StrIdError {}
+
+impl FromStr for StrId {
+    type Err = StrIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() || s.len() > STR_ID_CAPACITY {
+            return Err(StrIdError);
+        }
+
+        let mut bytes = [0; STR_ID_CAPACITY];
+        bytes[..s.len()].copy_from_slice(s.as_bytes());
+
+        Ok(Self {
+            len: s.len() as u8,
+            bytes,
+        })
+    }
+}
+
+impl PartialEq for StrId {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for StrId {}
+
+impl Hash for StrId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
+    }
+}
+
+impl PartialOrd for StrId {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for StrId {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl Display for StrId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Debug for StrId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl Serialize for StrId {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+struct StrIdVisitor;
+
+impl<'de> Visitor<'de> for StrIdVisitor {
+    type Value = StrId;
+
+    fn expecting(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "a string of 1 to {} bytes", STR_ID_CAPACITY)
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        value.parse().map_err(E::custom)
+    }
+
+    /* Ids that look like numbers are given as numbers by self describing formats, e.g. JSON */
+    fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.visit_str(&value.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for StrId {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(StrIdVisitor)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl rusqlite::ToSql for StrId {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        Ok(rusqlite::types::ToSqlOutput::Borrowed(
+            rusqlite::types::ValueRef::Blob(self.as_str().as_bytes()),
+        ))
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl rusqlite::types::FromSql for StrId {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        str::from_utf8(value.as_blob()?)
+            .map_err(|err| rusqlite::types::FromSqlError::Other(Box::new(err)))?
+            .parse()
+            .map_err(|err| rusqlite::types::FromSqlError::Other(Box::new(err)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_sequence() {
+        assert_eq!(Some(7), u16::from_sequence(7));
+        assert_eq!(None, u16::from_sequence(u64::from(u16::MAX) + 1));
+        assert_eq!(None, u32::from_sequence(u64::from(u32::MAX) + 1));
+        assert_eq!(Some(u64::MAX), u64::from_sequence(u64::MAX));
+        assert_eq!("42", StrId::from_sequence(42).unwrap().as_str());
+    }
+
+    #[test]
+    fn test_sequence() {
+        assert_eq!(Some(7), 7u16.sequence());
+        assert_eq!(Some(u64::MAX), u64::MAX.sequence());
+        assert_eq!(Some(42), StrId::from_sequence(42).unwrap().sequence());
+        assert_eq!(None, "007".parse::<StrId>().unwrap().sequence());
+        assert_eq!(None, "acct-1".parse::<StrId>().unwrap().sequence());
+    }
+
+    #[test]
+    fn test_str_id() {
+        let id: StrId = "acct-0001".parse().unwrap();
+        assert_eq!("acct-0001", id.to_string());
+        assert_eq!(id, "acct-0001".parse().unwrap());
+        assert!(id < "acct-01".parse().unwrap());
+
+        assert!("".parse::<StrId>().is_err());
+        assert!("x".repeat(STR_ID_CAPACITY).parse::<StrId>().is_ok());
+        assert!("x".repeat(STR_ID_CAPACITY + 1).parse::<StrId>().is_err());
+    }
+
+    #[test]
+    fn test_str_id_csv() {
+        let mut reader = csv::Reader::from_reader("client\n007\n".as_bytes());
+        let rows: Vec<(StrId,)> = reader.deserialize().collect::<Result<_, _>>().unwrap();
+        assert_eq!("007", rows[0].0.as_str());
+
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.serialize(rows[0]).unwrap();
+        assert_eq!(
+            "007\n",
+            String::from_utf8(writer.into_inner().unwrap()).unwrap()
+        );
+    }
+}
@@ -0,0 +1,184 @@
+use crate::bank::Bank;
+use crate::storage::Storage;
+use crate::transaction::Transaction;
+use csv::{Reader, ReaderBuilder, Trim, Writer};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::io;
+use std::str::FromStr;
+
+const MALFORMED: &str = "Malformed";
+
+/* Layout of input files, the columns are the same for each */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InputFormat {
+    Csv,
+    Tsv,
+}
+
+impl InputFormat {
+    fn delimiter(&self) -> u8 {
+        match self {
+            InputFormat::Csv => b',',
+            InputFormat::Tsv => b'\t',
+        }
+    }
+}
+
+impl FromStr for InputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(InputFormat::Csv),
+            "tsv" => Ok(InputFormat::Tsv),
+            _ => Err(format!("unknown input format {}, expected csv or tsv", s)),
+        }
+    }
+}
+
+impl Display for InputFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            InputFormat::Csv => write!(f, "csv"),
+            InputFormat::Tsv => write!(f, "tsv"),
+        }
+    }
+}
+
+pub fn trimmed_csv_reader<R>(reader: R) -> Reader<R>
+where
+    R: io::Read,
+{
+    input_reader(InputFormat::Csv, reader)
+}
+
+pub fn input_reader<R>(format: InputFormat, reader: R) -> Reader<R>
+where
+    R: io::Read,
+{
+    let mut builder = ReaderBuilder::new();
+    builder.trim(Trim::All).delimiter(format.delimiter());
+    builder.from_reader(reader)
+}
+
+/* A row of the input that was not applied, error is the kind of error or Malformed */
+#[derive(Debug, PartialEq, Serialize)]
+struct Rejection {
+    line: u64,
+    error: String,
+    message: String,
+}
+
+pub fn add_records_to_bank<S, T>(
+    bank: Bank<S>,
+    reader: Reader<T>,
+) -> Result<Bank<S>, Box<dyn Error>>
+where
+    S: Storage,
+    T: io::Read,
+{
+    add_records_to_bank_logging(bank, reader, io::sink())
+}
+
+/*
+ * As add_records_to_bank, writing every row that could not be parsed or was rejected to the
+ * rejection log as CSV, with its line in the input
+ */
+pub fn add_records_to_bank_logging<S, T, W>(
+    mut bank: Bank<S>,
+    mut reader: Reader<T>,
+    rejections: W,
+) -> Result<Bank<S>, Box<dyn Error>>
+where
+    S: Storage,
+    T: io::Read,
+    W: io::Write,
+{
+    let mut rejections = Writer::from_writer(rejections);
+    let headers = reader.headers()?.clone();
+
+    for record in reader.records() {
+        let (line, result) = match record {
+            Ok(record) => (
+                record.position().map(|position| position.line()),
+                record.deserialize::<Transaction>(Some(&headers)),
+            ),
+            Err(err) => (err.position().map(|position| position.line()), Err(err)),
+        };
+        let line = line.unwrap_or_default();
+
+        let rejection = match result {
+            Ok(transaction) => match bank.transact(transaction) {
+                Ok(()) => continue,
+                Err(err) if err.kind().is_storage() => return Err(Box::new(err)),
+                Err(err) => Rejection {
+                    line,
+                    error: format!("{:?}", err.kind()),
+                    message: err.message().to_string(),
+                },
+            },
+            Err(err) => Rejection {
+                line,
+                error: MALFORMED.to_string(),
+                message: err.to_string(),
+            },
+        };
+        rejections.serialize(rejection)?;
+    }
+    rejections.flush()?;
+
+    Ok(bank)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::id::{client, tx};
+
+    #[test]
+    fn test_rejection_log() {
+        let input = format!(
+            "type\tclient\ttx\tamount\n\
+            deposit\t{0}\t{1}\t5\n\
+            withdrawal\t{0}\t{2}\t10\n\
+            foobar\t{0}\t{3}\t1\n",
+            client(1),
+            tx(1),
+            tx(2),
+            tx(3)
+        );
+        let mut rejections = Vec::new();
+
+        let bank = add_records_to_bank_logging(
+            Bank::new(),
+            input_reader(InputFormat::Tsv, input.as_bytes()),
+            &mut rejections,
+        )
+        .unwrap();
+
+        assert_eq!(
+            "5",
+            bank.account(client(1))
+                .unwrap()
+                .unwrap()
+                .total()
+                .to_string()
+        );
+        let rejections = String::from_utf8(rejections).unwrap();
+        let lines: Vec<&str> = rejections.lines().collect();
+        assert_eq!(3, lines.len());
+        assert_eq!("line,error,message", lines[0]);
+        assert!(lines[1].starts_with("3,NegativeBalance,"));
+        assert!(lines[2].starts_with("4,Malformed,"));
+    }
+
+    #[test]
+    fn test_input_format() {
+        assert_eq!(Ok(InputFormat::Tsv), "tsv".parse());
+        assert!("json".parse::<InputFormat>().is_err());
+        assert_eq!("csv", InputFormat::Csv.to_string());
+    }
+}
@@ -0,0 +1,271 @@
+use crate::account::TransactionResult;
+use crate::transaction::transaction_error::TransactionError;
+use csv::{ReaderBuilder, Trim};
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::Deserialize;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+/* Interest is rounded to the four decimal places of input amounts */
+const INTEREST_SCALE: u32 = 4;
+
+/* A calendar day, interest is accrued one day at a time */
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct Date {
+    year: i32,
+    month: u32,
+    day: u32,
+}
+
+impl Date {
+    pub fn new(year: i32, month: u32, day: u32) -> Option<Self> {
+        if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+            return None;
+        }
+
+        Some(Self { year, month, day })
+    }
+
+    pub fn next(&self) -> Self {
+        match (self.day < days_in_month(self.year, self.month), self.month) {
+            (true, _) => Date::new(self.year, self.month, self.day + 1),
+            (false, 12) => Date::new(self.year + 1, 1, 1),
+            (false, month) => Date::new(self.year, month + 1, 1),
+        }
+        .expect("the day after a valid date is valid")
+    }
+}
+
+fn is_leap_year(year: i32) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/* YYYY-MM-DD */
+impl FromStr for Date {
+    type Err = Box<dyn Error>;
+
+    fn from_str(date: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid date {}, expected YYYY-MM-DD", date);
+        let parts: Vec<&str> = date.split('-').collect();
+        match parts.as_slice() {
+            [year, month, day] => Date::new(year.parse()?, month.parse()?, day.parse()?)
+                .ok_or_else(|| From::from(invalid())),
+            _ => Err(From::from(invalid())),
+        }
+    }
+}
+
+impl Display for Date {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/* How a day's share of the annual rate is counted */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DayCount {
+    Actual360,
+    Actual365,
+    /* 30E/360, every month accrues 30 days whatever its length, February's last day the rest */
+    Thirty360,
+}
+
+impl DayCount {
+    /* The days accrued on date and the days in a year */
+    fn days(&self, date: Date) -> (u32, u32) {
+        match self {
+            DayCount::Actual360 => (1, 360),
+            DayCount::Actual365 => (1, 365),
+            DayCount::Thirty360 => {
+                let next = date.next();
+                let (from, to) = (date.day.min(30), next.day.min(30));
+                if next.month == date.month {
+                    (to - from, 360)
+                } else {
+                    (30 + to - from, 360)
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for DayCount {
+    type Err = Box<dyn Error>;
+
+    fn from_str(day_count: &str) -> Result<Self, Self::Err> {
+        match day_count {
+            "actual/360" => Ok(DayCount::Actual360),
+            "actual/365" => Ok(DayCount::Actual365),
+            "30/360" => Ok(DayCount::Thirty360),
+            _ => Err(From::from(format!("Unknown day count {}", day_count))),
+        }
+    }
+}
+
+/*
+ * Annual rates in percent by the lowest available balance they apply to, the whole balance earns
+ * the rate of the highest tier it reaches and nothing below the first
+ */
+#[derive(Clone, Debug)]
+pub struct InterestPolicy {
+    day_count: DayCount,
+    rates: Vec<(Decimal, Decimal)>,
+}
+
+/* One line of a rate table file */
+#[derive(Deserialize)]
+struct RateRow {
+    from: Decimal,
+    rate: Decimal,
+}
+
+impl InterestPolicy {
+    pub fn new(day_count: DayCount) -> Self {
+        Self {
+            day_count,
+            rates: Vec::new(),
+        }
+    }
+
+    pub fn with_rate(mut self, from: Decimal, rate: Decimal) -> Self {
+        self.rates.push((from, rate));
+        self.rates.sort_by_key(|(from, _)| *from);
+        self
+    }
+
+    pub fn rate(&self, balance: Decimal) -> Decimal {
+        self.rates
+            .iter()
+            .rev()
+            .find(|(from, _)| balance >= *from)
+            .map(|(_, rate)| *rate)
+            .unwrap_or_default()
+    }
+
+    /* The interest a balance earns on date, nothing for a balance that is not positive */
+    pub fn interest(&self, balance: Decimal, date: Date) -> TransactionResult<Decimal> {
+        if balance <= Decimal::ZERO {
+            return Ok(Decimal::ZERO);
+        }
+
+        let (days, year) = self.day_count.days(date);
+        balance
+            .checked_mul(self.rate(balance))
+            .and_then(|interest| interest.checked_mul(Decimal::from(days)))
+            .and_then(|interest| interest.checked_div(Decimal::from(year)))
+            .and_then(|interest| interest.checked_div(Decimal::ONE_HUNDRED))
+            .map(|interest| {
+                interest
+                    .round_dp_with_strategy(INTEREST_SCALE, RoundingStrategy::MidpointAwayFromZero)
+            })
+            .ok_or_else(TransactionError::overflow)
+    }
+
+    pub fn open<P>(path: P, day_count: DayCount) -> Result<Self, Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+    {
+        InterestPolicy::from_reader(File::open(path)?, day_count)
+    }
+
+    /* A rate table with a from and a rate column */
+    pub fn from_reader<R>(reader: R, day_count: DayCount) -> Result<Self, Box<dyn Error>>
+    where
+        R: io::Read,
+    {
+        let mut policy = InterestPolicy::new(day_count);
+        let mut reader = ReaderBuilder::new().trim(Trim::All).from_reader(reader);
+        for row in reader.deserialize() {
+            let row: RateRow = row?;
+            policy = policy.with_rate(row.from, row.rate);
+        }
+
+        Ok(policy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimal_str(decimal: &str) -> Decimal {
+        Decimal::from_str(decimal).unwrap()
+    }
+
+    fn date(date: &str) -> Date {
+        Date::from_str(date).unwrap()
+    }
+
+    #[test]
+    fn test_date() {
+        assert_eq!(date("2024-03-01"), date("2024-02-29").next());
+        assert_eq!(date("2025-01-01"), date("2024-12-31").next());
+        assert_eq!("2023-02-28", date("2023-02-28").to_string());
+        assert!(Date::from_str("2023-02-29").is_err());
+        assert!(Date::from_str("2023-13-01").is_err());
+        assert!(Date::from_str("20230101").is_err());
+    }
+
+    #[test]
+    fn test_day_count() {
+        let thirty = DayCount::Thirty360;
+        assert_eq!((1, 360), thirty.days(date("2023-01-15")));
+        assert_eq!((0, 360), thirty.days(date("2023-01-30")));
+        assert_eq!((1, 360), thirty.days(date("2023-01-31")));
+        assert_eq!((3, 360), thirty.days(date("2023-02-28")));
+        assert_eq!((1, 360), thirty.days(date("2024-02-28")));
+        assert_eq!((2, 360), thirty.days(date("2024-02-29")));
+        assert_eq!((1, 365), DayCount::Actual365.days(date("2023-01-31")));
+    }
+
+    #[test]
+    fn test_interest() {
+        let policy = InterestPolicy::from_reader(
+            "from,rate
+            1000,3.65
+            0,1.8"
+                .as_bytes(),
+            DayCount::Actual365,
+        )
+        .unwrap();
+        let day = date("2023-06-01");
+
+        assert_eq!(
+            decimal_str("0.1"),
+            policy.interest(decimal_str("1000"), day).unwrap()
+        );
+        /* 999 * 1.8% / 365 = 0.049265... */
+        assert_eq!(
+            decimal_str("0.0493"),
+            policy.interest(decimal_str("999"), day).unwrap()
+        );
+        assert_eq!(
+            Decimal::ZERO,
+            policy.interest(decimal_str("-5"), day).unwrap()
+        );
+
+        let policy = InterestPolicy::new(DayCount::Actual360)
+            .with_rate(decimal_str("10"), decimal_str("3.6"));
+        assert_eq!(
+            Decimal::ZERO,
+            policy.interest(decimal_str("9"), day).unwrap()
+        );
+        assert_eq!(
+            decimal_str("0.001"),
+            policy.interest(decimal_str("10"), day).unwrap()
+        );
+    }
+}
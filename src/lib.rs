@@ -1,3 +1,26 @@
-mod account;
+pub mod account;
+pub mod audit;
 pub mod bank;
+pub mod cli;
+pub mod clock;
+pub mod consumer;
+pub mod dispute;
+pub mod event;
+pub mod fee;
+pub mod generator;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod id;
+pub mod ingest;
+pub mod interest;
+pub mod output;
+pub mod overdraft;
+pub mod schedule;
+pub mod storage;
+pub mod tcp;
 pub mod transaction;
+pub mod validate;
+#[cfg(feature = "webhooks")]
+pub mod webhook;
+#[cfg(feature = "websocket")]
+pub mod websocket;
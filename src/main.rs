@@ -1,59 +1,320 @@
-use csv::{Reader, ReaderBuilder, Trim, Writer};
+use payments::audit::AuditLog;
 use payments::bank::Bank;
+use payments::cli::{Args, BankCommand, Command, Config, USAGE};
+use payments::clock::SimulatedClock;
+use payments::dispute::{DisputeAction, DisputePolicy};
+use payments::fee::FeeSchedule;
+#[cfg(feature = "grpc")]
+use payments::grpc::{self, BankService};
+use payments::ingest::{
+    add_records_to_bank, add_records_to_bank_logging, input_reader, InputFormat,
+};
+use payments::output::{output_bank, output_bank_at, output_open_disputes, output_statement};
+use payments::overdraft::OverdraftLimits;
+use payments::schedule::StandingOrders;
+use payments::storage::memory::MemoryStorage;
+#[cfg(feature = "postgres")]
+use payments::storage::postgres::PostgresStorage;
+#[cfg(feature = "sqlite")]
+use payments::storage::sqlite::SqliteStorage;
+use payments::storage::Storage;
+use payments::tcp::{self, serve, serve_with_threads};
+use payments::validate::validate;
+#[cfg(feature = "webhooks")]
+use payments::webhook::{WebhookConfig, WebhookDispatcher};
+#[cfg(feature = "websocket")]
+use payments::websocket::{self, Feed};
 use std::error::Error;
-use std::ffi::OsString;
 use std::fs::File;
-use std::{env, io};
+#[cfg(feature = "grpc")]
+use std::net::SocketAddr;
+use std::net::TcpListener;
+use std::path::Path;
+#[cfg(feature = "websocket")]
+use std::thread;
+use std::{env, io, process};
+#[cfg(feature = "grpc")]
+use tonic::transport::Server;
 
-fn get_first_arg() -> Result<OsString, Box<dyn Error>> {
-    match env::args_os().nth(1) {
-        None => Err(From::from("expect 1 argument, but found no arguments")),
-        Some(file_path) => Ok(file_path),
+fn output(config: &Config) -> Result<Box<dyn io::Write>, Box<dyn Error>> {
+    Ok(match &config.output {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout()),
+    })
+}
+
+/* The business rules, shared by every command */
+fn with_rules<S>(mut bank: Bank<S>, config: &Config) -> Result<Bank<S>, Box<dyn Error>>
+where
+    S: Storage,
+{
+    if let Some(start) = config.simulated_clock {
+        bank = bank.with_clock(SimulatedClock::new(start));
+    }
+
+    if let Some(max_age) = config.authorization_expiry {
+        bank = bank.with_authorization_expiry(max_age);
+    }
+
+    match (config.dispute_max_age, config.dispute_action) {
+        (Some(max_age), action) => {
+            let action = action.unwrap_or(DisputeAction::Resolve);
+            bank = bank.with_dispute_policy(DisputePolicy::new(max_age, action));
+        }
+        (None, Some(_)) => return Err(From::from("dispute-action needs dispute-max-age")),
+        (None, None) => {}
+    }
+
+    if let Some(file_path) = &config.fee_schedule {
+        bank = bank.with_fee_schedule(FeeSchedule::open(file_path)?);
+    }
+
+    if let Some(file_path) = &config.overdraft_limits {
+        bank = bank.with_overdraft_limits(OverdraftLimits::open(file_path)?);
+    }
+
+    if let Some(first_tx) = config.bank_tx {
+        bank = bank.with_bank_tx(first_tx)?;
     }
+
+    if let Some(file_path) = &config.standing_orders {
+        bank = bank.with_standing_orders(StandingOrders::open(file_path)?);
+    }
+
+    Ok(bank)
 }
 
-fn trimmed_csv_reader(file: File) -> Reader<File> {
-    let mut builder = ReaderBuilder::new();
-    builder.trim(Trim::All);
-    builder.from_reader(file)
+/* The WebSocket feed of account changes, served alongside either server */
+#[cfg(feature = "websocket")]
+fn with_feed<S>(bank: Bank<S>, config: &Config) -> Result<Bank<S>, Box<dyn Error>>
+where
+    S: Storage,
+{
+    let address = match &config.websocket {
+        Some(address) => address,
+        None => return Ok(bank),
+    };
+
+    let feed = Feed::new();
+    let listener = TcpListener::bind(address)?;
+    let bank = bank.with_listener(feed.listener());
+    thread::spawn(move || websocket::serve(feed, listener));
+
+    Ok(bank)
+}
+
+#[cfg(not(feature = "websocket"))]
+fn with_feed<S>(bank: Bank<S>, config: &Config) -> Result<Bank<S>, Box<dyn Error>> {
+    match config.websocket {
+        Some(_) => Err(From::from("the websocket feed needs the websocket feature")),
+        None => Ok(bank),
+    }
 }
 
-fn add_records_to_bank<T>(mut bank: Bank, mut reader: Reader<T>) -> Result<Bank, Box<dyn Error>>
+/* With --threads the runtime has that many worker threads, transactions are applied one at a time */
+#[cfg(feature = "grpc")]
+fn serve_grpc<S>(bank: Bank<S>, config: &Config) -> Result<(), Box<dyn Error>>
 where
-    T: io::Read,
+    S: Storage + Send + 'static,
 {
-    for transaction in reader.deserialize() {
-        bank.transact(match transaction {
-            Ok(t) => t,
-            Err(_) => continue, //TODO: deserialization failure, write to log
-        })
+    let address: SocketAddr = config
+        .address
+        .as_deref()
+        .unwrap_or(grpc::DEFAULT_ADDRESS)
+        .parse()?;
+    let mut runtime = tokio::runtime::Builder::new_multi_thread();
+    if let Some(threads) = config.threads {
+        runtime.worker_threads(threads);
     }
 
-    Ok(bank)
+    runtime.enable_all().build()?.block_on(
+        Server::builder()
+            .add_service(BankService::new(bank).into_server())
+            .serve(address),
+    )?;
+
+    Ok(())
 }
 
-fn output_bank(bank: Bank) -> Result<(), Box<dyn Error>> {
-    let mut writer = Writer::from_writer(io::stdout());
-    for account in bank.accounts().values() {
-        writer.serialize(account)?;
+#[cfg(not(feature = "grpc"))]
+fn serve_grpc<S>(_bank: Bank<S>, _config: &Config) -> Result<(), Box<dyn Error>> {
+    Err(From::from("serve-grpc needs the grpc feature"))
+}
+
+fn ingest<S>(bank: Bank<S>, input: &Path, config: &Config) -> Result<Bank<S>, Box<dyn Error>>
+where
+    S: Storage,
+{
+    let reader = input_reader(
+        config.format.unwrap_or(InputFormat::Csv),
+        File::open(input)?,
+    );
+
+    match &config.rejections {
+        Some(file_path) => add_records_to_bank_logging(bank, reader, File::create(file_path)?),
+        None => add_records_to_bank(bank, reader),
+    }
+}
+
+/* Always against a bank in memory, so that nothing is stored, audited or notified */
+fn validate_input(input: &Path, config: &Config) -> Result<(), Box<dyn Error>> {
+    let validation = config.validation();
+    let reader = input_reader(
+        config.format.unwrap_or(InputFormat::Csv),
+        File::open(input)?,
+    );
+
+    let report = validate(with_rules(Bank::new(), config)?, reader, &validation)?;
+    write!(output(config)?, "{}", report)?;
+
+    if report.exceeds(&validation) {
+        eprintln!(
+            "more than {} rejected or {} malformed rows",
+            validation.max_rejected, validation.max_malformed
+        );
+        process::exit(1);
     }
-    writer.flush()?;
 
     Ok(())
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    output_bank(add_records_to_bank(
-        Bank::new(),
-        trimmed_csv_reader(File::open(get_first_arg()?)?),
-    )?)?;
+fn run<S>(bank: Bank<S>, command: BankCommand, config: &Config) -> Result<(), Box<dyn Error>>
+where
+    S: Storage + Send + 'static,
+{
+    let mut bank = with_rules(bank, config)?;
+
+    if let Some(file_path) = &config.audit_log {
+        bank = bank.with_audit_log(AuditLog::open(file_path)?);
+    }
+
+    #[cfg(feature = "webhooks")]
+    let mut dispatcher = None;
+    #[cfg(feature = "webhooks")]
+    if let Some(config) = WebhookConfig::from_env()? {
+        let (listener, webhooks) = WebhookDispatcher::start(config)?;
+        bank = bank.with_listener(listener);
+        dispatcher = Some(webhooks);
+    }
+
+    let bank = match command {
+        BankCommand::Process {
+            input,
+            open_disputes,
+        } => {
+            let bank = ingest(bank, &input, config)?;
+            if let Some(file_path) = open_disputes {
+                output_open_disputes(&bank, File::create(file_path)?)?;
+            }
+            output_bank(&bank, output(config)?)?;
+            /* A summary line, so that the accounts can still be piped on STDOUT */
+            if config.fee_schedule.is_some() {
+                eprintln!("revenue {}", bank.revenue()?.normalize());
+            }
+            bank
+        }
+        BankCommand::Replay { as_of, input } => {
+            let bank = match input {
+                Some(input) => ingest(bank, &input, config)?,
+                None => bank,
+            };
+            output_bank_at(&bank, as_of, output(config)?)?;
+            bank
+        }
+        BankCommand::Statement { client_id, input } => {
+            let bank = match input {
+                Some(input) => ingest(bank, &input, config)?,
+                None => bank,
+            };
+            output_statement(&bank, client_id, output(config)?)?;
+            bank
+        }
+        BankCommand::Serve => {
+            let bank = with_feed(bank, config)?;
+            let address = config.address.as_deref().unwrap_or(tcp::DEFAULT_ADDRESS);
+            let listener = TcpListener::bind(address)?;
+            match config.threads {
+                Some(threads) => serve_with_threads(bank, listener, threads)?,
+                None => serve(bank, listener)?,
+            }
+            return Ok(());
+        }
+        BankCommand::ServeGrpc => {
+            serve_grpc(with_feed(bank, config)?, config)?;
+            return Ok(());
+        }
+    };
+
+    /* Gives queued notifications a chance to be delivered before exiting */
+    #[cfg(feature = "webhooks")]
+    if let Some(dispatcher) = dispatcher {
+        drop(bank);
+        dispatcher.join();
+    }
+    #[cfg(not(feature = "webhooks"))]
+    drop(bank);
 
     Ok(())
 }
 
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse(env::args().skip(1))?;
+    /* Help is printed even when the settings are invalid */
+    let config = args.config();
+
+    let command = match args.command {
+        Command::Help => {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+        Command::Validate { input } => return validate_input(&input, &config?),
+        Command::Bank(command) => command,
+    };
+    let config = config?;
+
+    #[cfg(feature = "postgres")]
+    if let Some(params) = &config.postgres {
+        return run(
+            Bank::with_storage(PostgresStorage::connect(params)?),
+            command,
+            &config,
+        );
+    }
+    #[cfg(not(feature = "postgres"))]
+    if config.postgres.is_some() {
+        return Err(From::from("postgres storage needs the postgres feature"));
+    }
+
+    #[cfg(feature = "sqlite")]
+    if let Some(file_path) = &config.sqlite {
+        return run(
+            Bank::with_storage(SqliteStorage::open(file_path)?),
+            command,
+            &config,
+        );
+    }
+    #[cfg(not(feature = "sqlite"))]
+    if config.sqlite.is_some() {
+        return Err(From::from("sqlite storage needs the sqlite feature"));
+    }
+
+    /* Only replays and statements read the event log, so only they pay for keeping it */
+    let storage = match command {
+        BankCommand::Replay { .. } | BankCommand::Statement { .. } => {
+            MemoryStorage::with_event_log()
+        }
+        _ => MemoryStorage::new(),
+    };
+    run(Bank::with_storage(storage), command, &config)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use csv::Writer;
+    use payments::generator::{Generator, WorkloadConfig};
+    use payments::id::{ClientId, Id, TransactionId};
+    use payments::ingest::trimmed_csv_reader;
     use payments::transaction::Transaction;
     use rust_decimal::prelude::Zero;
     use rust_decimal::Decimal;
@@ -65,6 +326,10 @@ mod tests {
         Decimal::from_str(decimal).unwrap()
     }
 
+    fn client(sequence: u64) -> ClientId {
+        ClientId::from_sequence(sequence).unwrap()
+    }
+
     fn file_writer(file_name: &str) -> Writer<File> {
         Writer::from_writer(File::create(file_name).unwrap())
     }
@@ -75,14 +340,15 @@ mod tests {
 
     fn add_deposits(
         writer: &mut Writer<File>,
-        client_id: u16,
+        client_id: u64,
         value: Decimal,
-        amount: u32,
-        offset: u32,
+        amount: u64,
+        offset: u64,
     ) {
         for id in offset..offset + amount {
+            let id = TransactionId::from_sequence(id).unwrap();
             writer
-                .serialize(Transaction::new_deposit(client_id, id, value))
+                .serialize(Transaction::new_deposit(client(client_id), id, value))
                 .unwrap();
         }
         writer.flush().unwrap()
@@ -102,20 +368,19 @@ mod tests {
         )
         .unwrap();
 
-        let accounts = bank.accounts();
-        let account_1 = &accounts[&1];
+        let account_1 = bank.account(client(1)).unwrap().unwrap();
         let expected = decimal_str("10.0505").mul(decimal_str("200000"));
         assert_eq!(&expected, account_1.available());
         assert_eq!(&Decimal::zero(), account_1.held());
         assert_eq!(&expected, account_1.total());
 
-        let account_2 = &accounts[&2];
+        let account_2 = bank.account(client(2)).unwrap().unwrap();
         let expected = decimal_str("20.7836").mul(decimal_str("500000"));
         assert_eq!(&expected, account_2.available());
         assert_eq!(&Decimal::zero(), account_2.held());
         assert_eq!(&expected, account_2.total());
 
-        let account_3 = &accounts[&3];
+        let account_3 = bank.account(client(3)).unwrap().unwrap();
         let expected = decimal_str("25700.1234").mul(decimal_str("100000"));
         assert_eq!(&expected, account_3.available());
         assert_eq!(&Decimal::zero(), account_3.held());
@@ -123,4 +388,37 @@ mod tests {
 
         delete_file(file_name);
     }
+
+    #[test]
+    fn test_generated_workload() {
+        let mut generator = Generator::new(WorkloadConfig {
+            clients: 50,
+            transactions: 50_000,
+            malformed_rate: 0.01,
+            seed: 7,
+            ..WorkloadConfig::default()
+        });
+        let mut input = Vec::new();
+        generator.write(&mut input).unwrap();
+
+        let bank = add_records_to_bank(Bank::new(), trimmed_csv_reader(&input[..])).unwrap();
+
+        assert_eq!(generator.expected().count(), bank.accounts().unwrap().len());
+        for expected in generator.expected() {
+            let account = bank.account(expected.client_id).unwrap().unwrap();
+            assert_eq!(&expected.available, account.available());
+            assert_eq!(&expected.held, account.held());
+            assert_eq!(&expected.total, account.total());
+            assert_eq!(expected.locked, account.locked());
+        }
+
+        /* The expected output is the accounts output byte for byte */
+        let (mut output, mut expected) = (Vec::new(), Vec::new());
+        output_bank(&bank, &mut output).unwrap();
+        generator.write_expected(&mut expected).unwrap();
+        assert_eq!(
+            String::from_utf8(expected).unwrap(),
+            String::from_utf8(output).unwrap()
+        );
+    }
 }
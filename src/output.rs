@@ -0,0 +1,184 @@
+use crate::account::{decimal_normalize_serialize, Account};
+use crate::bank::Bank;
+use crate::event::AsOf;
+use crate::id::{ClientId, TransactionId};
+use crate::storage::Storage;
+use csv::Writer;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::error::Error;
+use std::io;
+
+pub fn output_open_disputes<S, W>(bank: &Bank<S>, writer: W) -> Result<(), Box<dyn Error>>
+where
+    S: Storage,
+    W: io::Write,
+{
+    let mut writer = Writer::from_writer(writer);
+    for dispute in bank.open_disputes()? {
+        writer.serialize(dispute)?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+/* Accounts are written in client order, so that outputs of the same input can be compared */
+fn sorted_accounts<S>(bank: &Bank<S>) -> Result<Vec<Cow<'_, Account>>, Box<dyn Error>>
+where
+    S: Storage,
+{
+    let mut accounts = bank.accounts()?;
+    accounts.sort_by_key(|account| account.client_id());
+
+    Ok(accounts)
+}
+
+pub fn output_bank<S, W>(bank: &Bank<S>, writer: W) -> Result<(), Box<dyn Error>>
+where
+    S: Storage,
+    W: io::Write,
+{
+    let mut writer = Writer::from_writer(writer);
+    for account in sorted_accounts(bank)? {
+        writer.serialize(account)?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+/* As output_bank, with the balances replayed from the event log as of the given point */
+pub fn output_bank_at<S, W>(bank: &Bank<S>, as_of: AsOf, writer: W) -> Result<(), Box<dyn Error>>
+where
+    S: Storage,
+    W: io::Write,
+{
+    let mut writer = Writer::from_writer(writer);
+    for account in sorted_accounts(bank)? {
+        if let Some(account) = bank.account_at(account.client_id(), as_of)? {
+            writer.serialize(account)?;
+        }
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct StatementLine {
+    sequence: u64,
+    timestamp: u64,
+    event: &'static str,
+    tx: Option<TransactionId>,
+    amount: Option<Decimal>,
+    #[serde(serialize_with = "decimal_normalize_serialize")]
+    available: Decimal,
+    #[serde(serialize_with = "decimal_normalize_serialize")]
+    held: Decimal,
+    #[serde(serialize_with = "decimal_normalize_serialize")]
+    total: Decimal,
+}
+
+/* Every event recorded for the client, with its balances after each */
+pub fn output_statement<S, W>(
+    bank: &Bank<S>,
+    client_id: ClientId,
+    writer: W,
+) -> Result<(), Box<dyn Error>>
+where
+    S: Storage,
+    W: io::Write,
+{
+    let mut writer = Writer::from_writer(writer);
+    let mut account = Account::new(client_id);
+    for event in bank.events(client_id)?.iter() {
+        account.apply(&event.event)?;
+        writer.serialize(StatementLine {
+            sequence: event.sequence,
+            timestamp: event.timestamp,
+            event: event.event.kind(),
+            tx: event.event.id(),
+            amount: event.event.amount().map(|amount| amount.normalize()),
+            available: *account.available(),
+            held: *account.held(),
+            total: *account.total(),
+        })?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::SimulatedClock;
+    use crate::id::{client, tx};
+    use crate::storage::memory::MemoryStorage;
+    use crate::transaction::Transaction;
+    use rust_decimal::prelude::FromPrimitive;
+
+    fn bank() -> Bank {
+        let mut bank = Bank::with_storage(MemoryStorage::with_event_log())
+            .with_clock(SimulatedClock::new(1_000));
+        bank.transact(Transaction::new_deposit(
+            client(1),
+            tx(1),
+            Decimal::from_u8(5).unwrap(),
+        ))
+        .unwrap();
+        bank.transact(Transaction::new_deposit(
+            client(1),
+            tx(2),
+            Decimal::from_u8(3).unwrap(),
+        ))
+        .unwrap();
+        bank.transact(Transaction::new_dispute(client(1), tx(1)))
+            .unwrap();
+        bank
+    }
+
+    fn output<F>(write: F) -> String
+    where
+        F: FnOnce(&mut Vec<u8>) -> Result<(), Box<dyn Error>>,
+    {
+        let mut output = Vec::new();
+        write(&mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_output_statement() {
+        let bank = bank();
+
+        assert_eq!(
+            format!(
+                "sequence,timestamp,event,tx,amount,available,held,total\n\
+                0,1000,deposited,{0},5,5,0,5\n\
+                1,1000,deposited,{1},3,8,0,8\n\
+                2,1000,dispute_opened,{0},5,3,5,8\n",
+                tx(1),
+                tx(2)
+            ),
+            output(|writer| output_statement(&bank, client(1), writer))
+        );
+        assert_eq!(
+            "",
+            output(|writer| output_statement(&bank, client(2), writer))
+        );
+    }
+
+    #[test]
+    fn test_output_bank_at() {
+        let bank = bank();
+
+        let balances = output(|writer| output_bank_at(&bank, AsOf::Sequence(1), writer));
+        assert!(balances
+            .lines()
+            .nth(1)
+            .unwrap()
+            .starts_with(&format!("{},8,0,8,false", client(1))));
+    }
+}
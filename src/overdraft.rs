@@ -0,0 +1,112 @@
+use crate::id::ClientId;
+use ahash::AHashMap;
+use csv::{ReaderBuilder, Trim};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::error::Error;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+/*
+ * How far below zero each client's available balance may be taken by withdrawals and the like, a
+ * client's own limit replaces the default, which is none
+ */
+#[derive(Clone, Default, Debug)]
+pub struct OverdraftLimits {
+    default: Decimal,
+    limits: AHashMap<ClientId, Decimal>,
+}
+
+/* One line of an overdraft limits file, the default limit has no client */
+#[derive(Deserialize)]
+struct LimitRow {
+    client: Option<ClientId>,
+    limit: Decimal,
+}
+
+impl OverdraftLimits {
+    pub fn new() -> Self {
+        OverdraftLimits::default()
+    }
+
+    pub fn with_default(mut self, limit: Decimal) -> Self {
+        self.default = limit;
+        self
+    }
+
+    pub fn with_limit(mut self, client_id: ClientId, limit: Decimal) -> Self {
+        self.limits.insert(client_id, limit);
+        self
+    }
+
+    pub fn limit(&self, client_id: ClientId) -> Decimal {
+        self.limits.get(&client_id).copied().unwrap_or(self.default)
+    }
+
+    pub fn open<P>(path: P) -> Result<Self, Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+    {
+        OverdraftLimits::from_reader(File::open(path)?)
+    }
+
+    pub fn from_reader<R>(reader: R) -> Result<Self, Box<dyn Error>>
+    where
+        R: io::Read,
+    {
+        let mut limits = OverdraftLimits::new();
+        let mut reader = ReaderBuilder::new().trim(Trim::All).from_reader(reader);
+        for row in reader.deserialize() {
+            let row: LimitRow = row?;
+            if row.limit < Decimal::ZERO {
+                return Err(From::from(format!(
+                    "Negative overdraft limit {}",
+                    row.limit
+                )));
+            }
+
+            limits = match row.client {
+                Some(client_id) => limits.with_limit(client_id, row.limit),
+                None => limits.with_default(row.limit),
+            };
+        }
+
+        Ok(limits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::id::client;
+    use std::str::FromStr;
+
+    fn decimal_str(decimal: &str) -> Decimal {
+        Decimal::from_str(decimal).unwrap()
+    }
+
+    #[test]
+    fn test_from_reader() {
+        let limits = OverdraftLimits::from_reader(
+            format!(
+                "client,limit
+                ,50
+                {},1000
+                {},0",
+                client(7),
+                client(8)
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(decimal_str("50"), limits.limit(client(1)));
+        assert_eq!(decimal_str("1000"), limits.limit(client(7)));
+        assert_eq!(Decimal::ZERO, limits.limit(client(8)));
+        assert_eq!(Decimal::ZERO, OverdraftLimits::new().limit(client(1)));
+
+        let negative = format!("client,limit\n{},-5", client(1));
+        assert!(OverdraftLimits::from_reader(negative.as_bytes()).is_err());
+    }
+}
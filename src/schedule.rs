@@ -0,0 +1,474 @@
+use crate::account::TransactionResult;
+use crate::id::{ClientId, TransactionId};
+use crate::transaction::Transaction;
+use csv::{ReaderBuilder, Trim};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::error::Error;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+const MINUTE: u64 = 60_000;
+const MINUTES_PER_DAY: u64 = 24 * 60;
+/* A cron expression that matches nothing in this many years, e.g. the 31st of February, never will */
+const MAX_CRON_YEARS: i64 = 8;
+
+/* Days since 1970-01-01 to the civil date, proleptic Gregorian */
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    } as u32;
+
+    (year_of_era + era * 400 + i64::from(month <= 2), month, day)
+}
+
+/* Inverse of civil_from_days */
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let shifted_month = i64::from((month + 9) % 12);
+    let day_of_year = (153 * shifted_month + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+/* The values a cron field matches, as bits */
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct CronField {
+    bits: u64,
+    any: bool,
+}
+
+impl CronField {
+    /* Comma separated values, a-b ranges and * for every value, each optionally stepped by /n */
+    fn parse(field: &str, min: u32, max: u32) -> Result<Self, Box<dyn Error>> {
+        let invalid = || format!("Invalid cron field {}", field);
+        let mut bits = 0;
+        for item in field.split(',') {
+            let (range, step) = match item.split_once('/') {
+                Some((range, step)) => (range, step.parse::<u32>()?),
+                None => (item, 1),
+            };
+            let (from, to) = match range.split_once('-') {
+                _ if range == "*" => (min, max),
+                Some((from, to)) => (from.parse()?, to.parse()?),
+                None if step > 1 => (range.parse()?, max),
+                None => (range.parse()?, range.parse()?),
+            };
+            if step == 0 || from < min || to > max || from > to {
+                return Err(From::from(invalid()));
+            }
+
+            for value in (from..=to).step_by(step as usize) {
+                bits |= 1 << value;
+            }
+        }
+
+        Ok(Self {
+            bits,
+            any: field == "*",
+        })
+    }
+
+    fn matches(&self, value: u32) -> bool {
+        self.bits & (1 << value) != 0
+    }
+}
+
+/* minute hour day-of-month month day-of-week, in UTC */
+#[derive(Clone, PartialEq, Debug)]
+pub struct Cron {
+    minutes: CronField,
+    hours: CronField,
+    days: CronField,
+    months: CronField,
+    weekdays: CronField,
+}
+
+impl Cron {
+    /* As in cron, a day matches either field when both the day of month and of week are set */
+    fn matches_day(&self, day: u32, weekday: u32) -> bool {
+        match (self.days.any, self.weekdays.any) {
+            (false, false) => self.days.matches(day) || self.weekdays.matches(weekday),
+            _ => self.days.matches(day) && self.weekdays.matches(weekday),
+        }
+    }
+
+    /* The first whole minute at or after time that matches */
+    fn next(&self, time: u64) -> Option<u64> {
+        let mut minutes = match time % MINUTE {
+            0 => time / MINUTE,
+            _ => time / MINUTE + 1,
+        };
+        let (first_year, _, _) = civil_from_days((minutes / MINUTES_PER_DAY) as i64);
+        loop {
+            let days = (minutes / MINUTES_PER_DAY) as i64;
+            let (year, month, day) = civil_from_days(days);
+            if year - first_year > MAX_CRON_YEARS {
+                return None;
+            }
+
+            let minute_of_day = minutes % MINUTES_PER_DAY;
+            let (hour, minute) = ((minute_of_day / 60) as u32, (minute_of_day % 60) as u32);
+            /* 1970-01-01 was a Thursday */
+            let weekday = (days + 4).rem_euclid(7) as u32;
+            let start_of_day = days as u64 * MINUTES_PER_DAY;
+            if !self.months.matches(month) {
+                let (year, month) = if month == 12 {
+                    (year + 1, 1)
+                } else {
+                    (year, month + 1)
+                };
+                minutes = days_from_civil(year, month, 1) as u64 * MINUTES_PER_DAY;
+            } else if !self.matches_day(day, weekday) {
+                minutes = start_of_day + MINUTES_PER_DAY;
+            } else if !self.hours.matches(hour) {
+                minutes = start_of_day + u64::from(hour + 1) * 60;
+            } else if !self.minutes.matches(minute) {
+                minutes += 1;
+            } else {
+                return Some(minutes * MINUTE);
+            }
+        }
+    }
+}
+
+impl FromStr for Cron {
+    type Err = Box<dyn Error>;
+
+    fn from_str(cron: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = cron.split_whitespace().collect();
+        match fields.as_slice() {
+            [minutes, hours, days, months, weekdays] => {
+                let mut weekdays = CronField::parse(weekdays, 0, 7)?;
+                /* Both 0 and 7 are Sunday */
+                if weekdays.matches(7) {
+                    weekdays.bits |= 1;
+                }
+
+                Ok(Self {
+                    minutes: CronField::parse(minutes, 0, 59)?,
+                    hours: CronField::parse(hours, 0, 23)?,
+                    days: CronField::parse(days, 1, 31)?,
+                    months: CronField::parse(months, 1, 12)?,
+                    weekdays,
+                })
+            }
+            _ => Err(From::from(format!("Expected 5 cron fields in {}", cron))),
+        }
+    }
+}
+
+/* When a standing order pays, times are in milliseconds since the unix epoch */
+#[derive(Clone, PartialEq, Debug)]
+pub enum Schedule {
+    /* Every this many milliseconds from the order's start */
+    Interval(u64),
+    Cron(Cron),
+}
+
+impl Schedule {
+    /* The first payment at or after time, for an order starting at start */
+    fn next(&self, start: u64, time: u64) -> Option<u64> {
+        let time = time.max(start);
+        match self {
+            Schedule::Interval(interval) => {
+                let elapsed = time - start;
+                let intervals = match elapsed % interval {
+                    0 => elapsed / interval,
+                    _ => elapsed / interval + 1,
+                };
+                intervals
+                    .checked_mul(*interval)
+                    .and_then(|elapsed| start.checked_add(elapsed))
+            }
+            Schedule::Cron(cron) => cron.next(time),
+        }
+    }
+}
+
+/* A 5 field cron expression, or every followed by a number of ms, s, m, h or d, e.g. every 7d */
+impl FromStr for Schedule {
+    type Err = Box<dyn Error>;
+
+    fn from_str(schedule: &str) -> Result<Self, Self::Err> {
+        let interval = match schedule.strip_prefix("every ") {
+            Some(interval) => interval.trim(),
+            None => return Ok(Schedule::Cron(schedule.parse()?)),
+        };
+
+        let split = interval
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(interval.len());
+        let (count, unit) = interval.split_at(split);
+        let unit: u64 = match unit {
+            "ms" => 1,
+            "s" => 1_000,
+            "m" => MINUTE,
+            "h" => 60 * MINUTE,
+            "d" => MINUTES_PER_DAY * MINUTE,
+            _ => return Err(From::from(format!("Unknown interval unit in {}", schedule))),
+        };
+        match count.parse::<u64>()?.checked_mul(unit) {
+            Some(interval) if interval > 0 => Ok(Schedule::Interval(interval)),
+            _ => Err(From::from(format!("Invalid interval {}", schedule))),
+        }
+    }
+}
+
+/* Withdraws amount from the client on schedule, from start until end (inclusive) if any */
+#[derive(Clone, Debug)]
+pub struct StandingOrder {
+    pub client_id: ClientId,
+    pub amount: Decimal,
+    pub schedule: Schedule,
+    pub start: u64,
+    pub end: Option<u64>,
+}
+
+/* One line of a standing orders file */
+#[derive(Deserialize)]
+struct OrderRow {
+    client: ClientId,
+    amount: Decimal,
+    schedule: String,
+    start: u64,
+    end: Option<u64>,
+}
+
+/*
+ * The standing orders of the bank and when each pays next, payments are withdrawals numbered by
+ * the bank. The storage knows an order by its index, the row of the file, and keeps when it was
+ * last paid, so that a restarted bank resumes it
+ */
+#[derive(Clone, Debug, Default)]
+pub struct StandingOrders {
+    orders: Vec<(StandingOrder, Option<u64>)>,
+    resumed: bool,
+}
+
+impl StandingOrders {
+    pub fn new() -> Self {
+        StandingOrders::default()
+    }
+
+    pub fn with_order(mut self, order: StandingOrder) -> Self {
+        let next = order.schedule.next(order.start, order.start);
+        self.orders.push((order, next));
+        self
+    }
+
+    pub fn open<P>(path: P) -> Result<Self, Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+    {
+        StandingOrders::from_reader(File::open(path)?)
+    }
+
+    pub fn from_reader<R>(reader: R) -> Result<Self, Box<dyn Error>>
+    where
+        R: io::Read,
+    {
+        let mut orders = StandingOrders::new();
+        let mut reader = ReaderBuilder::new().trim(Trim::All).from_reader(reader);
+        for row in reader.deserialize() {
+            let row: OrderRow = row?;
+            orders = orders.with_order(StandingOrder {
+                client_id: row.client,
+                amount: row.amount,
+                schedule: row.schedule.parse()?,
+                start: row.start,
+                end: row.end,
+            });
+        }
+
+        Ok(orders)
+    }
+
+    /*
+     * Schedules every order after the time its last payment was due, as returned by paid for its
+     * index, only the first time it is called
+     */
+    pub fn resume<F>(&mut self, mut paid: F) -> TransactionResult<()>
+    where
+        F: FnMut(u64) -> TransactionResult<Option<u64>>,
+    {
+        if self.resumed {
+            return Ok(());
+        }
+
+        for (index, (order, next)) in self.orders.iter_mut().enumerate() {
+            if let Some(paid) = paid(index as u64)? {
+                *next = paid
+                    .checked_add(1)
+                    .and_then(|after| order.schedule.next(order.start, after));
+            }
+        }
+        self.resumed = true;
+
+        Ok(())
+    }
+
+    /*
+     * The index of the order with the earliest payment due by now and when it was due, in the
+     * order the orders were added when due at the same time
+     */
+    pub fn next_due(&self, now: u64) -> Option<(usize, u64)> {
+        self.orders
+            .iter()
+            .enumerate()
+            .filter_map(|(index, (order, next))| match next {
+                Some(next) if *next <= now && order.end.map(|end| *next <= end).unwrap_or(true) => {
+                    Some((index, *next))
+                }
+                _ => None,
+            })
+            .min_by_key(|(_, next)| *next)
+    }
+
+    /* The withdrawal of the order's payment due next as id, the order is then scheduled for its next */
+    pub fn pay(&mut self, index: usize, id: TransactionId) -> Option<Transaction> {
+        let (order, next) = self.orders.get_mut(index)?;
+        let due = (*next)?;
+        *next = due
+            .checked_add(1)
+            .and_then(|after| order.schedule.next(order.start, after));
+
+        Some(Transaction::new_withdrawal(
+            order.client_id,
+            id,
+            order.amount,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::id::{client, tx};
+
+    const DAY: u64 = MINUTES_PER_DAY * MINUTE;
+
+    fn time(year: i64, month: u32, day: u32, hour: u64, minute: u64) -> u64 {
+        days_from_civil(year, month, day) as u64 * DAY + hour * 60 * MINUTE + minute * MINUTE
+    }
+
+    #[test]
+    fn test_civil_days() {
+        assert_eq!(0, days_from_civil(1970, 1, 1));
+        assert_eq!((2000, 2, 29), civil_from_days(days_from_civil(2000, 2, 29)));
+        assert_eq!((2023, 12, 31), civil_from_days(19_722));
+    }
+
+    #[test]
+    fn test_cron() {
+        /* 09:30 on weekdays */
+        let cron: Cron = "30 9 * * 1-5".parse().unwrap();
+        /* Saturday 2023-06-03 */
+        let saturday = time(2023, 6, 3, 12, 0);
+        assert_eq!(Some(time(2023, 6, 5, 9, 30)), cron.next(saturday));
+        assert_eq!(
+            Some(time(2023, 6, 5, 9, 30)),
+            cron.next(time(2023, 6, 5, 9, 30))
+        );
+
+        let monthly: Cron = "0 0 31 */2 *".parse().unwrap();
+        assert_eq!(Some(time(2023, 7, 31, 0, 0)), monthly.next(saturday));
+        let never: Cron = "0 0 30 2 *".parse().unwrap();
+        assert_eq!(None, never.next(saturday));
+
+        assert!("60 * * * *".parse::<Cron>().is_err());
+        assert!("* * * *".parse::<Cron>().is_err());
+        assert!("*/0 * * * *".parse::<Cron>().is_err());
+    }
+
+    #[test]
+    fn test_schedule() {
+        assert_eq!(
+            Schedule::Interval(7 * DAY),
+            "every 7d".parse::<Schedule>().unwrap()
+        );
+        assert!("every 7w".parse::<Schedule>().is_err());
+        assert!("every 0s".parse::<Schedule>().is_err());
+
+        let hourly = Schedule::Interval(60 * MINUTE);
+        assert_eq!(Some(1_000), hourly.next(1_000, 0));
+        assert_eq!(Some(1_000 + 60 * MINUTE), hourly.next(1_000, 1_001));
+    }
+
+    #[test]
+    fn test_next_due() {
+        let mut orders = StandingOrders::from_reader(
+            format!(
+                "client,amount,schedule,start,end
+                {},10,every 1d,0,172800000
+                {},5,0 12 * * *,0,",
+                client(1),
+                client(2)
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        let mut due = Vec::new();
+        while let Some((index, time)) = orders.next_due(3 * DAY) {
+            let transaction = orders.pay(index, tx(100 + due.len() as u64)).unwrap();
+            due.push((time, transaction.client_id, transaction.id));
+        }
+        assert_eq!(
+            vec![
+                (0, client(1), tx(100)),
+                (DAY / 2, client(2), tx(101)),
+                (DAY, client(1), tx(102)),
+                (DAY + DAY / 2, client(2), tx(103)),
+                (2 * DAY, client(1), tx(104)),
+                (2 * DAY + DAY / 2, client(2), tx(105)),
+            ],
+            due
+        );
+    }
+
+    #[test]
+    fn test_resume() {
+        let mut orders = StandingOrders::new()
+            .with_order(StandingOrder {
+                client_id: client(1),
+                amount: Decimal::ONE,
+                schedule: "every 1d".parse().unwrap(),
+                start: 0,
+                end: None,
+            })
+            .with_order(StandingOrder {
+                client_id: client(2),
+                amount: Decimal::ONE,
+                schedule: "every 1d".parse().unwrap(),
+                start: DAY,
+                end: None,
+            });
+
+        orders
+            .resume(|index| Ok(if index == 0 { Some(DAY) } else { None }))
+            .unwrap();
+        assert_eq!(Some((1, DAY)), orders.next_due(3 * DAY));
+        orders.pay(1, tx(100)).unwrap();
+        assert_eq!(Some((0, 2 * DAY)), orders.next_due(3 * DAY));
+
+        /* Only the first time */
+        orders.resume(|_| Ok(Some(10 * DAY))).unwrap();
+        assert_eq!(Some((0, 2 * DAY)), orders.next_due(3 * DAY));
+    }
+}
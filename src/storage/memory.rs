@@ -0,0 +1,238 @@
+use crate::account::{Account, TransactionResult};
+use crate::dispute::{OpenDispute, OpenDisputes};
+use crate::event::Event;
+use crate::id::{ClientId, TransactionId};
+use crate::interest::Date;
+use crate::storage::Storage;
+use crate::transaction::transaction_error::TransactionError;
+use ahash::AHashMap;
+use rust_decimal::Decimal;
+use std::borrow::Cow;
+
+#[derive(Default)]
+pub struct MemoryStorage {
+    accounts: AHashMap<ClientId, Account>,
+    transactions: AHashMap<TransactionId, ClientId>,
+    position: u64,
+    disputes: OpenDisputes,
+    authorizations: OpenDisputes,
+    /* Only kept when enabled, as it grows with every transaction for the life of the storage */
+    events: Option<AHashMap<ClientId, Vec<Event>>>,
+    consumer_offsets: AHashMap<(String, u32), u64>,
+    revenue: Decimal,
+    interest_accrued: AHashMap<ClientId, Date>,
+    standing_orders_paid: AHashMap<u64, u64>,
+    bank_sequence: u64,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        MemoryStorage::default()
+    }
+
+    /* Also keeps the event log, for Bank::account_at and statements */
+    pub fn with_event_log() -> Self {
+        Self {
+            events: Some(AHashMap::new()),
+            ..MemoryStorage::default()
+        }
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn begin(&mut self) -> TransactionResult<()> {
+        Ok(())
+    }
+
+    fn commit(&mut self) -> TransactionResult<()> {
+        Ok(())
+    }
+
+    fn rollback(&mut self) -> TransactionResult<()> {
+        Ok(())
+    }
+
+    fn next_position(&mut self) -> TransactionResult<u64> {
+        let position = self.position;
+        self.position += 1;
+
+        Ok(position)
+    }
+
+    fn get_or_insert_client_id(
+        &mut self,
+        id: TransactionId,
+        client_id: ClientId,
+    ) -> TransactionResult<ClientId> {
+        Ok(*self.transactions.entry(id).or_insert(client_id))
+    }
+
+    fn account_mut(
+        &mut self,
+        client_id: ClientId,
+        _id: TransactionId,
+    ) -> TransactionResult<&mut Account> {
+        Ok(self
+            .accounts
+            .entry(client_id)
+            .or_insert_with(|| Account::new(client_id)))
+    }
+
+    fn account(&self, client_id: ClientId) -> TransactionResult<Option<Cow<'_, Account>>> {
+        Ok(self.accounts.get(&client_id).map(Cow::Borrowed))
+    }
+
+    fn accounts(&self) -> TransactionResult<Vec<Cow<'_, Account>>> {
+        Ok(self.accounts.values().map(Cow::Borrowed).collect())
+    }
+
+    fn open_dispute(
+        &mut self,
+        client_id: ClientId,
+        id: TransactionId,
+        position: u64,
+    ) -> TransactionResult<()> {
+        self.disputes.open(client_id, id, position);
+
+        Ok(())
+    }
+
+    fn close_dispute(&mut self, id: TransactionId) -> TransactionResult<()> {
+        self.disputes.close(&id);
+
+        Ok(())
+    }
+
+    fn pop_expired_dispute(
+        &mut self,
+        cutoff: u64,
+    ) -> TransactionResult<Option<(ClientId, TransactionId)>> {
+        Ok(self.disputes.pop_expired(cutoff))
+    }
+
+    fn open_disputes(&self) -> TransactionResult<Vec<OpenDispute>> {
+        let mut disputes: Vec<OpenDispute> = self
+            .disputes
+            .iter()
+            .filter_map(|(id, &(client_id, opened))| {
+                let transaction = self.accounts.get(&client_id)?.transaction(id)?;
+                Some(OpenDispute {
+                    client_id,
+                    id: *id,
+                    amount: transaction.amount,
+                    opened,
+                    age: self.position - opened,
+                })
+            })
+            .collect();
+        disputes.sort_by_key(|dispute| dispute.opened);
+
+        Ok(disputes)
+    }
+
+    fn open_authorization(
+        &mut self,
+        client_id: ClientId,
+        id: TransactionId,
+        position: u64,
+    ) -> TransactionResult<()> {
+        self.authorizations.open(client_id, id, position);
+
+        Ok(())
+    }
+
+    fn close_authorization(&mut self, id: TransactionId) -> TransactionResult<()> {
+        self.authorizations.close(&id);
+
+        Ok(())
+    }
+
+    fn pop_expired_authorization(
+        &mut self,
+        cutoff: u64,
+    ) -> TransactionResult<Option<(ClientId, TransactionId)>> {
+        Ok(self.authorizations.pop_expired(cutoff))
+    }
+
+    fn revenue(&self) -> TransactionResult<Decimal> {
+        Ok(self.revenue)
+    }
+
+    fn credit_revenue(&mut self, amount: Decimal) -> TransactionResult<()> {
+        self.revenue = self
+            .revenue
+            .checked_add(amount)
+            .ok_or_else(TransactionError::overflow)?;
+
+        Ok(())
+    }
+
+    fn append_events(&mut self, events: &[Event]) -> TransactionResult<()> {
+        if let Some(log) = &mut self.events {
+            for event in events {
+                log.entry(event.client_id).or_default().push(event.clone());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn events(&self, client_id: ClientId) -> TransactionResult<Cow<'_, [Event]>> {
+        let log = self.events.as_ref().ok_or_else(|| {
+            TransactionError::storage(
+                "The event log is not kept, see MemoryStorage::with_event_log",
+            )
+        })?;
+
+        Ok(log
+            .get(&client_id)
+            .map_or(Cow::Borrowed(&[][..]), |events| Cow::Borrowed(events)))
+    }
+
+    fn interest_accrued(&self, client_id: ClientId) -> TransactionResult<Option<Date>> {
+        Ok(self.interest_accrued.get(&client_id).copied())
+    }
+
+    fn set_interest_accrued(&mut self, client_id: ClientId, date: Date) -> TransactionResult<()> {
+        self.interest_accrued.insert(client_id, date);
+
+        Ok(())
+    }
+
+    fn standing_order_paid(&self, index: u64) -> TransactionResult<Option<u64>> {
+        Ok(self.standing_orders_paid.get(&index).copied())
+    }
+
+    fn set_standing_order_paid(&mut self, index: u64, due: u64) -> TransactionResult<()> {
+        self.standing_orders_paid.insert(index, due);
+
+        Ok(())
+    }
+
+    fn next_bank_sequence(&mut self) -> TransactionResult<u64> {
+        let sequence = self.bank_sequence;
+        self.bank_sequence += 1;
+
+        Ok(sequence)
+    }
+
+    fn consumer_offset(&self, topic: &str, partition: u32) -> TransactionResult<u64> {
+        Ok(self
+            .consumer_offsets
+            .get(&(topic.to_string(), partition))
+            .copied()
+            .unwrap_or_default())
+    }
+
+    fn set_consumer_offset(
+        &mut self,
+        topic: &str,
+        partition: u32,
+        offset: u64,
+    ) -> TransactionResult<()> {
+        self.consumer_offsets
+            .insert((topic.to_string(), partition), offset);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,129 @@
+pub mod memory;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+use crate::account::{Account, TransactionResult};
+use crate::dispute::OpenDispute;
+use crate::event::Event;
+use crate::id::{ClientId, TransactionId};
+use crate::interest::Date;
+use crate::transaction::transaction_error::TransactionError;
+use rust_decimal::Decimal;
+use std::borrow::Cow;
+use std::fmt::Display;
+
+/* Failures of the files and services the bank writes to are storage failures, which abort transact */
+pub(crate) fn storage_error<E>(err: E) -> TransactionError
+where
+    E: Display,
+{
+    TransactionError::storage(&err.to_string())
+}
+
+/*
+ * Backing store for the bank, every call to Bank::transact is wrapped in begin and commit (or
+ * rollback on a storage failure) so implementations can apply a transaction atomically
+ */
+pub trait Storage {
+    fn begin(&mut self) -> TransactionResult<()>;
+
+    fn commit(&mut self) -> TransactionResult<()>;
+
+    fn rollback(&mut self) -> TransactionResult<()>;
+
+    /* Returns the input position for the next transaction and advances it */
+    fn next_position(&mut self) -> TransactionResult<u64>;
+
+    /* Returns the client that owns the transaction id, assigning it to client_id if unseen */
+    fn get_or_insert_client_id(
+        &mut self,
+        id: TransactionId,
+        client_id: ClientId,
+    ) -> TransactionResult<ClientId>;
+
+    /*
+     * Returns the account, creating it if it does not exist, with at least transaction id loaded
+     * so that the account can apply a transaction referencing it
+     */
+    fn account_mut(
+        &mut self,
+        client_id: ClientId,
+        id: TransactionId,
+    ) -> TransactionResult<&mut Account>;
+
+    fn account(&self, client_id: ClientId) -> TransactionResult<Option<Cow<'_, Account>>>;
+
+    fn accounts(&self) -> TransactionResult<Vec<Cow<'_, Account>>>;
+
+    fn open_dispute(
+        &mut self,
+        client_id: ClientId,
+        id: TransactionId,
+        position: u64,
+    ) -> TransactionResult<()>;
+
+    fn close_dispute(&mut self, id: TransactionId) -> TransactionResult<()>;
+
+    /*
+     * Returns the oldest dispute opened before cutoff that has not been returned before, it stays
+     * in open_disputes until closed
+     */
+    fn pop_expired_dispute(
+        &mut self,
+        cutoff: u64,
+    ) -> TransactionResult<Option<(ClientId, TransactionId)>>;
+
+    /* Open disputes ordered by the position they were opened at */
+    fn open_disputes(&self) -> TransactionResult<Vec<OpenDispute>>;
+
+    fn open_authorization(
+        &mut self,
+        client_id: ClientId,
+        id: TransactionId,
+        position: u64,
+    ) -> TransactionResult<()>;
+
+    fn close_authorization(&mut self, id: TransactionId) -> TransactionResult<()>;
+
+    /* As pop_expired_dispute, for authorizations that still hold funds */
+    fn pop_expired_authorization(
+        &mut self,
+        cutoff: u64,
+    ) -> TransactionResult<Option<(ClientId, TransactionId)>>;
+
+    /* The bank's revenue account, which every fee charged is credited to */
+    fn revenue(&self) -> TransactionResult<Decimal>;
+
+    fn credit_revenue(&mut self, amount: Decimal) -> TransactionResult<()>;
+
+    /* Appends to the event log, events are never updated or removed */
+    fn append_events(&mut self, events: &[Event]) -> TransactionResult<()>;
+
+    /* Events of the client in the order they were appended */
+    fn events(&self, client_id: ClientId) -> TransactionResult<Cow<'_, [Event]>>;
+
+    /* The last day interest was accrued to the client for, None if it never was */
+    fn interest_accrued(&self, client_id: ClientId) -> TransactionResult<Option<Date>>;
+
+    fn set_interest_accrued(&mut self, client_id: ClientId, date: Date) -> TransactionResult<()>;
+
+    /* When the last payment of the standing order at index was due, None if it was never paid */
+    fn standing_order_paid(&self, index: u64) -> TransactionResult<Option<u64>>;
+
+    fn set_standing_order_paid(&mut self, index: u64, due: u64) -> TransactionResult<()>;
+
+    /* Returns the number of the next transaction made by the bank, from 0, and advances it */
+    fn next_bank_sequence(&mut self) -> TransactionResult<u64>;
+
+    /* Offset of the next record to consume from the partition of the topic, 0 if none were */
+    fn consumer_offset(&self, topic: &str, partition: u32) -> TransactionResult<u64>;
+
+    fn set_consumer_offset(
+        &mut self,
+        topic: &str,
+        partition: u32,
+        offset: u64,
+    ) -> TransactionResult<()>;
+}
@@ -0,0 +1,609 @@
+use crate::account::{Account, TransactionResult};
+use crate::dispute::OpenDispute;
+use crate::event::{AccountEvent, Event};
+use crate::interest::Date;
+use crate::storage::Storage;
+use crate::transaction::transaction_error::TransactionError;
+use crate::transaction::transaction_lite::TransactionLite;
+use ahash::AHashMap;
+use postgres::error::SqlState;
+use postgres::{Client, NoTls, Row};
+use rust_decimal::Decimal;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::hash_map::Entry;
+use std::convert::TryFrom;
+
+const MIGRATIONS: [(i32, &str); 9] = [
+    (
+        1,
+        include_str!("../../migrations/postgres/0001_create_bank.sql"),
+    ),
+    (
+        2,
+        include_str!("../../migrations/postgres/0002_create_events.sql"),
+    ),
+    (
+        3,
+        include_str!("../../migrations/postgres/0003_create_consumer_offsets.sql"),
+    ),
+    (
+        4,
+        include_str!("../../migrations/postgres/0004_create_authorizations.sql"),
+    ),
+    (
+        5,
+        include_str!("../../migrations/postgres/0005_add_transfer_counterparty.sql"),
+    ),
+    (
+        6,
+        include_str!("../../migrations/postgres/0006_create_revenue.sql"),
+    ),
+    (
+        7,
+        include_str!("../../migrations/postgres/0007_add_overdraft_limit.sql"),
+    ),
+    (
+        8,
+        include_str!("../../migrations/postgres/0008_create_interest_accrued.sql"),
+    ),
+    (
+        9,
+        include_str!("../../migrations/postgres/0009_create_standing_orders_paid.sql"),
+    ),
+];
+
+/* Arbitrary key, held while migrating so that concurrently starting processes migrate once */
+const MIGRATION_LOCK: i64 = 0x7061_796d_656e_7473;
+
+impl From<postgres::Error> for TransactionError {
+    fn from(error: postgres::Error) -> Self {
+        match error.code() {
+            Some(&SqlState::T_R_DEADLOCK_DETECTED) | Some(&SqlState::T_R_SERIALIZATION_FAILURE) => {
+                TransactionError::conflict(&error.to_string())
+            }
+            _ => TransactionError::storage(&error.to_string()),
+        }
+    }
+}
+
+fn client_id(row: &Row, index: usize) -> TransactionResult<u16> {
+    u16::try_from(row.try_get::<_, i32>(index)?)
+        .map_err(|_| TransactionError::storage("Client id out of range"))
+}
+
+fn id(row: &Row, index: usize) -> TransactionResult<u32> {
+    u32::try_from(row.try_get::<_, i64>(index)?)
+        .map_err(|_| TransactionError::storage("Transaction id out of range"))
+}
+
+fn position(row: &Row, index: usize) -> TransactionResult<u64> {
+    u64::try_from(row.try_get::<_, i64>(index)?)
+        .map_err(|_| TransactionError::storage("Position out of range"))
+}
+
+fn position_param(value: u64) -> TransactionResult<i64> {
+    i64::try_from(value).map_err(|_| TransactionError::storage("Position out of range"))
+}
+
+fn event_from_row(row: &Row) -> TransactionResult<Event> {
+    let id = row
+        .try_get::<_, Option<i64>>(4)?
+        .map(u32::try_from)
+        .transpose()
+        .map_err(|_| TransactionError::storage("Transaction id out of range"))?;
+    let event = AccountEvent::from_parts(row.try_get(3)?, id, row.try_get(5)?)?;
+    let timestamp = u64::try_from(row.try_get::<_, i64>(1)?)
+        .map_err(|_| TransactionError::storage("Timestamp out of range"))?;
+
+    Ok(Event::new(
+        position(row, 0)?,
+        timestamp,
+        client_id(row, 2)?,
+        event,
+    ))
+}
+
+fn account_from_row(row: &Row) -> TransactionResult<Account> {
+    let mut account = Account::from_balances(
+        client_id(row, 0)?,
+        row.try_get(1)?,
+        row.try_get(2)?,
+        row.try_get(3)?,
+        row.try_get(4)?,
+        row.try_get(5)?,
+        row.try_get(6)?,
+    );
+    account.set_overdraft_limit(row.try_get(7)?);
+
+    Ok(account)
+}
+
+fn transaction_from_row(row: &Row) -> TransactionResult<(u32, TransactionLite)> {
+    let mut transaction =
+        TransactionLite::new(row.try_get::<_, &str>(1)?.parse()?, row.try_get(2)?);
+    transaction.disputed = row.try_get(3)?;
+    transaction.counterparty = row
+        .try_get::<_, Option<i32>>(4)?
+        .map(u16::try_from)
+        .transpose()
+        .map_err(|_| TransactionError::storage("Client id out of range"))?;
+
+    Ok((id(row, 0)?, transaction))
+}
+
+fn load_account(client: &mut Client, client_id: u16) -> TransactionResult<Account> {
+    let client_id = i32::from(client_id);
+    client.execute(
+        "INSERT INTO accounts (client, available, held, total, locked)
+        VALUES ($1, 0, 0, 0, FALSE) ON CONFLICT DO NOTHING",
+        &[&client_id],
+    )?;
+    let row = client.query_one(
+        "SELECT client, available, held, total, locked, authorized, fees, overdraft_limit
+        FROM accounts
+        WHERE client = $1 FOR UPDATE",
+        &[&client_id],
+    )?;
+
+    account_from_row(&row)
+}
+
+fn load_transaction(
+    client: &mut Client,
+    client_id: u16,
+    id: u32,
+) -> TransactionResult<Option<(u32, TransactionLite)>> {
+    client
+        .query_opt(
+            "SELECT tx, type, amount, disputed, counterparty FROM transactions
+            WHERE tx = $1 AND client = $2",
+            &[&i64::from(id), &i32::from(client_id)],
+        )?
+        .map(|row| transaction_from_row(&row))
+        .transpose()
+}
+
+fn save_account(client: &mut Client, account: &Account) -> TransactionResult<()> {
+    let client_id = i32::from(account.client_id());
+    client.execute(
+        "UPDATE accounts SET available = $2, held = $3, total = $4, locked = $5, authorized = $6,
+        fees = $7, overdraft_limit = $8 WHERE client = $1",
+        &[
+            &client_id,
+            account.available(),
+            account.held(),
+            account.total(),
+            &account.locked(),
+            account.authorized(),
+            account.fees(),
+            account.overdraft_limit(),
+        ],
+    )?;
+
+    for (id, transaction) in account.transactions() {
+        client.execute(
+            "INSERT INTO transactions (tx, client, type, amount, disputed, counterparty)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (tx) DO UPDATE SET amount = EXCLUDED.amount, disputed = EXCLUDED.disputed",
+            &[
+                &i64::from(*id),
+                &client_id,
+                &transaction.t_type.as_str(),
+                &transaction.amount,
+                &transaction.disputed,
+                &transaction.counterparty.map(i32::from),
+            ],
+        )?;
+    }
+
+    Ok(())
+}
+
+/*
+ * Every Bank::transact runs in a single database transaction, the account row of each client
+ * touched is locked (SELECT ... FOR UPDATE) until commit, so several engine processes can share
+ * one database, deadlocks between them surface as conflicts that the bank retries
+ */
+pub struct PostgresStorage {
+    client: RefCell<Client>,
+    cache: AHashMap<u16, Account>,
+}
+
+impl PostgresStorage {
+    pub fn connect(params: &str) -> TransactionResult<Self> {
+        PostgresStorage::from_client(Client::connect(params, NoTls)?)
+    }
+
+    pub fn from_client(mut client: Client) -> TransactionResult<Self> {
+        PostgresStorage::migrate(&mut client)?;
+
+        Ok(Self {
+            client: RefCell::new(client),
+            cache: AHashMap::new(),
+        })
+    }
+
+    fn migrate(client: &mut Client) -> TransactionResult<()> {
+        let mut transaction = client.transaction()?;
+        transaction.execute("SELECT pg_advisory_xact_lock($1)", &[&MIGRATION_LOCK])?;
+        transaction.batch_execute(
+            "CREATE TABLE IF NOT EXISTS schema_migrations (version INTEGER PRIMARY KEY)",
+        )?;
+
+        for (version, migration) in MIGRATIONS {
+            let applied = transaction
+                .query_opt(
+                    "SELECT version FROM schema_migrations WHERE version = $1",
+                    &[&version],
+                )?
+                .is_some();
+            if !applied {
+                transaction.batch_execute(migration)?;
+                transaction.execute(
+                    "INSERT INTO schema_migrations (version) VALUES ($1)",
+                    &[&version],
+                )?;
+            }
+        }
+
+        Ok(transaction.commit()?)
+    }
+
+    /* The sequence is shared and never rolled back, so this is the next position to be used */
+    fn current_position(&self) -> TransactionResult<u64> {
+        let row = self
+            .client
+            .borrow_mut()
+            .query_one("SELECT last_value + is_called::INTEGER FROM positions", &[])?;
+
+        position(&row, 0)
+    }
+}
+
+impl Storage for PostgresStorage {
+    fn begin(&mut self) -> TransactionResult<()> {
+        Ok(self.client.get_mut().batch_execute("BEGIN")?)
+    }
+
+    fn commit(&mut self) -> TransactionResult<()> {
+        let client = self.client.get_mut();
+        for account in self.cache.values() {
+            save_account(client, account)?;
+        }
+        client.batch_execute("COMMIT")?;
+        self.cache.clear();
+
+        Ok(())
+    }
+
+    fn rollback(&mut self) -> TransactionResult<()> {
+        self.cache.clear();
+
+        Ok(self.client.get_mut().batch_execute("ROLLBACK")?)
+    }
+
+    fn next_position(&mut self) -> TransactionResult<u64> {
+        let row = self
+            .client
+            .get_mut()
+            .query_one("SELECT nextval('positions')", &[])?;
+
+        position(&row, 0)
+    }
+
+    fn get_or_insert_client_id(&mut self, id: u32, client_id: u16) -> TransactionResult<u16> {
+        let client = self.client.get_mut();
+        let (id, client_id) = (i64::from(id), i32::from(client_id));
+        client.execute(
+            "INSERT INTO transaction_clients (tx, client) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+            &[&id, &client_id],
+        )?;
+        let row = client.query_one(
+            "SELECT client FROM transaction_clients WHERE tx = $1",
+            &[&id],
+        )?;
+
+        self::client_id(&row, 0)
+    }
+
+    fn account_mut(&mut self, client_id: u16, id: u32) -> TransactionResult<&mut Account> {
+        let client = self.client.get_mut();
+        let account = match self.cache.entry(client_id) {
+            Entry::Occupied(account) => account.into_mut(),
+            Entry::Vacant(account) => account.insert(load_account(client, client_id)?),
+        };
+
+        if account.transaction(&id).is_none() {
+            if let Some((id, transaction)) = load_transaction(client, client_id, id)? {
+                account.restore_transaction(id, transaction);
+            }
+        }
+
+        Ok(account)
+    }
+
+    /* Loads the account with all of its transactions */
+    fn account(&self, client_id: u16) -> TransactionResult<Option<Cow<'_, Account>>> {
+        let mut client = self.client.borrow_mut();
+        let client_id = i32::from(client_id);
+        let mut account = match client.query_opt(
+            "SELECT client, available, held, total, locked, authorized, fees, overdraft_limit
+        FROM accounts
+            WHERE client = $1",
+            &[&client_id],
+        )? {
+            Some(row) => account_from_row(&row)?,
+            None => return Ok(None),
+        };
+
+        for row in client.query(
+            "SELECT tx, type, amount, disputed, counterparty FROM transactions WHERE client = $1",
+            &[&client_id],
+        )? {
+            let (id, transaction) = transaction_from_row(&row)?;
+            account.restore_transaction(id, transaction);
+        }
+
+        Ok(Some(Cow::Owned(account)))
+    }
+
+    /* Loads the balances of every account, without their transactions */
+    fn accounts(&self) -> TransactionResult<Vec<Cow<'_, Account>>> {
+        self.client
+            .borrow_mut()
+            .query(
+                "SELECT client, available, held, total, locked, authorized, fees, overdraft_limit
+        FROM accounts",
+                &[],
+            )?
+            .iter()
+            .map(|row| account_from_row(row).map(Cow::Owned))
+            .collect()
+    }
+
+    fn open_dispute(&mut self, client_id: u16, id: u32, position: u64) -> TransactionResult<()> {
+        let position = position_param(position)?;
+        self.client.get_mut().execute(
+            "INSERT INTO disputes (tx, client, opened, expired) VALUES ($1, $2, $3, FALSE)
+            ON CONFLICT (tx) DO UPDATE SET opened = EXCLUDED.opened, expired = FALSE",
+            &[&i64::from(id), &i32::from(client_id), &position],
+        )?;
+
+        Ok(())
+    }
+
+    fn close_dispute(&mut self, id: u32) -> TransactionResult<()> {
+        self.client
+            .get_mut()
+            .execute("DELETE FROM disputes WHERE tx = $1", &[&i64::from(id)])?;
+
+        Ok(())
+    }
+
+    /* Disputes being expired by another process are skipped rather than waited on */
+    fn pop_expired_dispute(&mut self, cutoff: u64) -> TransactionResult<Option<(u16, u32)>> {
+        let client = self.client.get_mut();
+        let cutoff = position_param(cutoff)?;
+        let row = match client.query_opt(
+            "SELECT client, tx FROM disputes WHERE NOT expired AND opened < $1
+            ORDER BY opened LIMIT 1 FOR UPDATE SKIP LOCKED",
+            &[&cutoff],
+        )? {
+            Some(row) => row,
+            None => return Ok(None),
+        };
+
+        let (client_id, id) = (client_id(&row, 0)?, self::id(&row, 1)?);
+        client.execute(
+            "UPDATE disputes SET expired = TRUE WHERE tx = $1",
+            &[&i64::from(id)],
+        )?;
+
+        Ok(Some((client_id, id)))
+    }
+
+    fn open_disputes(&self) -> TransactionResult<Vec<OpenDispute>> {
+        let current = self.current_position()?;
+        self.client
+            .borrow_mut()
+            .query(
+                "SELECT disputes.client, disputes.tx, transactions.amount, disputes.opened
+                FROM disputes JOIN transactions ON transactions.tx = disputes.tx
+                ORDER BY disputes.opened",
+                &[],
+            )?
+            .iter()
+            .map(|row| {
+                let opened = position(row, 3)?;
+                Ok(OpenDispute {
+                    client_id: client_id(row, 0)?,
+                    id: id(row, 1)?,
+                    amount: row.try_get(2)?,
+                    opened,
+                    age: current.saturating_sub(opened),
+                })
+            })
+            .collect()
+    }
+
+    fn open_authorization(
+        &mut self,
+        client_id: u16,
+        id: u32,
+        position: u64,
+    ) -> TransactionResult<()> {
+        let position = position_param(position)?;
+        self.client.get_mut().execute(
+            "INSERT INTO authorizations (tx, client, opened, expired) VALUES ($1, $2, $3, FALSE)",
+            &[&i64::from(id), &i32::from(client_id), &position],
+        )?;
+
+        Ok(())
+    }
+
+    fn close_authorization(&mut self, id: u32) -> TransactionResult<()> {
+        self.client.get_mut().execute(
+            "DELETE FROM authorizations WHERE tx = $1",
+            &[&i64::from(id)],
+        )?;
+
+        Ok(())
+    }
+
+    fn pop_expired_authorization(&mut self, cutoff: u64) -> TransactionResult<Option<(u16, u32)>> {
+        let client = self.client.get_mut();
+        let cutoff = position_param(cutoff)?;
+        let row = match client.query_opt(
+            "SELECT client, tx FROM authorizations WHERE NOT expired AND opened < $1
+            ORDER BY opened LIMIT 1 FOR UPDATE SKIP LOCKED",
+            &[&cutoff],
+        )? {
+            Some(row) => row,
+            None => return Ok(None),
+        };
+
+        let (client_id, id) = (client_id(&row, 0)?, self::id(&row, 1)?);
+        client.execute(
+            "UPDATE authorizations SET expired = TRUE WHERE tx = $1",
+            &[&i64::from(id)],
+        )?;
+
+        Ok(Some((client_id, id)))
+    }
+
+    fn revenue(&self) -> TransactionResult<Decimal> {
+        let row = self
+            .client
+            .borrow_mut()
+            .query_one("SELECT amount FROM revenue WHERE id = 0", &[])?;
+
+        Ok(row.try_get(0)?)
+    }
+
+    /* Updated in place so that processes sharing the database only wait on each other's commit */
+    fn credit_revenue(&mut self, amount: Decimal) -> TransactionResult<()> {
+        self.client.get_mut().execute(
+            "UPDATE revenue SET amount = amount + $1 WHERE id = 0",
+            &[&amount],
+        )?;
+
+        Ok(())
+    }
+
+    fn append_events(&mut self, events: &[Event]) -> TransactionResult<()> {
+        let client = self.client.get_mut();
+        for event in events {
+            client.execute(
+                "INSERT INTO events (sequence, timestamp, client, kind, tx, amount)
+                VALUES ($1, $2, $3, $4, $5, $6)",
+                &[
+                    &position_param(event.sequence)?,
+                    &i64::try_from(event.timestamp)
+                        .map_err(|_| TransactionError::storage("Timestamp out of range"))?,
+                    &i32::from(event.client_id),
+                    &event.event.kind(),
+                    &event.event.id().map(i64::from),
+                    &event.event.amount(),
+                ],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn events(&self, client_id: u16) -> TransactionResult<Cow<'_, [Event]>> {
+        self.client
+            .borrow_mut()
+            .query(
+                "SELECT sequence, timestamp, client, kind, tx, amount FROM events
+                WHERE client = $1 ORDER BY id",
+                &[&i32::from(client_id)],
+            )?
+            .iter()
+            .map(event_from_row)
+            .collect::<TransactionResult<Vec<Event>>>()
+            .map(Cow::Owned)
+    }
+
+    fn interest_accrued(&self, client_id: u16) -> TransactionResult<Option<Date>> {
+        self.client
+            .borrow_mut()
+            .query_opt(
+                "SELECT date FROM interest_accrued WHERE client = $1",
+                &[&i32::from(client_id)],
+            )?
+            .map(|row| {
+                row.try_get::<_, &str>(0)?
+                    .parse::<Date>()
+                    .map_err(|err| TransactionError::storage(&err.to_string()))
+            })
+            .transpose()
+    }
+
+    fn set_interest_accrued(&mut self, client_id: u16, date: Date) -> TransactionResult<()> {
+        self.client.get_mut().execute(
+            "INSERT INTO interest_accrued (client, date) VALUES ($1, $2)
+            ON CONFLICT (client) DO UPDATE SET date = excluded.date",
+            &[&i32::from(client_id), &date.to_string()],
+        )?;
+
+        Ok(())
+    }
+
+    fn standing_order_paid(&self, index: u64) -> TransactionResult<Option<u64>> {
+        self.client
+            .borrow_mut()
+            .query_opt(
+                "SELECT due FROM standing_orders_paid WHERE order_index = $1",
+                &[&position_param(index)?],
+            )?
+            .map(|row| position(&row, 0))
+            .transpose()
+    }
+
+    fn set_standing_order_paid(&mut self, index: u64, due: u64) -> TransactionResult<()> {
+        self.client.get_mut().execute(
+            "INSERT INTO standing_orders_paid (order_index, due) VALUES ($1, $2)
+            ON CONFLICT (order_index) DO UPDATE SET due = excluded.due",
+            &[&position_param(index)?, &position_param(due)?],
+        )?;
+
+        Ok(())
+    }
+
+    /* A sequence like positions, so numbers are not reused after a rollback */
+    fn next_bank_sequence(&mut self) -> TransactionResult<u64> {
+        let row = self
+            .client
+            .get_mut()
+            .query_one("SELECT nextval('bank_transactions')", &[])?;
+
+        position(&row, 0)
+    }
+
+    fn consumer_offset(&self, topic: &str, partition: u32) -> TransactionResult<u64> {
+        match self.client.borrow_mut().query_opt(
+            "SELECT next_offset FROM consumer_offsets WHERE topic = $1 AND partition_id = $2",
+            &[&topic, &i64::from(partition)],
+        )? {
+            Some(row) => position(&row, 0),
+            None => Ok(0),
+        }
+    }
+
+    fn set_consumer_offset(
+        &mut self,
+        topic: &str,
+        partition: u32,
+        offset: u64,
+    ) -> TransactionResult<()> {
+        self.client.get_mut().execute(
+            "INSERT INTO consumer_offsets (topic, partition_id, next_offset) VALUES ($1, $2, $3)
+            ON CONFLICT (topic, partition_id) DO UPDATE SET next_offset = excluded.next_offset",
+            &[&topic, &i64::from(partition), &position_param(offset)?],
+        )?;
+
+        Ok(())
+    }
+}
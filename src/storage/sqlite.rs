@@ -0,0 +1,843 @@
+use crate::account::{Account, TransactionResult};
+use crate::dispute::OpenDispute;
+use crate::event::{AccountEvent, Event};
+use crate::id::{ClientId, TransactionId};
+use crate::interest::Date;
+use crate::storage::{storage_error, Storage};
+use crate::transaction::transaction_error::TransactionError;
+use crate::transaction::transaction_lite::TransactionLite;
+use ahash::AHashMap;
+use rusqlite::types::Type;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use rust_decimal::Decimal;
+use std::borrow::Cow;
+use std::collections::hash_map::Entry;
+use std::path::Path;
+use std::str::FromStr;
+
+/*
+ * Integer ids are kept in INTEGER columns, the key of a table becoming its rowid, UUIDs and string
+ * ids in BLOB columns, as SQLite would turn text that looks like a number into one
+ */
+#[cfg(not(any(feature = "client-id-uuid", feature = "client-id-string")))]
+const CLIENT_COLUMN: &str = "INTEGER";
+#[cfg(any(feature = "client-id-uuid", feature = "client-id-string"))]
+const CLIENT_COLUMN: &str = "BLOB";
+#[cfg(not(any(feature = "tx-id-uuid", feature = "tx-id-string")))]
+const TX_COLUMN: &str = "INTEGER";
+#[cfg(any(feature = "tx-id-uuid", feature = "tx-id-string"))]
+const TX_COLUMN: &str = "BLOB";
+
+/* {client} and {tx} are the column types of the ids */
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS bank (
+        id INTEGER PRIMARY KEY CHECK (id = 0),
+        position INTEGER NOT NULL,
+        revenue TEXT NOT NULL DEFAULT '0',
+        bank_sequence INTEGER NOT NULL DEFAULT 0
+    );
+    INSERT OR IGNORE INTO bank (id, position) VALUES (0, 0);
+    CREATE TABLE IF NOT EXISTS accounts (
+        client {client} PRIMARY KEY,
+        available TEXT NOT NULL,
+        held TEXT NOT NULL,
+        total TEXT NOT NULL,
+        locked INTEGER NOT NULL,
+        authorized TEXT NOT NULL DEFAULT '0',
+        fees TEXT NOT NULL DEFAULT '0',
+        overdraft_limit TEXT NOT NULL DEFAULT '0'
+    );
+    CREATE TABLE IF NOT EXISTS transaction_clients (
+        tx {tx} PRIMARY KEY,
+        client {client} NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS transactions (
+        tx {tx} PRIMARY KEY,
+        client {client} NOT NULL,
+        type TEXT NOT NULL,
+        amount TEXT NOT NULL,
+        disputed INTEGER NOT NULL,
+        counterparty {client}
+    );
+    CREATE INDEX IF NOT EXISTS transactions_client ON transactions (client);
+    CREATE TABLE IF NOT EXISTS disputes (
+        tx {tx} PRIMARY KEY,
+        client {client} NOT NULL,
+        opened INTEGER NOT NULL,
+        expired INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS disputes_expiry ON disputes (expired, opened);
+    CREATE TABLE IF NOT EXISTS authorizations (
+        tx {tx} PRIMARY KEY,
+        client {client} NOT NULL,
+        opened INTEGER NOT NULL,
+        expired INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS authorizations_expiry ON authorizations (expired, opened);
+    CREATE TABLE IF NOT EXISTS events (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        sequence INTEGER NOT NULL,
+        timestamp INTEGER NOT NULL,
+        client {client} NOT NULL,
+        kind TEXT NOT NULL,
+        tx {tx},
+        amount TEXT
+    );
+    CREATE INDEX IF NOT EXISTS events_client ON events (client, id);
+    CREATE TABLE IF NOT EXISTS consumer_offsets (
+        topic TEXT NOT NULL,
+        partition_id INTEGER NOT NULL,
+        next_offset INTEGER NOT NULL,
+        PRIMARY KEY (topic, partition_id)
+    );
+    CREATE TABLE IF NOT EXISTS interest_accrued (
+        client {client} PRIMARY KEY,
+        date TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS standing_orders_paid (
+        order_index INTEGER PRIMARY KEY,
+        due INTEGER NOT NULL
+    );
+";
+
+impl From<rusqlite::Error> for TransactionError {
+    fn from(error: rusqlite::Error) -> Self {
+        TransactionError::storage(&error.to_string())
+    }
+}
+
+fn decimal(row: &Row, index: usize) -> rusqlite::Result<Decimal> {
+    Decimal::from_str(&row.get::<_, String>(index)?)
+        .map_err(|err| rusqlite::Error::FromSqlConversionFailure(index, Type::Text, Box::new(err)))
+}
+
+fn account_from_row(row: &Row) -> rusqlite::Result<Account> {
+    let mut account = Account::from_balances(
+        row.get(0)?,
+        decimal(row, 1)?,
+        decimal(row, 2)?,
+        decimal(row, 3)?,
+        row.get(4)?,
+        decimal(row, 5)?,
+        decimal(row, 6)?,
+    );
+    account.set_overdraft_limit(decimal(row, 7)?);
+
+    Ok(account)
+}
+
+fn transaction_from_row(row: &Row) -> rusqlite::Result<(TransactionId, TransactionLite)> {
+    let t_type = row
+        .get::<_, String>(1)?
+        .parse()
+        .map_err(|err| rusqlite::Error::FromSqlConversionFailure(1, Type::Text, Box::new(err)))?;
+    let mut transaction = TransactionLite::new(t_type, decimal(row, 2)?);
+    transaction.disputed = row.get(3)?;
+    transaction.counterparty = row.get(4)?;
+
+    Ok((row.get(0)?, transaction))
+}
+
+fn event_from_row(row: &Row) -> rusqlite::Result<Event> {
+    let amount = row
+        .get::<_, Option<String>>(5)?
+        .map(|amount| Decimal::from_str(&amount))
+        .transpose()
+        .map_err(|err| rusqlite::Error::FromSqlConversionFailure(5, Type::Text, Box::new(err)))?;
+    let event = AccountEvent::from_parts(&row.get::<_, String>(3)?, row.get(4)?, amount)
+        .map_err(|err| rusqlite::Error::FromSqlConversionFailure(3, Type::Text, Box::new(err)))?;
+
+    Ok(Event::new(row.get(0)?, row.get(1)?, row.get(2)?, event))
+}
+
+fn load_account(
+    connection: &Connection,
+    client_id: ClientId,
+) -> TransactionResult<Option<Account>> {
+    Ok(connection
+        .prepare_cached(
+            "SELECT client, available, held, total, locked, authorized, fees, overdraft_limit
+            FROM accounts
+            WHERE client = ?1",
+        )?
+        .query_row(params![client_id], account_from_row)
+        .optional()?)
+}
+
+fn load_transaction(
+    connection: &Connection,
+    client_id: ClientId,
+    id: TransactionId,
+) -> TransactionResult<Option<(TransactionId, TransactionLite)>> {
+    Ok(connection
+        .prepare_cached(
+            "SELECT tx, type, amount, disputed, counterparty FROM transactions
+            WHERE tx = ?1 AND client = ?2",
+        )?
+        .query_row(params![id, client_id], transaction_from_row)
+        .optional()?)
+}
+
+fn save_account(connection: &Connection, account: &Account) -> TransactionResult<()> {
+    connection
+        .prepare_cached(
+            "INSERT OR REPLACE INTO accounts
+            (client, available, held, total, locked, authorized, fees, overdraft_limit)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        )?
+        .execute(params![
+            account.client_id(),
+            account.available().to_string(),
+            account.held().to_string(),
+            account.total().to_string(),
+            account.locked(),
+            account.authorized().to_string(),
+            account.fees().to_string(),
+            account.overdraft_limit().to_string(),
+        ])?;
+
+    let mut statement = connection.prepare_cached(
+        "INSERT OR REPLACE INTO transactions (tx, client, type, amount, disputed, counterparty)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+    )?;
+    for (id, transaction) in account.transactions() {
+        statement.execute(params![
+            id,
+            account.client_id(),
+            transaction.t_type.as_str(),
+            transaction.amount.to_string(),
+            transaction.disputed,
+            transaction.counterparty,
+        ])?;
+    }
+
+    Ok(())
+}
+
+/*
+ * Accounts touched by a transaction are loaded into a cache, with only the transactions they
+ * reference, and written back on commit so every Bank::transact is a single SQLite transaction
+ */
+pub struct SqliteStorage {
+    connection: Connection,
+    position: u64,
+    cache: AHashMap<ClientId, Account>,
+}
+
+impl SqliteStorage {
+    pub fn open<P>(path: P) -> TransactionResult<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let connection = Connection::open(path)?;
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        connection.pragma_update(None, "synchronous", "NORMAL")?;
+
+        SqliteStorage::from_connection(connection)
+    }
+
+    pub fn open_in_memory() -> TransactionResult<Self> {
+        SqliteStorage::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(connection: Connection) -> TransactionResult<Self> {
+        connection.execute_batch(
+            &SCHEMA
+                .replace("{client}", CLIENT_COLUMN)
+                .replace("{tx}", TX_COLUMN),
+        )?;
+        SqliteStorage::add_column(
+            &connection,
+            "accounts",
+            "authorized TEXT NOT NULL DEFAULT '0'",
+        )?;
+        SqliteStorage::add_column(
+            &connection,
+            "transactions",
+            &format!("counterparty {}", CLIENT_COLUMN),
+        )?;
+        SqliteStorage::add_column(&connection, "accounts", "fees TEXT NOT NULL DEFAULT '0'")?;
+        SqliteStorage::add_column(&connection, "bank", "revenue TEXT NOT NULL DEFAULT '0'")?;
+        SqliteStorage::add_column(
+            &connection,
+            "accounts",
+            "overdraft_limit TEXT NOT NULL DEFAULT '0'",
+        )?;
+        SqliteStorage::add_column(
+            &connection,
+            "bank",
+            "bank_sequence INTEGER NOT NULL DEFAULT 0",
+        )?;
+        let position = SqliteStorage::load_position(&connection)?;
+
+        Ok(Self {
+            connection,
+            position,
+            cache: AHashMap::new(),
+        })
+    }
+
+    /* Adds a column added to the schema since the database was created, if it is missing */
+    fn add_column(connection: &Connection, table: &str, column: &str) -> TransactionResult<()> {
+        let name = column.split_whitespace().next().unwrap_or_default();
+        let exists: bool = connection.query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info(?1) WHERE name = ?2",
+            params![table, name],
+            |row| row.get(0),
+        )?;
+        if !exists {
+            connection.execute_batch(&format!("ALTER TABLE {} ADD {}", table, column))?;
+        }
+
+        Ok(())
+    }
+
+    fn load_position(connection: &Connection) -> TransactionResult<u64> {
+        Ok(
+            connection.query_row("SELECT position FROM bank WHERE id = 0", [], |row| {
+                row.get(0)
+            })?,
+        )
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn begin(&mut self) -> TransactionResult<()> {
+        self.connection.execute_batch("BEGIN IMMEDIATE")?;
+
+        Ok(())
+    }
+
+    fn commit(&mut self) -> TransactionResult<()> {
+        for account in self.cache.values() {
+            save_account(&self.connection, account)?;
+        }
+        self.connection.execute(
+            "UPDATE bank SET position = ?1 WHERE id = 0",
+            params![self.position],
+        )?;
+        self.connection.execute_batch("COMMIT")?;
+        self.cache.clear();
+
+        Ok(())
+    }
+
+    fn rollback(&mut self) -> TransactionResult<()> {
+        self.cache.clear();
+        self.connection.execute_batch("ROLLBACK")?;
+        self.position = SqliteStorage::load_position(&self.connection)?;
+
+        Ok(())
+    }
+
+    fn next_position(&mut self) -> TransactionResult<u64> {
+        let position = self.position;
+        self.position += 1;
+
+        Ok(position)
+    }
+
+    fn get_or_insert_client_id(
+        &mut self,
+        id: TransactionId,
+        client_id: ClientId,
+    ) -> TransactionResult<ClientId> {
+        let existing = self
+            .connection
+            .prepare_cached("SELECT client FROM transaction_clients WHERE tx = ?1")?
+            .query_row(params![id], |row| row.get(0))
+            .optional()?;
+
+        match existing {
+            Some(existing) => Ok(existing),
+            None => {
+                self.connection
+                    .prepare_cached("INSERT INTO transaction_clients (tx, client) VALUES (?1, ?2)")?
+                    .execute(params![id, client_id])?;
+                Ok(client_id)
+            }
+        }
+    }
+
+    fn account_mut(
+        &mut self,
+        client_id: ClientId,
+        id: TransactionId,
+    ) -> TransactionResult<&mut Account> {
+        let account = match self.cache.entry(client_id) {
+            Entry::Occupied(account) => account.into_mut(),
+            Entry::Vacant(account) => account.insert(
+                load_account(&self.connection, client_id)?
+                    .unwrap_or_else(|| Account::new(client_id)),
+            ),
+        };
+
+        if account.transaction(&id).is_none() {
+            if let Some((id, transaction)) = load_transaction(&self.connection, client_id, id)? {
+                account.restore_transaction(id, transaction);
+            }
+        }
+
+        Ok(account)
+    }
+
+    /* Loads the account with all of its transactions */
+    fn account(&self, client_id: ClientId) -> TransactionResult<Option<Cow<'_, Account>>> {
+        let mut account = match load_account(&self.connection, client_id)? {
+            Some(account) => account,
+            None => return Ok(None),
+        };
+
+        let mut statement = self.connection.prepare_cached(
+            "SELECT tx, type, amount, disputed, counterparty FROM transactions WHERE client = ?1",
+        )?;
+        for transaction in statement.query_map(params![client_id], transaction_from_row)? {
+            let (id, transaction) = transaction?;
+            account.restore_transaction(id, transaction);
+        }
+
+        Ok(Some(Cow::Owned(account)))
+    }
+
+    /* Loads the balances of every account, without their transactions */
+    fn accounts(&self) -> TransactionResult<Vec<Cow<'_, Account>>> {
+        let mut statement = self.connection.prepare_cached(
+            "SELECT client, available, held, total, locked, authorized, fees, overdraft_limit
+            FROM accounts",
+        )?;
+        let accounts = statement
+            .query_map([], account_from_row)?
+            .map(|account| account.map(Cow::Owned))
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(accounts)
+    }
+
+    fn open_dispute(
+        &mut self,
+        client_id: ClientId,
+        id: TransactionId,
+        position: u64,
+    ) -> TransactionResult<()> {
+        self.connection
+            .prepare_cached(
+                "INSERT OR REPLACE INTO disputes (tx, client, opened, expired)
+                VALUES (?1, ?2, ?3, FALSE)",
+            )?
+            .execute(params![id, client_id, position])?;
+
+        Ok(())
+    }
+
+    fn close_dispute(&mut self, id: TransactionId) -> TransactionResult<()> {
+        self.connection
+            .prepare_cached("DELETE FROM disputes WHERE tx = ?1")?
+            .execute(params![id])?;
+
+        Ok(())
+    }
+
+    fn pop_expired_dispute(
+        &mut self,
+        cutoff: u64,
+    ) -> TransactionResult<Option<(ClientId, TransactionId)>> {
+        let expired: Option<(ClientId, TransactionId)> = self
+            .connection
+            .prepare_cached(
+                "SELECT client, tx FROM disputes WHERE NOT expired AND opened < ?1
+                ORDER BY opened LIMIT 1",
+            )?
+            .query_row(params![cutoff], |row| Ok((row.get(0)?, row.get(1)?)))
+            .optional()?;
+
+        if let Some((_, id)) = expired {
+            self.connection
+                .prepare_cached("UPDATE disputes SET expired = TRUE WHERE tx = ?1")?
+                .execute(params![id])?;
+        }
+
+        Ok(expired)
+    }
+
+    fn open_disputes(&self) -> TransactionResult<Vec<OpenDispute>> {
+        let mut statement = self.connection.prepare_cached(
+            "SELECT disputes.client, disputes.tx, transactions.amount, disputes.opened
+            FROM disputes JOIN transactions ON transactions.tx = disputes.tx
+            ORDER BY disputes.opened",
+        )?;
+        let disputes = statement
+            .query_map([], |row| {
+                let opened: u64 = row.get(3)?;
+                Ok(OpenDispute {
+                    client_id: row.get(0)?,
+                    id: row.get(1)?,
+                    amount: decimal(row, 2)?,
+                    opened,
+                    age: self.position - opened,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(disputes)
+    }
+
+    fn open_authorization(
+        &mut self,
+        client_id: ClientId,
+        id: TransactionId,
+        position: u64,
+    ) -> TransactionResult<()> {
+        self.connection
+            .prepare_cached(
+                "INSERT OR REPLACE INTO authorizations (tx, client, opened, expired)
+                VALUES (?1, ?2, ?3, FALSE)",
+            )?
+            .execute(params![id, client_id, position])?;
+
+        Ok(())
+    }
+
+    fn close_authorization(&mut self, id: TransactionId) -> TransactionResult<()> {
+        self.connection
+            .prepare_cached("DELETE FROM authorizations WHERE tx = ?1")?
+            .execute(params![id])?;
+
+        Ok(())
+    }
+
+    fn pop_expired_authorization(
+        &mut self,
+        cutoff: u64,
+    ) -> TransactionResult<Option<(ClientId, TransactionId)>> {
+        let expired: Option<(ClientId, TransactionId)> = self
+            .connection
+            .prepare_cached(
+                "SELECT client, tx FROM authorizations WHERE NOT expired AND opened < ?1
+                ORDER BY opened LIMIT 1",
+            )?
+            .query_row(params![cutoff], |row| Ok((row.get(0)?, row.get(1)?)))
+            .optional()?;
+
+        if let Some((_, id)) = expired {
+            self.connection
+                .prepare_cached("UPDATE authorizations SET expired = TRUE WHERE tx = ?1")?
+                .execute(params![id])?;
+        }
+
+        Ok(expired)
+    }
+
+    fn revenue(&self) -> TransactionResult<Decimal> {
+        Ok(self
+            .connection
+            .query_row("SELECT revenue FROM bank WHERE id = 0", [], |row| {
+                decimal(row, 0)
+            })?)
+    }
+
+    fn credit_revenue(&mut self, amount: Decimal) -> TransactionResult<()> {
+        let revenue = self
+            .revenue()?
+            .checked_add(amount)
+            .ok_or_else(TransactionError::overflow)?;
+        self.connection.execute(
+            "UPDATE bank SET revenue = ?1 WHERE id = 0",
+            params![revenue.to_string()],
+        )?;
+
+        Ok(())
+    }
+
+    fn append_events(&mut self, events: &[Event]) -> TransactionResult<()> {
+        let mut statement = self.connection.prepare_cached(
+            "INSERT INTO events (sequence, timestamp, client, kind, tx, amount)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?;
+        for event in events {
+            statement.execute(params![
+                event.sequence,
+                event.timestamp,
+                event.client_id,
+                event.event.kind(),
+                event.event.id(),
+                event.event.amount().map(|amount| amount.to_string()),
+            ])?;
+        }
+
+        Ok(())
+    }
+
+    fn events(&self, client_id: ClientId) -> TransactionResult<Cow<'_, [Event]>> {
+        let mut statement = self.connection.prepare_cached(
+            "SELECT sequence, timestamp, client, kind, tx, amount FROM events
+            WHERE client = ?1 ORDER BY id",
+        )?;
+        let events = statement
+            .query_map(params![client_id], event_from_row)?
+            .collect::<rusqlite::Result<Vec<Event>>>()?;
+
+        Ok(Cow::Owned(events))
+    }
+
+    fn interest_accrued(&self, client_id: ClientId) -> TransactionResult<Option<Date>> {
+        let date: Option<String> = self
+            .connection
+            .prepare_cached("SELECT date FROM interest_accrued WHERE client = ?1")?
+            .query_row(params![client_id], |row| row.get(0))
+            .optional()?;
+
+        date.map(|date| date.parse::<Date>())
+            .transpose()
+            .map_err(storage_error)
+    }
+
+    fn set_interest_accrued(&mut self, client_id: ClientId, date: Date) -> TransactionResult<()> {
+        self.connection
+            .prepare_cached(
+                "INSERT INTO interest_accrued (client, date) VALUES (?1, ?2)
+                ON CONFLICT (client) DO UPDATE SET date = excluded.date",
+            )?
+            .execute(params![client_id, date.to_string()])?;
+
+        Ok(())
+    }
+
+    fn standing_order_paid(&self, index: u64) -> TransactionResult<Option<u64>> {
+        Ok(self
+            .connection
+            .prepare_cached("SELECT due FROM standing_orders_paid WHERE order_index = ?1")?
+            .query_row(params![index], |row| row.get(0))
+            .optional()?)
+    }
+
+    fn set_standing_order_paid(&mut self, index: u64, due: u64) -> TransactionResult<()> {
+        self.connection
+            .prepare_cached(
+                "INSERT INTO standing_orders_paid (order_index, due) VALUES (?1, ?2)
+                ON CONFLICT (order_index) DO UPDATE SET due = excluded.due",
+            )?
+            .execute(params![index, due])?;
+
+        Ok(())
+    }
+
+    fn next_bank_sequence(&mut self) -> TransactionResult<u64> {
+        let sequence = self.connection.query_row(
+            "SELECT bank_sequence FROM bank WHERE id = 0",
+            [],
+            |row| row.get(0),
+        )?;
+        self.connection.execute(
+            "UPDATE bank SET bank_sequence = bank_sequence + 1 WHERE id = 0",
+            [],
+        )?;
+
+        Ok(sequence)
+    }
+
+    fn consumer_offset(&self, topic: &str, partition: u32) -> TransactionResult<u64> {
+        let offset = self
+            .connection
+            .prepare_cached(
+                "SELECT next_offset FROM consumer_offsets WHERE topic = ?1 AND partition_id = ?2",
+            )?
+            .query_row(params![topic, partition], |row| row.get(0))
+            .optional()?;
+
+        Ok(offset.unwrap_or_default())
+    }
+
+    fn set_consumer_offset(
+        &mut self,
+        topic: &str,
+        partition: u32,
+        offset: u64,
+    ) -> TransactionResult<()> {
+        self.connection
+            .prepare_cached(
+                "INSERT INTO consumer_offsets (topic, partition_id, next_offset) VALUES (?1, ?2, ?3)
+                ON CONFLICT (topic, partition_id) DO UPDATE SET next_offset = excluded.next_offset",
+            )?
+            .execute(params![topic, partition, offset])?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::{Bank, DEFAULT_BANK_TX};
+    use crate::event::AsOf;
+    use crate::id::{client, tx};
+    use crate::transaction::transaction_error::TransactionErrorKind;
+    use crate::transaction::Transaction;
+    use std::fs::remove_file;
+
+    fn decimal_str(decimal: &str) -> Decimal {
+        Decimal::from_str(decimal).unwrap()
+    }
+
+    fn delete_database(file_name: &str) {
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = remove_file(format!("{}{}", file_name, suffix));
+        }
+    }
+
+    #[test]
+    fn test_transact() {
+        let mut bank = Bank::with_storage(SqliteStorage::open_in_memory().unwrap());
+        bank.transact(Transaction::new_deposit(client(1), tx(1), decimal_str("2")))
+            .unwrap();
+        bank.transact(Transaction::new_deposit(client(1), tx(3), decimal_str("1")))
+            .unwrap();
+        bank.transact(Transaction::new_withdrawal(
+            client(1),
+            tx(2),
+            decimal_str("0.5"),
+        ))
+        .unwrap();
+        bank.transact(Transaction::new_dispute(client(1), tx(1)))
+            .unwrap();
+
+        let err = bank
+            .transact(Transaction::new_deposit(client(1), tx(1), decimal_str("2")))
+            .unwrap_err();
+        assert_eq!(&TransactionErrorKind::AlreadyExists, err.kind());
+        let err = bank
+            .transact(Transaction::new_deposit(client(2), tx(2), decimal_str("2")))
+            .unwrap_err();
+        assert_eq!(&TransactionErrorKind::IncorrectClient, err.kind());
+
+        let account = bank.account(client(1)).unwrap().unwrap();
+        assert_eq!(&decimal_str("0.5"), account.available());
+        assert_eq!(&decimal_str("2"), account.held());
+        assert_eq!(&decimal_str("2.5"), account.total());
+        assert!(account.transaction(&tx(1)).unwrap().disputed);
+        assert!(bank.account(client(2)).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_resume() {
+        let file_name = "sqlite_resume_test.db";
+        delete_database(file_name);
+
+        let mut bank = Bank::with_storage(SqliteStorage::open(file_name).unwrap());
+        bank.transact(Transaction::new_deposit(client(1), tx(1), decimal_str("2")))
+            .unwrap();
+        bank.transact(Transaction::new_deposit(client(2), tx(2), decimal_str("3")))
+            .unwrap();
+        bank.transact(Transaction::new_dispute(client(1), tx(1)))
+            .unwrap();
+        drop(bank);
+
+        let mut bank = Bank::with_storage(SqliteStorage::open(file_name).unwrap());
+        let disputes = bank.open_disputes().unwrap();
+        assert_eq!(1, disputes.len());
+        assert_eq!(2, disputes[0].opened);
+        assert_eq!(1, disputes[0].age);
+
+        bank.transact(Transaction::new_chargeback(client(1), tx(1)))
+            .unwrap();
+        let err = bank
+            .transact(Transaction::new_deposit(client(1), tx(3), decimal_str("1")))
+            .unwrap_err();
+        assert_eq!(&TransactionErrorKind::Locked, err.kind());
+        drop(bank);
+
+        let bank = Bank::with_storage(SqliteStorage::open(file_name).unwrap());
+        let mut accounts = bank.accounts().unwrap();
+        accounts.sort_by_key(|account| account.client_id());
+        assert_eq!(2, accounts.len());
+        assert_eq!(&Decimal::ZERO, accounts[0].total());
+        assert!(accounts[0].locked());
+        assert_eq!(&decimal_str("3"), accounts[1].available());
+        assert!(bank.open_disputes().unwrap().is_empty());
+
+        let account = bank
+            .account_at(client(1), AsOf::Sequence(2))
+            .unwrap()
+            .unwrap();
+        assert_eq!(&decimal_str("2"), account.held());
+        assert!(!account.locked());
+        assert_eq!(4, bank.storage().events(client(1)).unwrap().len());
+
+        delete_database(file_name);
+    }
+
+    #[test]
+    fn test_resume_interest() {
+        use crate::interest::{DayCount, InterestPolicy};
+
+        let file_name = "sqlite_interest_test.db";
+        delete_database(file_name);
+        let policy =
+            InterestPolicy::new(DayCount::Actual360).with_rate(Decimal::ZERO, decimal_str("3.6"));
+        let date: Date = "2023-06-01".parse().unwrap();
+
+        let mut bank = Bank::with_storage(SqliteStorage::open(file_name).unwrap());
+        bank.transact(Transaction::new_deposit(
+            client(1),
+            tx(1),
+            decimal_str("100"),
+        ))
+        .unwrap();
+        bank.accrue_interest(&policy, date).unwrap();
+        drop(bank);
+
+        let mut bank = Bank::with_storage(SqliteStorage::open(file_name).unwrap());
+        assert_eq!(
+            Some(date),
+            bank.storage().interest_accrued(client(1)).unwrap()
+        );
+        bank.accrue_interest(&policy, date).unwrap();
+        bank.accrue_interest(&policy, date.next()).unwrap();
+        let account = bank.account(client(1)).unwrap().unwrap();
+        assert_eq!(&decimal_str("100.02"), account.available());
+        assert!(account.transaction(&tx(DEFAULT_BANK_TX + 1)).is_some());
+
+        delete_database(file_name);
+    }
+
+    #[test]
+    fn test_resume_standing_orders() {
+        use crate::schedule::{StandingOrder, StandingOrders};
+
+        const DAY: u64 = 86_400_000;
+        let file_name = "sqlite_standing_orders_test.db";
+        delete_database(file_name);
+        let orders = || {
+            StandingOrders::new().with_order(StandingOrder {
+                client_id: client(1),
+                amount: decimal_str("1"),
+                schedule: "every 1d".parse().unwrap(),
+                start: 0,
+                end: None,
+            })
+        };
+
+        let mut bank = Bank::with_storage(SqliteStorage::open(file_name).unwrap())
+            .with_bank_tx(1_000)
+            .unwrap()
+            .with_standing_orders(orders());
+        bank.transact(Transaction::new_deposit(
+            client(1),
+            tx(1),
+            decimal_str("10"),
+        ))
+        .unwrap();
+        bank.advance_to(DAY).unwrap();
+        drop(bank);
+
+        let mut bank = Bank::with_storage(SqliteStorage::open(file_name).unwrap())
+            .with_bank_tx(1_000)
+            .unwrap()
+            .with_standing_orders(orders());
+        bank.advance_to(2 * DAY).unwrap();
+        let account = bank.account(client(1)).unwrap().unwrap();
+        assert_eq!(&decimal_str("7"), account.available());
+        assert!(account.transaction(&tx(1_002)).is_some());
+
+        delete_database(file_name);
+    }
+}
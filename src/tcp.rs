@@ -0,0 +1,253 @@
+use crate::bank::Bank;
+use crate::output::output_bank;
+use crate::storage::Storage;
+use crate::transaction::Transaction;
+use csv::StringRecord;
+use std::error::Error;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:7878";
+
+const DUMP: &str = "DUMP";
+const MALFORMED: &str = "Malformed";
+
+fn record(line: &str) -> StringRecord {
+    line.split(',').map(str::trim).collect()
+}
+
+fn lock<S>(bank: &Mutex<Bank<S>>) -> Result<MutexGuard<'_, Bank<S>>, Box<dyn Error>> {
+    bank.lock().map_err(|_| From::from("Bank lock poisoned"))
+}
+
+/*
+ * Lines are applied in the order they arrive, replying OK or the kind of error the transaction
+ * was rejected with, a header line (starting with type) is acknowledged and used to map the
+ * columns of the lines that follow it, otherwise columns are in the order of the input file.
+ * DUMP replies with the accounts CSV followed by an empty line
+ */
+pub fn handle<S, R, W>(
+    bank: &Mutex<Bank<S>>,
+    mut reader: R,
+    writer: W,
+) -> Result<(), Box<dyn Error>>
+where
+    S: Storage,
+    R: BufRead,
+    W: Write,
+{
+    let mut writer = BufWriter::new(writer);
+    let mut headers: Option<StringRecord> = None;
+    let mut line = String::new();
+
+    while reader.read_line(&mut line)? > 0 {
+        let trimmed = line.trim();
+        if trimmed == DUMP {
+            /* Rendered under the lock and written after it, so a slow client holds up no other */
+            let mut accounts = Vec::new();
+            output_bank(&*lock(bank)?, &mut accounts)?;
+            writer.write_all(&accounts)?;
+            writeln!(writer)?;
+        } else if trimmed.starts_with("type") {
+            headers = Some(record(trimmed));
+            writeln!(writer, "OK")?;
+        } else {
+            match record(trimmed).deserialize::<Transaction>(headers.as_ref()) {
+                Ok(transaction) => match lock(bank)?.transact(transaction) {
+                    Ok(()) => writeln!(writer, "OK")?,
+                    Err(err) => writeln!(writer, "{:?}", err.kind())?,
+                },
+                Err(_) => writeln!(writer, "{}", MALFORMED)?,
+            }
+        }
+        line.clear();
+
+        /* Replies are batched while the client has more lines in flight */
+        if reader.fill_buf()?.is_empty() {
+            writer.flush()?;
+        }
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+/* Serves every connection on its own thread, all sharing the bank */
+pub fn serve<S>(bank: Bank<S>, listener: TcpListener) -> io::Result<()>
+where
+    S: Storage + Send + 'static,
+{
+    let bank = Arc::new(Mutex::new(bank));
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let bank = bank.clone();
+        thread::spawn(move || {
+            /* The connection is dropped on any IO error */
+            if let Ok(reader) = stream.try_clone() {
+                let _ = handle(&bank, BufReader::new(reader), stream);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+/*
+ * As serve, with connections handled by a fixed number of threads, so at most that many clients
+ * are served at once and the rest wait to be accepted
+ */
+pub fn serve_with_threads<S>(bank: Bank<S>, listener: TcpListener, threads: usize) -> io::Result<()>
+where
+    S: Storage + Send + 'static,
+{
+    let bank = Arc::new(Mutex::new(bank));
+    let mut workers = Vec::with_capacity(threads);
+    for _ in 0..threads {
+        let listener = listener.try_clone()?;
+        let bank = bank.clone();
+        workers.push(thread::spawn(move || -> io::Result<()> {
+            for stream in listener.incoming() {
+                let stream = stream?;
+                if let Ok(reader) = stream.try_clone() {
+                    let _ = handle(&bank, BufReader::new(reader), stream);
+                }
+            }
+            Ok(())
+        }));
+    }
+
+    /* io::Error::other needs Rust 1.74 */
+    #[allow(clippy::io_other_error)]
+    for worker in workers {
+        worker
+            .join()
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "Server thread panicked"))??;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::id::{client, tx};
+    use std::io::Read;
+    use std::net::{Shutdown, TcpStream};
+
+    fn replies(bank: &Mutex<Bank>, input: &str) -> String {
+        let mut output = Vec::new();
+        handle(bank, input.as_bytes(), &mut output).unwrap();
+
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_handle() {
+        let bank = Mutex::new(Bank::new());
+        let output = replies(
+            &bank,
+            &format!(
+                "deposit, {0}, {2}, 2.0\n\
+                withdrawal, {0}, {3}, 5\n\
+                dispute, {0}, {2},\n\
+                deposit, {1}, {2}, 1.0\n\
+                withdraw, {0}, {4}, 1\n\
+                DUMP\n",
+                client(1),
+                client(2),
+                tx(1),
+                tx(2),
+                tx(3)
+            ),
+        );
+
+        assert_eq!(
+            format!(
+                "OK\nNegativeBalance\nOK\nIncorrectClient\nMalformed\n\
+                client,available,held,total,locked,authorized,fees,overdraft,debt\n\
+                {},0,2,2,false,0,0,0,0\n\n",
+                client(1)
+            ),
+            output
+        );
+    }
+
+    #[test]
+    fn test_header_maps_columns() {
+        let bank = Mutex::new(Bank::new());
+        let output = replies(
+            &bank,
+            &format!(
+                "type, client, tx, amount, example\n\
+                deposit, {0}, {1}, 1.5, text\n\
+                dispute, {0}, {1},,\n",
+                client(1),
+                tx(1)
+            ),
+        );
+
+        assert_eq!("OK\nOK\nOK\n", output);
+        assert_eq!(
+            "1.5",
+            lock(&bank)
+                .unwrap()
+                .account(client(1))
+                .unwrap()
+                .unwrap()
+                .held()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn test_serve() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || serve(Bank::new(), listener));
+
+        let deposit = format!("deposit, {}, {}, 2.0\n", client(1), tx(1));
+        let mut first = TcpStream::connect(address).unwrap();
+        first.write_all(deposit.as_bytes()).unwrap();
+        first.shutdown(Shutdown::Write).unwrap();
+        let mut reply = String::new();
+        first.read_to_string(&mut reply).unwrap();
+        assert_eq!("OK\n", reply);
+
+        let mut second = TcpStream::connect(address).unwrap();
+        second
+            .write_all(format!("{}DUMP\n", deposit).as_bytes())
+            .unwrap();
+        second.shutdown(Shutdown::Write).unwrap();
+        let mut reply = String::new();
+        second.read_to_string(&mut reply).unwrap();
+        assert_eq!(
+            format!(
+                "AlreadyExists\nclient,available,held,total,locked,authorized,fees,overdraft,debt\n\
+                {},2,0,2,false,0,0,0,0\n\n",
+                client(1)
+            ),
+            reply
+        );
+    }
+
+    #[test]
+    fn test_serve_with_threads() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || serve_with_threads(Bank::new(), listener, 1));
+
+        /* With one thread the second connection is only served once the first is closed */
+        let deposit = format!("deposit, {}, {}, 2.0\n", client(1), tx(1));
+        for (input, expected) in [(&deposit, "OK\n"), (&deposit, "AlreadyExists\n")] {
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream.write_all(input.as_bytes()).unwrap();
+            stream.shutdown(Shutdown::Write).unwrap();
+            let mut reply = String::new();
+            stream.read_to_string(&mut reply).unwrap();
+            assert_eq!(expected, reply);
+        }
+    }
+}
@@ -2,11 +2,15 @@ pub mod transaction_error;
 pub mod transaction_lite;
 pub mod transaction_type;
 
+use crate::id::{ClientId, TransactionId};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use transaction_error::TransactionError;
 use transaction_type::TransactionType;
-use transaction_type::TransactionType::{Chargeback, Deposit, Dispute, Resolve, Withdrawal};
+use transaction_type::TransactionType::{
+    Authorize, Capture, Chargeback, Deposit, Dispute, Interest, Refund, Resolve, Transfer, Void,
+    Withdrawal,
+};
 
 /*
  * TODO: Move this to trait (transact) with custom deserializer using type and injecting the account
@@ -21,42 +25,95 @@ pub struct Transaction {
     #[serde(rename = "type")]
     pub t_type: TransactionType,
     #[serde(rename = "client")]
-    pub client_id: u16,
+    pub client_id: ClientId,
     #[serde(rename = "tx")]
-    pub id: u32,
+    pub id: TransactionId,
     pub amount: Option<Decimal>,
+    /* Destination client of a transfer, the column is left out of input and output without them */
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<ClientId>,
+    /*
+     * When the transaction was made in milliseconds since the unix epoch, standing orders due by
+     * then are paid before it is applied, left out like to
+     */
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<u64>,
 }
 
 impl Transaction {
-    fn new(t_type: TransactionType, client_id: u16, id: u32, amount: Option<Decimal>) -> Self {
+    fn new(
+        t_type: TransactionType,
+        client_id: ClientId,
+        id: TransactionId,
+        amount: Option<Decimal>,
+    ) -> Self {
         Self {
             t_type,
             client_id,
             id,
             amount,
+            to: None,
+            timestamp: None,
         }
     }
 
-    pub fn new_deposit(client_id: u16, id: u32, amount: Decimal) -> Self {
+    pub fn new_deposit(client_id: ClientId, id: TransactionId, amount: Decimal) -> Self {
         Transaction::new(Deposit, client_id, id, Some(amount))
     }
 
-    pub fn new_withdrawal(client_id: u16, id: u32, amount: Decimal) -> Self {
+    pub fn new_withdrawal(client_id: ClientId, id: TransactionId, amount: Decimal) -> Self {
         Transaction::new(Withdrawal, client_id, id, Some(amount))
     }
 
-    pub fn new_dispute(client_id: u16, id: u32) -> Self {
+    pub fn new_dispute(client_id: ClientId, id: TransactionId) -> Self {
         Transaction::new(Dispute, client_id, id, None)
     }
 
-    pub fn new_resolve(client_id: u16, id: u32) -> Self {
+    pub fn new_resolve(client_id: ClientId, id: TransactionId) -> Self {
         Transaction::new(Resolve, client_id, id, None)
     }
 
-    pub fn new_chargeback(client_id: u16, id: u32) -> Self {
+    pub fn new_chargeback(client_id: ClientId, id: TransactionId) -> Self {
         Transaction::new(Chargeback, client_id, id, None)
     }
 
+    /* tx is the id of the authorization, which a capture or void refers to */
+    pub fn new_authorize(client_id: ClientId, id: TransactionId, amount: Decimal) -> Self {
+        Transaction::new(Authorize, client_id, id, Some(amount))
+    }
+
+    /* Without an amount, whatever remains of the authorization is captured */
+    pub fn new_capture(client_id: ClientId, id: TransactionId, amount: Option<Decimal>) -> Self {
+        Transaction::new(Capture, client_id, id, amount)
+    }
+
+    pub fn new_void(client_id: ClientId, id: TransactionId) -> Self {
+        Transaction::new(Void, client_id, id, None)
+    }
+
+    /* Moves amount from client_id to the to client, it is disputed by client_id */
+    pub fn new_transfer(
+        client_id: ClientId,
+        id: TransactionId,
+        to: ClientId,
+        amount: Decimal,
+    ) -> Self {
+        Self {
+            to: Some(to),
+            ..Transaction::new(Transfer, client_id, id, Some(amount))
+        }
+    }
+
+    /* tx is the id of the deposit refunded, without an amount all that remains of it is */
+    pub fn new_refund(client_id: ClientId, id: TransactionId, amount: Option<Decimal>) -> Self {
+        Transaction::new(Refund, client_id, id, amount)
+    }
+
+    /* Interest accrued by the bank, see Bank::accrue_interest */
+    pub fn new_interest(client_id: ClientId, id: TransactionId, amount: Decimal) -> Self {
+        Transaction::new(Interest, client_id, id, Some(amount))
+    }
+
     pub fn amount(&self) -> Result<Decimal, TransactionError> {
         self.amount.ok_or_else(TransactionError::must_have_amount)
     }
@@ -3,7 +3,7 @@ use std::fmt::{Display, Formatter, Result};
 /*
 * TODO: Split error into separate errors per type or into account specific errors
  */
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum TransactionErrorKind {
     IncorrectClient,
     Locked,
@@ -11,13 +11,33 @@ pub enum TransactionErrorKind {
     DoesNotExist,
     NegativeBalance,
     AlreadyDisputed,
-    FraudulentDispute,
     NotDisputed,
     NoWithdrawalDisputes,
     MustHaveAmount,
+    NonPositiveAmount,
+    NotAuthorized,
+    ExceedsAuthorization,
+    InvalidTransfer,
+    AlreadyReversed,
+    NotRefundable,
+    ExceedsRefundable,
+    NotDisputable,
+    ReservedId,
+    Overflow,
+    Storage,
+    Conflict,
     Misc,
 }
 
+impl TransactionErrorKind {
+    pub fn is_storage(&self) -> bool {
+        matches!(
+            self,
+            TransactionErrorKind::Storage | TransactionErrorKind::Conflict
+        )
+    }
+}
+
 #[derive(Debug)]
 pub struct TransactionError {
     kind: TransactionErrorKind,
@@ -88,7 +108,7 @@ impl TransactionError {
     pub fn negative_balance() -> Self {
         TransactionError::new(
             TransactionErrorKind::NegativeBalance,
-            "Amount is greater than available, withdrawal would lead to negative balance",
+            "Amount is greater than available plus the overdraft limit",
         )
     }
 
@@ -99,13 +119,6 @@ impl TransactionError {
         )
     }
 
-    pub fn fraudulent_dispute() -> Self {
-        TransactionError::new(
-            TransactionErrorKind::FraudulentDispute,
-            "Cannot claim a dispute greater than the total balance of the account",
-        )
-    }
-
     pub fn not_disputed() -> Self {
         TransactionError::new(
             TransactionErrorKind::NotDisputed,
@@ -126,4 +139,82 @@ impl TransactionError {
             "Deposits and Withdrawals must have amounts",
         )
     }
+
+    pub fn non_positive_amount() -> Self {
+        TransactionError::new(
+            TransactionErrorKind::NonPositiveAmount,
+            "Amounts must be greater than zero",
+        )
+    }
+
+    pub fn not_authorized() -> Self {
+        TransactionError::new(
+            TransactionErrorKind::NotAuthorized,
+            "Transaction is not an authorization with funds still held",
+        )
+    }
+
+    pub fn exceeds_authorization() -> Self {
+        TransactionError::new(
+            TransactionErrorKind::ExceedsAuthorization,
+            "Cannot capture more than remains held by the authorization",
+        )
+    }
+
+    pub fn invalid_transfer() -> Self {
+        TransactionError::new(
+            TransactionErrorKind::InvalidTransfer,
+            "Transfers must have a destination client other than the source",
+        )
+    }
+
+    pub fn already_reversed() -> Self {
+        TransactionError::new(
+            TransactionErrorKind::AlreadyReversed,
+            "Cannot dispute a transfer that has been charged back or a deposit that has been refunded",
+        )
+    }
+
+    pub fn not_refundable() -> Self {
+        TransactionError::new(
+            TransactionErrorKind::NotRefundable,
+            "Only deposits with an unrefunded remainder, and not in dispute, can be refunded",
+        )
+    }
+
+    pub fn exceeds_refundable() -> Self {
+        TransactionError::new(
+            TransactionErrorKind::ExceedsRefundable,
+            "Cannot refund more than remains unrefunded of the deposit",
+        )
+    }
+
+    pub fn not_disputable() -> Self {
+        TransactionError::new(
+            TransactionErrorKind::NotDisputable,
+            "Interest is posted by the bank and is not allowed to be disputed",
+        )
+    }
+
+    pub fn reserved_id() -> Self {
+        TransactionError::new(
+            TransactionErrorKind::ReservedId,
+            "Transaction ids from the first id of the bank's own transactions are reserved for them",
+        )
+    }
+
+    pub fn storage(message: &str) -> Self {
+        TransactionError::new(TransactionErrorKind::Storage, message)
+    }
+
+    pub fn conflict(message: &str) -> Self {
+        TransactionError::new(TransactionErrorKind::Conflict, message)
+    }
+
+    pub fn overflow() -> Self {
+        TransactionError::new(
+            TransactionErrorKind::Overflow,
+            "Amount would overflow the account balance",
+        )
+    }
 }
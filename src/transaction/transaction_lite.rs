@@ -1,12 +1,15 @@
+use crate::id::ClientId;
 use crate::transaction::transaction_error::TransactionError;
 use crate::transaction::transaction_type::TransactionType;
 use rust_decimal::Decimal;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct TransactionLite {
     pub disputed: bool,
     pub t_type: TransactionType,
     pub amount: Decimal,
+    /* The destination of a transfer, whose account holds its funds while it is disputed */
+    pub counterparty: Option<ClientId>,
 }
 
 impl TransactionLite {
@@ -15,6 +18,7 @@ impl TransactionLite {
             disputed: false,
             t_type,
             amount,
+            counterparty: None,
         }
     }
 
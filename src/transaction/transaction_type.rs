@@ -1,6 +1,8 @@
+use crate::transaction::transaction_error::TransactionError;
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 
-#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash, Debug)]
 pub enum TransactionType {
     #[serde(rename = "deposit")]
     Deposit,
@@ -12,4 +14,56 @@ pub enum TransactionType {
     Resolve,
     #[serde(rename = "chargeback")]
     Chargeback,
+    #[serde(rename = "authorize")]
+    Authorize,
+    #[serde(rename = "capture")]
+    Capture,
+    #[serde(rename = "void")]
+    Void,
+    #[serde(rename = "transfer")]
+    Transfer,
+    #[serde(rename = "refund")]
+    Refund,
+    /* Posted by the bank when accruing interest, never read from input */
+    #[serde(rename = "interest", skip_deserializing)]
+    Interest,
+}
+
+impl TransactionType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransactionType::Deposit => "deposit",
+            TransactionType::Withdrawal => "withdrawal",
+            TransactionType::Dispute => "dispute",
+            TransactionType::Resolve => "resolve",
+            TransactionType::Chargeback => "chargeback",
+            TransactionType::Authorize => "authorize",
+            TransactionType::Capture => "capture",
+            TransactionType::Void => "void",
+            TransactionType::Transfer => "transfer",
+            TransactionType::Refund => "refund",
+            TransactionType::Interest => "interest",
+        }
+    }
+}
+
+impl FromStr for TransactionType {
+    type Err = TransactionError;
+
+    fn from_str(t_type: &str) -> Result<Self, Self::Err> {
+        match t_type {
+            "deposit" => Ok(TransactionType::Deposit),
+            "withdrawal" => Ok(TransactionType::Withdrawal),
+            "dispute" => Ok(TransactionType::Dispute),
+            "resolve" => Ok(TransactionType::Resolve),
+            "chargeback" => Ok(TransactionType::Chargeback),
+            "authorize" => Ok(TransactionType::Authorize),
+            "capture" => Ok(TransactionType::Capture),
+            "void" => Ok(TransactionType::Void),
+            "transfer" => Ok(TransactionType::Transfer),
+            "refund" => Ok(TransactionType::Refund),
+            "interest" => Ok(TransactionType::Interest),
+            _ => Err(TransactionError::from("Unknown transaction type")),
+        }
+    }
 }
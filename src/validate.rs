@@ -0,0 +1,263 @@
+use crate::bank::Bank;
+use crate::storage::Storage;
+use crate::transaction::transaction_error::TransactionErrorKind;
+use crate::transaction::Transaction;
+use csv::Reader;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::io;
+
+/* A row that was rejected or could not be parsed, line is its line in the input */
+#[derive(Debug, PartialEq)]
+pub struct ErrorSample {
+    pub line: u64,
+    pub message: String,
+}
+
+/*
+ * How many errors of each kind a validation tolerates, and how many of each are kept as samples
+ * for the report
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct ValidationConfig {
+    pub samples: usize,
+    pub max_rejected: u64,
+    pub max_malformed: u64,
+}
+
+impl Default for ValidationConfig {
+    fn default() -> Self {
+        Self {
+            samples: 5,
+            max_rejected: 0,
+            max_malformed: 0,
+        }
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct ValidationReport {
+    pub rows: u64,
+    pub accepted: u64,
+    pub rejected: BTreeMap<TransactionErrorKind, u64>,
+    pub malformed: u64,
+    /* The first rows rejected with each kind of error */
+    pub rejected_samples: BTreeMap<TransactionErrorKind, Vec<ErrorSample>>,
+    pub malformed_samples: Vec<ErrorSample>,
+    samples: usize,
+}
+
+impl ValidationReport {
+    fn new(samples: usize) -> Self {
+        Self {
+            samples,
+            ..ValidationReport::default()
+        }
+    }
+
+    fn reject(&mut self, kind: TransactionErrorKind, line: u64, message: String) {
+        *self.rejected.entry(kind).or_default() += 1;
+        let samples = self.rejected_samples.entry(kind).or_default();
+        if samples.len() < self.samples {
+            samples.push(ErrorSample { line, message });
+        }
+    }
+
+    fn malform(&mut self, line: u64, message: String) {
+        self.malformed += 1;
+        if self.malformed_samples.len() < self.samples {
+            self.malformed_samples.push(ErrorSample { line, message });
+        }
+    }
+
+    pub fn rejected_total(&self) -> u64 {
+        self.rejected.values().sum()
+    }
+
+    pub fn exceeds(&self, config: &ValidationConfig) -> bool {
+        self.rejected_total() > config.max_rejected || self.malformed > config.max_malformed
+    }
+}
+
+impl Display for ValidationReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        writeln!(f, "rows: {}", self.rows)?;
+        writeln!(f, "accepted: {}", self.accepted)?;
+        writeln!(f, "rejected: {}", self.rejected_total())?;
+        for (kind, count) in &self.rejected {
+            writeln!(f, "  {:?}: {}", kind, count)?;
+        }
+        writeln!(f, "malformed: {}", self.malformed)?;
+
+        for (kind, samples) in &self.rejected_samples {
+            writeln!(f, "\n{:?}:", kind)?;
+            for sample in samples {
+                writeln!(f, "  line {}: {}", sample.line, sample.message)?;
+            }
+        }
+        if !self.malformed_samples.is_empty() {
+            writeln!(f, "\nMalformed:")?;
+            for sample in &self.malformed_samples {
+                writeln!(f, "  line {}: {}", sample.line, sample.message)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/*
+ * Applies every row of the input to the bank, as add_records_to_bank does, counting the outcome
+ * of each instead of writing out the accounts. Storage failures stop the validation
+ */
+pub fn validate<S, R>(
+    mut bank: Bank<S>,
+    mut reader: Reader<R>,
+    config: &ValidationConfig,
+) -> Result<ValidationReport, Box<dyn Error>>
+where
+    S: Storage,
+    R: io::Read,
+{
+    let mut report = ValidationReport::new(config.samples);
+    let headers = reader.headers()?.clone();
+
+    for record in reader.records() {
+        report.rows += 1;
+        let record = match record {
+            Ok(record) => record,
+            Err(err) => {
+                let line = err.position().map(|position| position.line());
+                report.malform(line.unwrap_or_default(), err.to_string());
+                continue;
+            }
+        };
+        let line = record
+            .position()
+            .map(|position| position.line())
+            .unwrap_or_default();
+
+        let transaction: Transaction = match record.deserialize(Some(&headers)) {
+            Ok(transaction) => transaction,
+            Err(err) => {
+                report.malform(line, err.to_string());
+                continue;
+            }
+        };
+        let description = format!(
+            "{} client {} tx {}",
+            transaction.t_type.as_str(),
+            transaction.client_id,
+            transaction.id
+        );
+
+        match bank.transact(transaction) {
+            Ok(()) => report.accepted += 1,
+            Err(err) if err.kind().is_storage() => return Err(Box::new(err)),
+            Err(err) => report.reject(
+                *err.kind(),
+                line,
+                format!("{}: {}", description, err.message()),
+            ),
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::id::{client, tx};
+    use crate::ingest::trimmed_csv_reader;
+
+    fn input() -> String {
+        format!(
+            "type, client, tx, amount
+deposit, {0}, {2}, 5
+withdrawal, {0}, {3}, 10
+withdrawal, {0}, {4}, 20
+deposit, {1}, {2}, 1
+foobar, {0}, {5}, 1
+dispute, {0}, {6},
+deposit, {0}, {7}
+",
+            client(1),
+            client(2),
+            tx(1),
+            tx(2),
+            tx(3),
+            tx(4),
+            tx(9),
+            tx(5)
+        )
+    }
+
+    fn report(config: &ValidationConfig) -> ValidationReport {
+        let input = input();
+        validate(Bank::new(), trimmed_csv_reader(input.as_bytes()), config).unwrap()
+    }
+
+    #[test]
+    fn test_counts() {
+        let report = report(&ValidationConfig::default());
+
+        assert_eq!(7, report.rows);
+        assert_eq!(1, report.accepted);
+        assert_eq!(4, report.rejected_total());
+        assert_eq!(
+            Some(&2),
+            report.rejected.get(&TransactionErrorKind::NegativeBalance)
+        );
+        assert_eq!(
+            Some(&1),
+            report.rejected.get(&TransactionErrorKind::IncorrectClient)
+        );
+        assert_eq!(
+            Some(&1),
+            report.rejected.get(&TransactionErrorKind::DoesNotExist)
+        );
+        assert_eq!(2, report.malformed);
+        assert_eq!(
+            vec![6, 8],
+            report
+                .malformed_samples
+                .iter()
+                .map(|sample| sample.line)
+                .collect::<Vec<u64>>()
+        );
+    }
+
+    #[test]
+    fn test_samples() {
+        let report = report(&ValidationConfig {
+            samples: 1,
+            ..ValidationConfig::default()
+        });
+
+        let samples = &report.rejected_samples[&TransactionErrorKind::NegativeBalance];
+        assert_eq!(1, samples.len());
+        assert_eq!(3, samples[0].line);
+        let description = format!("withdrawal client {} tx {}: ", client(1), tx(2));
+        assert!(samples[0].message.starts_with(&description));
+        assert_eq!(1, report.malformed_samples.len());
+    }
+
+    #[test]
+    fn test_thresholds() {
+        let report = report(&ValidationConfig::default());
+
+        assert!(report.exceeds(&ValidationConfig::default()));
+        assert!(report.exceeds(&ValidationConfig {
+            max_rejected: 4,
+            max_malformed: 1,
+            ..ValidationConfig::default()
+        }));
+        assert!(!report.exceeds(&ValidationConfig {
+            max_rejected: 4,
+            max_malformed: 2,
+            ..ValidationConfig::default()
+        }));
+    }
+}
@@ -0,0 +1,691 @@
+use crate::account::{Account, TransactionResult};
+use crate::bank::TransactionListener;
+use crate::clock::timestamp;
+use crate::event::{AccountEvent, Event};
+use crate::id::{ClientId, TransactionId};
+use crate::storage::storage_error;
+use crate::transaction::transaction_type::TransactionType;
+use crate::transaction::Transaction;
+use ahash::AHashSet;
+use hmac::{Hmac, Mac};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use sha2::Sha256;
+use std::error::Error;
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use std::{cmp, env};
+
+const DEFAULT_OUTBOX: &str = "webhook-outbox";
+const PENDING_EXTENSION: &str = "json";
+const SIGNATURE_HEADER: &str = "X-Payments-Signature";
+/* How long the dispatcher waits for new notifications before checking the outbox again */
+const IDLE_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    Chargeback,
+    Locked,
+    Debt,
+    LargeWithdrawal,
+    /*
+     * The name debt notifications had when disputes of spent deposits were rejected as fraudulent,
+     * only sent when asked for by name, alongside debt
+     */
+    FraudulentDispute,
+}
+
+impl WebhookEvent {
+    pub const ALL: [WebhookEvent; 4] = [
+        WebhookEvent::Chargeback,
+        WebhookEvent::Locked,
+        WebhookEvent::Debt,
+        WebhookEvent::LargeWithdrawal,
+    ];
+}
+
+impl FromStr for WebhookEvent {
+    type Err = String;
+
+    fn from_str(event: &str) -> Result<Self, Self::Err> {
+        match event {
+            "chargeback" => Ok(WebhookEvent::Chargeback),
+            "locked" => Ok(WebhookEvent::Locked),
+            "debt" => Ok(WebhookEvent::Debt),
+            "large_withdrawal" => Ok(WebhookEvent::LargeWithdrawal),
+            "fraudulent_dispute" => Ok(WebhookEvent::FraudulentDispute),
+            _ => Err(format!("Unknown webhook event {}", event)),
+        }
+    }
+}
+
+/*
+ * Deliveries are retried max_attempts times, the wait between attempts doubling from
+ * initial_backoff up to max_backoff, a notification that still fails stays in the outbox and is
+ * retried, along with every notification queued after it, once max_backoff has passed
+ */
+#[derive(Clone, Debug)]
+pub struct WebhookConfig {
+    pub url: String,
+    pub secret: String,
+    pub outbox: PathBuf,
+    pub events: AHashSet<WebhookEvent>,
+    pub large_withdrawal: Decimal,
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub timeout: Duration,
+}
+
+impl WebhookConfig {
+    pub fn new<P>(url: &str, secret: &str, outbox: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self {
+            url: url.to_string(),
+            secret: secret.to_string(),
+            outbox: outbox.as_ref().to_path_buf(),
+            events: WebhookEvent::ALL.into_iter().collect(),
+            large_withdrawal: Decimal::from(10_000),
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    /* None unless PAYMENTS_WEBHOOK_URL is set */
+    pub fn from_env() -> Result<Option<Self>, Box<dyn Error>> {
+        let url = match env::var("PAYMENTS_WEBHOOK_URL") {
+            Ok(url) => url,
+            Err(_) => return Ok(None),
+        };
+        let secret = env::var("PAYMENTS_WEBHOOK_SECRET")
+            .map_err(|_| "PAYMENTS_WEBHOOK_SECRET must be set along with PAYMENTS_WEBHOOK_URL")?;
+        let outbox = env::var_os("PAYMENTS_WEBHOOK_OUTBOX")
+            .unwrap_or_else(|| OsString::from(DEFAULT_OUTBOX));
+
+        let mut config = WebhookConfig::new(&url, &secret, outbox);
+        if let Ok(events) = env::var("PAYMENTS_WEBHOOK_EVENTS") {
+            config.events = events
+                .split(',')
+                .map(|event| event.trim().parse())
+                .collect::<Result<_, _>>()?;
+        }
+        if let Ok(amount) = env::var("PAYMENTS_WEBHOOK_LARGE_WITHDRAWAL") {
+            config.large_withdrawal = Decimal::from_str(&amount)?;
+        }
+
+        Ok(Some(config))
+    }
+}
+
+/* The body of a webhook request, delivery is unique to the notification across retries */
+#[derive(Serialize)]
+struct Notification {
+    delivery: String,
+    event: WebhookEvent,
+    #[serde(rename = "client")]
+    client_id: ClientId,
+    #[serde(rename = "tx")]
+    id: Option<TransactionId>,
+    amount: Option<Decimal>,
+    timestamp: u64,
+}
+
+/* Hex encoded HMAC-SHA256 of the body, sent as sha256=<signature> */
+pub fn signature(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/* Notifications waiting to be delivered, oldest first */
+fn pending(outbox: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(outbox)? {
+        let path = entry?.path();
+        if path.extension() == Some(OsStr::new(PENDING_EXTENSION)) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    Ok(paths)
+}
+
+/*
+ * One file per notification, named by the order it was queued in, written in full before it is
+ * renamed into place so that the dispatcher never reads a partial notification
+ */
+struct Outbox {
+    dir: PathBuf,
+    next: u64,
+}
+
+impl Outbox {
+    fn open(dir: &Path) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let next = pending(dir)?
+            .last()
+            .and_then(|path| path.file_stem()?.to_str()?.parse::<u64>().ok())
+            .map_or(0, |last| last + 1);
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            next,
+        })
+    }
+
+    fn push(&mut self, body: &[u8]) -> io::Result<()> {
+        let path = self
+            .dir
+            .join(format!("{:020}.{}", self.next, PENDING_EXTENSION));
+        let partial = path.with_extension("partial");
+        let mut file = File::create(&partial)?;
+        file.write_all(body)?;
+        file.sync_all()?;
+        fs::rename(partial, path)?;
+        self.next += 1;
+
+        Ok(())
+    }
+}
+
+/*
+ * Queues a notification in the outbox for chargebacks, accounts being locked, disputes leaving
+ * the account in debt and withdrawals of at least large_withdrawal, chargebacks made by the dispute
+ * policy included
+ */
+pub struct WebhookListener {
+    config: Arc<WebhookConfig>,
+    outbox: Outbox,
+    wake: Sender<()>,
+    /* The bank's time when the last transaction's events were recorded */
+    recorded_at: Option<u64>,
+}
+
+impl WebhookListener {
+    fn queue(
+        &mut self,
+        event: WebhookEvent,
+        client_id: ClientId,
+        id: Option<TransactionId>,
+        amount: Option<Decimal>,
+        timestamp: u64,
+    ) -> TransactionResult<()> {
+        if !self.config.events.contains(&event) {
+            return Ok(());
+        }
+
+        let notification = Notification {
+            delivery: format!("{:016x}", rand::random::<u64>()),
+            event,
+            client_id,
+            id,
+            amount: amount.map(|amount| amount.normalize()),
+            timestamp,
+        };
+        let body = serde_json::to_vec(&notification).map_err(storage_error)?;
+        self.outbox.push(&body).map_err(storage_error)?;
+
+        /* Fails only once the dispatcher has stopped, the notification is sent on the next start */
+        let _ = self.wake.send(());
+
+        Ok(())
+    }
+}
+
+impl TransactionListener for WebhookListener {
+    fn on_events(&mut self, events: &[Event]) -> TransactionResult<()> {
+        self.recorded_at = events.last().map(|event| event.timestamp);
+        for event in events {
+            match event.event {
+                AccountEvent::ChargedBack { id, amount } => self.queue(
+                    WebhookEvent::Chargeback,
+                    event.client_id,
+                    Some(id),
+                    Some(amount),
+                    event.timestamp,
+                )?,
+                AccountEvent::Locked => self.queue(
+                    WebhookEvent::Locked,
+                    event.client_id,
+                    None,
+                    None,
+                    event.timestamp,
+                )?,
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn on_transaction(
+        &mut self,
+        transaction: &Transaction,
+        result: &TransactionResult<()>,
+        accounts: &[Account],
+    ) -> TransactionResult<()> {
+        let timestamp = self.recorded_at.unwrap_or_else(timestamp);
+        match (result, &transaction.t_type, transaction.amount) {
+            /* The dispute of a transfer holds the funds of the recipient too, who may go into debt */
+            (Ok(()), TransactionType::Dispute, _) => {
+                for account in accounts.iter().filter(|account| !account.debt().is_zero()) {
+                    for event in [WebhookEvent::Debt, WebhookEvent::FraudulentDispute] {
+                        self.queue(
+                            event,
+                            account.client_id(),
+                            Some(transaction.id),
+                            Some(*account.debt()),
+                            timestamp,
+                        )?;
+                    }
+                }
+
+                Ok(())
+            }
+            (Ok(()), TransactionType::Withdrawal, Some(amount))
+                if amount >= self.config.large_withdrawal =>
+            {
+                self.queue(
+                    WebhookEvent::LargeWithdrawal,
+                    transaction.client_id,
+                    Some(transaction.id),
+                    Some(amount),
+                    timestamp,
+                )
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+fn deliver(config: &WebhookConfig, body: &[u8]) -> bool {
+    let signature = format!("sha256={}", signature(&config.secret, body));
+    let mut backoff = config.initial_backoff;
+
+    for attempt in 1..=config.max_attempts {
+        let response = ureq::post(&config.url)
+            .timeout(config.timeout)
+            .set("Content-Type", "application/json")
+            .set(SIGNATURE_HEADER, &signature)
+            .send_bytes(body);
+        if response.is_ok() {
+            return true;
+        }
+
+        if attempt < config.max_attempts {
+            thread::sleep(backoff);
+            backoff = cmp::min(backoff * 2, config.max_backoff);
+        }
+    }
+
+    false
+}
+
+/* Notifications are delivered in the order they were queued, stopping at the first that fails */
+fn deliver_pending(config: &WebhookConfig) -> io::Result<bool> {
+    for path in pending(&config.outbox)? {
+        if !deliver(config, &fs::read(&path)?) {
+            return Ok(false);
+        }
+        fs::remove_file(path)?;
+    }
+
+    Ok(true)
+}
+
+/*
+ * Delivers the notifications queued by its listener on a background thread, starting with any
+ * left in the outbox by a previous run
+ */
+pub struct WebhookDispatcher {
+    thread: JoinHandle<()>,
+}
+
+impl WebhookDispatcher {
+    pub fn start(config: WebhookConfig) -> io::Result<(WebhookListener, Self)> {
+        let config = Arc::new(config);
+        let outbox = Outbox::open(&config.outbox)?;
+        let (wake, woken) = channel();
+
+        let dispatcher_config = config.clone();
+        let thread = thread::spawn(move || dispatch(&dispatcher_config, woken));
+
+        Ok((
+            WebhookListener {
+                config,
+                outbox,
+                wake,
+                recorded_at: None,
+            },
+            Self { thread },
+        ))
+    }
+
+    /*
+     * Returns once the listener has been dropped (along with its bank) and every notification it
+     * queued has been delivered or has run out of attempts
+     */
+    pub fn join(self) {
+        let _ = self.thread.join();
+    }
+}
+
+fn dispatch(config: &WebhookConfig, woken: Receiver<()>) {
+    loop {
+        let wait = match deliver_pending(config) {
+            Ok(true) => IDLE_INTERVAL,
+            _ => config.max_backoff,
+        };
+
+        if let Err(RecvTimeoutError::Disconnected) = woken.recv_timeout(wait) {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::Bank;
+    use crate::dispute::{DisputeAction, DisputePolicy};
+    use crate::id::{client, tx};
+    use serde_json::{json, Value};
+    use std::collections::VecDeque;
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::TcpListener;
+    use std::sync::Mutex;
+
+    fn decimal_str(decimal: &str) -> Decimal {
+        Decimal::from_str(decimal).unwrap()
+    }
+
+    /* Status replied with, signature header and body of every request received */
+    type Received = Arc<Mutex<Vec<(u16, String, Vec<u8>)>>>;
+
+    /* Replies with the given statuses in turn, then 200 */
+    fn stand_in(statuses: Vec<u16>) -> (String, Received) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hooks", listener.local_addr().unwrap());
+        let received = Received::default();
+        let server_received = received.clone();
+        let mut statuses = VecDeque::from(statuses);
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let (mut length, mut signature) = (0, String::new());
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end();
+                    if line.is_empty() {
+                        break;
+                    }
+                    let (name, value) = line.split_once(": ").unwrap_or((line, ""));
+                    if name.eq_ignore_ascii_case("content-length") {
+                        length = value.parse().unwrap();
+                    } else if name.eq_ignore_ascii_case(SIGNATURE_HEADER) {
+                        signature = value.to_string();
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+
+                let status = statuses.pop_front().unwrap_or(200);
+                server_received
+                    .lock()
+                    .unwrap()
+                    .push((status, signature, body));
+                write!(
+                    stream,
+                    "HTTP/1.1 {} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                )
+                .unwrap();
+            }
+        });
+
+        (url, received)
+    }
+
+    fn config(url: &str, outbox: &str) -> WebhookConfig {
+        let _ = fs::remove_dir_all(outbox);
+        let mut config = WebhookConfig::new(url, "secret", outbox);
+        config.large_withdrawal = decimal_str("50");
+        config.max_attempts = 2;
+        config.initial_backoff = Duration::from_millis(10);
+        config.max_backoff = Duration::from_millis(50);
+
+        config
+    }
+
+    fn delivered(received: &Received) -> Vec<Value> {
+        received
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(status, _, _)| *status == 200)
+            .map(|(_, signature, body)| {
+                assert_eq!(
+                    &format!("sha256={}", super::signature("secret", body)),
+                    signature
+                );
+                serde_json::from_slice(body).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_notifications() {
+        let (url, received) = stand_in(Vec::new());
+        let outbox = "webhook_notifications_test";
+        let (listener, dispatcher) = WebhookDispatcher::start(config(&url, outbox)).unwrap();
+        let mut bank = Bank::new()
+            .with_dispute_policy(DisputePolicy::new(1, DisputeAction::Chargeback))
+            .with_listener(listener);
+
+        bank.transact(Transaction::new_deposit(
+            client(1),
+            tx(1),
+            decimal_str("100"),
+        ))
+        .unwrap();
+        bank.transact(Transaction::new_withdrawal(
+            client(1),
+            tx(2),
+            decimal_str("60"),
+        ))
+        .unwrap();
+        bank.transact(Transaction::new_dispute(client(1), tx(1)))
+            .unwrap();
+        bank.transact(Transaction::new_resolve(client(1), tx(1)))
+            .unwrap();
+        bank.transact(Transaction::new_deposit(
+            client(2),
+            tx(3),
+            decimal_str("10"),
+        ))
+        .unwrap();
+        bank.transact(Transaction::new_dispute(client(2), tx(3)))
+            .unwrap();
+        bank.transact(Transaction::new_withdrawal(
+            client(1),
+            tx(4),
+            decimal_str("10"),
+        ))
+        .unwrap();
+        /* Past the dispute's max age, so the policy charges it back */
+        bank.transact(Transaction::new_deposit(client(1), tx(5), decimal_str("1")))
+            .unwrap();
+        drop(bank);
+        dispatcher.join();
+
+        let notifications = delivered(&received);
+        let events: Vec<(&str, &Value)> = notifications
+            .iter()
+            .map(|notification| {
+                (
+                    notification["event"].as_str().unwrap(),
+                    &notification["client"],
+                )
+            })
+            .collect();
+        let (first, second) = (json!(client(1)), json!(client(2)));
+        assert_eq!(
+            vec![
+                ("large_withdrawal", &first),
+                ("debt", &first),
+                ("chargeback", &second),
+                ("locked", &second)
+            ],
+            events
+        );
+        assert_eq!("60", notifications[0]["amount"]);
+        assert_eq!("60", notifications[1]["amount"]);
+        assert_eq!(json!(tx(3)), notifications[2]["tx"]);
+        assert_eq!(Value::Null, notifications[3]["tx"]);
+        assert!(pending(Path::new(outbox)).unwrap().is_empty());
+
+        fs::remove_dir_all(outbox).unwrap();
+    }
+
+    #[test]
+    fn test_fraudulent_dispute_alias() {
+        let (url, received) = stand_in(Vec::new());
+        let outbox = "webhook_alias_test";
+        let mut config = config(&url, outbox);
+        config.events = "fraudulent_dispute,debt"
+            .split(',')
+            .map(|event| event.parse().unwrap())
+            .collect();
+
+        let (listener, dispatcher) = WebhookDispatcher::start(config).unwrap();
+        let mut bank = Bank::new().with_listener(listener);
+        bank.transact(Transaction::new_deposit(
+            client(1),
+            tx(1),
+            decimal_str("100"),
+        ))
+        .unwrap();
+        bank.transact(Transaction::new_withdrawal(
+            client(1),
+            tx(2),
+            decimal_str("60"),
+        ))
+        .unwrap();
+        bank.transact(Transaction::new_dispute(client(1), tx(1)))
+            .unwrap();
+        drop(bank);
+        dispatcher.join();
+
+        let notifications = delivered(&received);
+        assert_eq!(2, notifications.len());
+        assert_eq!("debt", notifications[0]["event"]);
+        assert_eq!("fraudulent_dispute", notifications[1]["event"]);
+        assert_eq!("60", notifications[1]["amount"]);
+
+        fs::remove_dir_all(outbox).unwrap();
+    }
+
+    #[test]
+    fn test_transfer_dispute_into_recipient_debt() {
+        let (url, received) = stand_in(Vec::new());
+        let outbox = "webhook_transfer_dispute_test";
+        let (listener, dispatcher) = WebhookDispatcher::start(config(&url, outbox)).unwrap();
+        let mut bank = Bank::new().with_listener(listener);
+        bank.transact(Transaction::new_deposit(
+            client(1),
+            tx(1),
+            decimal_str("40"),
+        ))
+        .unwrap();
+        bank.transact(Transaction::new_transfer(
+            client(1),
+            tx(2),
+            client(2),
+            decimal_str("40"),
+        ))
+        .unwrap();
+        bank.transact(Transaction::new_withdrawal(
+            client(2),
+            tx(3),
+            decimal_str("30"),
+        ))
+        .unwrap();
+        bank.transact(Transaction::new_dispute(client(1), tx(2)))
+            .unwrap();
+        drop(bank);
+        dispatcher.join();
+
+        let notifications = delivered(&received);
+        assert_eq!(1, notifications.len());
+        assert_eq!("debt", notifications[0]["event"]);
+        assert_eq!(json!(client(2)), notifications[0]["client"]);
+        assert_eq!(json!(tx(2)), notifications[0]["tx"]);
+        assert_eq!("30", notifications[0]["amount"]);
+
+        fs::remove_dir_all(outbox).unwrap();
+    }
+
+    #[test]
+    fn test_retries_and_outbox() {
+        let (url, received) = stand_in(vec![500, 503]);
+        let outbox = "webhook_outbox_test";
+        let mut config = config(&url, outbox);
+        config.events = [WebhookEvent::LargeWithdrawal].into_iter().collect();
+
+        let (listener, dispatcher) = WebhookDispatcher::start(config.clone()).unwrap();
+        let mut bank = Bank::new().with_listener(listener);
+        bank.transact(Transaction::new_deposit(
+            client(1),
+            tx(1),
+            decimal_str("100"),
+        ))
+        .unwrap();
+        bank.transact(Transaction::new_withdrawal(
+            client(1),
+            tx(2),
+            decimal_str("50"),
+        ))
+        .unwrap();
+        drop(bank);
+        dispatcher.join();
+
+        /* Both attempts failed, so the notification is kept for the next run */
+        assert_eq!(2, received.lock().unwrap().len());
+        assert!(delivered(&received).is_empty());
+        assert_eq!(1, pending(Path::new(outbox)).unwrap().len());
+
+        let (listener, dispatcher) = WebhookDispatcher::start(config).unwrap();
+        drop(listener);
+        dispatcher.join();
+
+        let notifications = delivered(&received);
+        assert_eq!(1, notifications.len());
+        assert_eq!("large_withdrawal", notifications[0]["event"]);
+        assert_eq!(json!(tx(2)), notifications[0]["tx"]);
+        assert!(pending(Path::new(outbox)).unwrap().is_empty());
+
+        fs::remove_dir_all(outbox).unwrap();
+    }
+}
@@ -0,0 +1,314 @@
+use crate::account::{Account, TransactionResult};
+use crate::bank::TransactionListener;
+use crate::id::ClientId;
+use crate::transaction::Transaction;
+use ahash::{AHashMap, AHashSet};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::io;
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tungstenite::{accept, Message};
+
+/* How long a connection waits for a subscription change before sending queued messages */
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/*
+ * Sent by the client, each subscription replaces the previous one, e.g.
+ * {"clients": [1, 2], "rejections": true}
+ */
+#[derive(Deserialize, Default, Debug, PartialEq)]
+pub struct Subscription {
+    pub clients: AHashSet<ClientId>,
+    #[serde(default)]
+    pub rejections: bool,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum FeedMessage<'a> {
+    Applied {
+        transaction: &'a Transaction,
+        account: &'a Account,
+    },
+    Rejected {
+        transaction: &'a Transaction,
+        error: String,
+    },
+}
+
+struct Subscriber {
+    subscription: Subscription,
+    sender: Sender<String>,
+}
+
+/* Connections subscribed to the feed, shared between the listener and the server */
+#[derive(Clone, Default)]
+pub struct Feed {
+    subscribers: Arc<Mutex<AHashMap<u64, Subscriber>>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl Feed {
+    pub fn new() -> Self {
+        Feed::default()
+    }
+
+    pub fn listener(&self) -> FeedListener {
+        FeedListener { feed: self.clone() }
+    }
+
+    fn subscribe(&self) -> (u64, Receiver<String>) {
+        let (sender, receiver) = channel();
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.subscribers.lock().unwrap().insert(
+            id,
+            Subscriber {
+                subscription: Subscription::default(),
+                sender,
+            },
+        );
+
+        (id, receiver)
+    }
+
+    fn update(&self, id: u64, subscription: Subscription) {
+        if let Some(subscriber) = self.subscribers.lock().unwrap().get_mut(&id) {
+            subscriber.subscription = subscription;
+        }
+    }
+
+    fn unsubscribe(&self, id: u64) {
+        self.subscribers.lock().unwrap().remove(&id);
+    }
+
+    fn publish(&self, client_id: ClientId, rejection: bool, message: &str) {
+        self.subscribers.lock().unwrap().retain(|_, subscriber| {
+            let subscription = &subscriber.subscription;
+            if !subscription.clients.contains(&client_id) || (rejection && !subscription.rejections)
+            {
+                return true;
+            }
+
+            /* The connection has gone away */
+            subscriber.sender.send(message.to_string()).is_ok()
+        });
+    }
+}
+
+/* Publishes applied and rejected transactions to the feed, storage failures are not published */
+pub struct FeedListener {
+    feed: Feed,
+}
+
+impl TransactionListener for FeedListener {
+    fn on_transaction(
+        &mut self,
+        transaction: &Transaction,
+        result: &TransactionResult<()>,
+        accounts: &[Account],
+    ) -> TransactionResult<()> {
+        /* Each account the transaction touched is published to the subscribers of its client */
+        let messages = match result {
+            Ok(()) => accounts
+                .iter()
+                .map(|account| {
+                    let message = FeedMessage::Applied {
+                        transaction,
+                        account,
+                    };
+                    (account.client_id(), message)
+                })
+                .collect(),
+            Err(err) if !err.kind().is_storage() => {
+                let message = FeedMessage::Rejected {
+                    transaction,
+                    error: format!("{:?}", err.kind()),
+                };
+                vec![(transaction.client_id, message)]
+            }
+            Err(_) => Vec::new(),
+        };
+
+        for (client_id, message) in messages {
+            if let Ok(message) = serde_json::to_string(&message) {
+                self.feed.publish(client_id, result.is_err(), &message);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn handle(feed: &Feed, stream: TcpStream) -> Result<(), Box<dyn Error>> {
+    let mut socket = accept(stream)?;
+    socket.get_ref().set_read_timeout(Some(POLL_INTERVAL))?;
+    let (id, receiver) = feed.subscribe();
+
+    let result = loop {
+        match socket.read_message() {
+            Ok(Message::Text(text)) => match serde_json::from_str(&text) {
+                Ok(subscription) => feed.update(id, subscription),
+                Err(err) => {
+                    let error = serde_json::json!({"type": "error", "error": err.to_string()});
+                    if let Err(err) = socket.write_message(Message::Text(error.to_string())) {
+                        break Err(err);
+                    }
+                }
+            },
+            Ok(Message::Close(_)) => break Ok(()),
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(err))
+                if matches!(
+                    err.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) => {}
+            Err(err) => break Err(err),
+        }
+
+        if let Some(err) = receiver
+            .try_iter()
+            .find_map(|message| socket.write_message(Message::Text(message)).err())
+        {
+            break Err(err);
+        }
+    };
+    feed.unsubscribe(id);
+
+    Ok(result?)
+}
+
+/* Serves every WebSocket connection on its own thread */
+pub fn serve(feed: Feed, listener: TcpListener) -> io::Result<()> {
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let feed = feed.clone();
+        thread::spawn(move || {
+            let _ = handle(&feed, stream);
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::Bank;
+    use crate::id::{client, tx};
+    use rust_decimal::Decimal;
+    use serde_json::Value;
+    use std::str::FromStr;
+
+    fn decimal_str(decimal: &str) -> Decimal {
+        Decimal::from_str(decimal).unwrap()
+    }
+
+    #[test]
+    fn test_publish() {
+        let feed = Feed::new();
+        let (all, all_receiver) = feed.subscribe();
+        feed.update(
+            all,
+            Subscription {
+                clients: [client(1), client(2)].into_iter().collect(),
+                rejections: true,
+            },
+        );
+        let (applied, applied_receiver) = feed.subscribe();
+        feed.update(
+            applied,
+            Subscription {
+                clients: [client(1)].into_iter().collect(),
+                rejections: false,
+            },
+        );
+
+        let mut bank = Bank::new().with_listener(feed.listener());
+        bank.transact(Transaction::new_deposit(client(1), tx(1), decimal_str("2")))
+            .unwrap();
+        bank.transact(Transaction::new_withdrawal(
+            client(1),
+            tx(2),
+            decimal_str("3"),
+        ))
+        .unwrap_err();
+        bank.transact(Transaction::new_deposit(client(3), tx(3), decimal_str("1")))
+            .unwrap();
+
+        let messages: Vec<Value> = all_receiver
+            .try_iter()
+            .map(|message| serde_json::from_str(&message).unwrap())
+            .collect();
+        assert_eq!(2, messages.len());
+        assert_eq!("applied", messages[0]["type"]);
+        assert_eq!("deposit", messages[0]["transaction"]["type"]);
+        assert_eq!("2", messages[0]["account"]["available"]);
+        assert_eq!(false, messages[0]["account"]["locked"]);
+        assert_eq!("rejected", messages[1]["type"]);
+        assert_eq!("NegativeBalance", messages[1]["error"]);
+
+        assert_eq!(1, applied_receiver.try_iter().count());
+
+        drop(applied_receiver);
+        bank.transact(Transaction::new_deposit(client(1), tx(4), decimal_str("1")))
+            .unwrap();
+        assert_eq!(1, feed.subscribers.lock().unwrap().len());
+
+        /* The recipient of a transfer is published too */
+        bank.transact(Transaction::new_transfer(
+            client(3),
+            tx(5),
+            client(1),
+            decimal_str("1"),
+        ))
+        .unwrap();
+        let messages: Vec<Value> = all_receiver
+            .try_iter()
+            .map(|message| serde_json::from_str(&message).unwrap())
+            .collect();
+        assert_eq!(2, messages.len());
+        assert_eq!("transfer", messages[1]["transaction"]["type"]);
+        assert_eq!("4", messages[1]["account"]["available"]);
+    }
+
+    #[test]
+    fn test_serve() {
+        let feed = Feed::new();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server_feed = feed.clone();
+        thread::spawn(move || serve(server_feed, listener));
+
+        let (mut socket, _) = tungstenite::connect(format!("ws://{}", address)).unwrap();
+        let subscription = serde_json::json!({ "clients": [client(1)] });
+        socket
+            .write_message(Message::Text(subscription.to_string()))
+            .unwrap();
+        while feed
+            .subscribers
+            .lock()
+            .unwrap()
+            .values()
+            .all(|subscriber| subscriber.subscription.clients.is_empty())
+        {
+            thread::sleep(POLL_INTERVAL);
+        }
+
+        let mut bank = Bank::new().with_listener(feed.listener());
+        bank.transact(Transaction::new_deposit(client(1), tx(1), decimal_str("2")))
+            .unwrap();
+
+        let message: Value = match socket.read_message().unwrap() {
+            Message::Text(text) => serde_json::from_str(&text).unwrap(),
+            message => panic!("unexpected message {:?}", message),
+        };
+        assert_eq!("applied", message["type"]);
+        assert_eq!(serde_json::json!(tx(1)), message["transaction"]["tx"]);
+        assert_eq!("2", message["account"]["total"]);
+    }
+}
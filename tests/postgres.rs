@@ -0,0 +1,381 @@
+#![cfg(feature = "postgres")]
+/*
+ * Runs against a local Postgres given by PAYMENTS_POSTGRES_TEST, e.g.
+ * PAYMENTS_POSTGRES_TEST="host=localhost user=postgres" cargo test --features postgres
+ * each test works in its own schema, tests are skipped when the variable is not set
+ */
+use payments::bank::Bank;
+use payments::event::AsOf;
+use payments::fee::{Fee, FeeSchedule};
+use payments::generator::{Generator, WorkloadConfig};
+use payments::ingest::trimmed_csv_reader;
+use payments::overdraft::OverdraftLimits;
+use payments::storage::postgres::PostgresStorage;
+use payments::storage::Storage;
+use payments::transaction::transaction_error::TransactionErrorKind;
+use payments::transaction::transaction_type::TransactionType;
+use payments::transaction::Transaction;
+use postgres::{Client, NoTls};
+use rust_decimal::Decimal;
+use std::env;
+use std::str::FromStr;
+use std::thread;
+
+const THREADS: u16 = 4;
+
+fn decimal_str(decimal: &str) -> Decimal {
+    Decimal::from_str(decimal).unwrap()
+}
+
+fn params() -> Option<String> {
+    let params = env::var("PAYMENTS_POSTGRES_TEST").ok();
+    if params.is_none() {
+        eprintln!("PAYMENTS_POSTGRES_TEST is not set, skipping");
+    }
+    params
+}
+
+fn client(params: &str, schema: &str) -> Client {
+    let mut client = Client::connect(params, NoTls).unwrap();
+    client
+        .batch_execute(&format!("SET search_path TO {}", schema))
+        .unwrap();
+    client
+}
+
+fn postgres_bank(params: &str, schema: &str) -> Bank<PostgresStorage> {
+    Bank::with_storage(PostgresStorage::from_client(client(params, schema)).unwrap())
+}
+
+fn reset(params: &str, schema: &str) {
+    Client::connect(params, NoTls)
+        .unwrap()
+        .batch_execute(&format!(
+            "DROP SCHEMA IF EXISTS {0} CASCADE; CREATE SCHEMA {0}",
+            schema
+        ))
+        .unwrap();
+}
+
+fn drop_schema(params: &str, schema: &str) {
+    Client::connect(params, NoTls)
+        .unwrap()
+        .batch_execute(&format!("DROP SCHEMA {} CASCADE", schema))
+        .unwrap();
+}
+
+#[test]
+fn test_consumer_offsets() {
+    let params = match params() {
+        Some(params) => params,
+        None => return,
+    };
+    let schema = "payments_test_consumer_offsets";
+    reset(&params, schema);
+
+    let mut bank = postgres_bank(&params, schema);
+    let deposit = Transaction::new_deposit(1, 1, decimal_str("2"));
+    assert!(bank
+        .consume("transactions", 3, 0, Some(deposit.clone()))
+        .unwrap());
+    assert!(bank.consume("transactions", 3, 1, None).unwrap());
+    drop(bank);
+
+    let mut bank = postgres_bank(&params, schema);
+    assert_eq!(
+        2,
+        bank.storage().consumer_offset("transactions", 3).unwrap()
+    );
+    assert_eq!(0, bank.storage().consumer_offset("other", 3).unwrap());
+    assert!(!bank.consume("transactions", 3, 0, Some(deposit)).unwrap());
+    assert_eq!(&decimal_str("2"), bank.account(1).unwrap().unwrap().total());
+
+    drop(bank);
+    drop_schema(&params, schema);
+}
+
+#[test]
+fn test_transact() {
+    let params = match params() {
+        Some(params) => params,
+        None => return,
+    };
+    let schema = "payments_test_transact";
+    reset(&params, schema);
+
+    let mut bank = postgres_bank(&params, schema);
+    bank.transact(Transaction::new_deposit(1, 1, decimal_str("2.5")))
+        .unwrap();
+    bank.transact(Transaction::new_withdrawal(1, 2, decimal_str("0.5")))
+        .unwrap();
+    bank.transact(Transaction::new_deposit(2, 3, decimal_str("1")))
+        .unwrap();
+    bank.transact(Transaction::new_dispute(2, 3)).unwrap();
+
+    let err = bank
+        .transact(Transaction::new_deposit(2, 1, decimal_str("1")))
+        .unwrap_err();
+    assert_eq!(&TransactionErrorKind::IncorrectClient, err.kind());
+    let err = bank
+        .transact(Transaction::new_withdrawal(1, 4, decimal_str("5")))
+        .unwrap_err();
+    assert_eq!(&TransactionErrorKind::NegativeBalance, err.kind());
+
+    let account = bank.account(1).unwrap().unwrap();
+    assert_eq!(&decimal_str("2"), account.available());
+    assert_eq!(&decimal_str("2"), account.total());
+
+    let account = bank.account(2).unwrap().unwrap();
+    assert_eq!(&decimal_str("0"), account.available());
+    assert_eq!(&decimal_str("1"), account.held());
+    assert!(account.transaction(&3).unwrap().disputed);
+
+    drop_schema(&params, schema);
+}
+
+#[test]
+fn test_resume() {
+    let params = match params() {
+        Some(params) => params,
+        None => return,
+    };
+    let schema = "payments_test_resume";
+    reset(&params, schema);
+
+    let mut bank = postgres_bank(&params, schema);
+    bank.transact(Transaction::new_deposit(1, 1, decimal_str("2")))
+        .unwrap();
+    bank.transact(Transaction::new_dispute(1, 1)).unwrap();
+    drop(bank);
+
+    let mut bank = postgres_bank(&params, schema);
+    let disputes = bank.open_disputes().unwrap();
+    assert_eq!(1, disputes.len());
+    assert_eq!(1, disputes[0].opened);
+    assert_eq!(1, disputes[0].age);
+
+    bank.transact(Transaction::new_chargeback(1, 1)).unwrap();
+    let err = bank
+        .transact(Transaction::new_deposit(1, 2, decimal_str("1")))
+        .unwrap_err();
+    assert_eq!(&TransactionErrorKind::Locked, err.kind());
+
+    let accounts = bank.accounts().unwrap();
+    assert_eq!(1, accounts.len());
+    assert_eq!(&Decimal::ZERO, accounts[0].total());
+    assert!(accounts[0].locked());
+    assert!(bank.open_disputes().unwrap().is_empty());
+
+    let account = bank.account_at(1, AsOf::Sequence(1)).unwrap().unwrap();
+    assert_eq!(&decimal_str("2"), account.held());
+    assert!(!account.locked());
+    assert_eq!(4, bank.storage().events(1).unwrap().len());
+
+    drop_schema(&params, schema);
+}
+
+#[test]
+fn test_authorizations() {
+    let params = match params() {
+        Some(params) => params,
+        None => return,
+    };
+    let schema = "payments_test_authorizations";
+    reset(&params, schema);
+
+    let mut bank = postgres_bank(&params, schema).with_authorization_expiry(2);
+    bank.transact(Transaction::new_deposit(1, 1, decimal_str("10")))
+        .unwrap();
+    bank.transact(Transaction::new_authorize(1, 2, decimal_str("6")))
+        .unwrap();
+    bank.transact(Transaction::new_capture(1, 2, Some(decimal_str("2"))))
+        .unwrap();
+    drop(bank);
+
+    /* What remains of the authorization is reloaded, then expired by the next transaction */
+    let mut bank = postgres_bank(&params, schema).with_authorization_expiry(2);
+    let err = bank
+        .transact(Transaction::new_capture(1, 2, Some(decimal_str("5"))))
+        .unwrap_err();
+    assert_eq!(&TransactionErrorKind::ExceedsAuthorization, err.kind());
+    let account = bank.account(1).unwrap().unwrap();
+    assert_eq!(&decimal_str("4"), account.held());
+    assert_eq!(&decimal_str("4"), account.authorized());
+    assert_eq!(&decimal_str("8"), account.total());
+
+    bank.transact(Transaction::new_deposit(2, 3, decimal_str("1")))
+        .unwrap();
+    let account = bank.account(1).unwrap().unwrap();
+    assert_eq!(&decimal_str("8"), account.available());
+    assert_eq!(&Decimal::ZERO, account.authorized());
+    let err = bank.transact(Transaction::new_void(1, 2)).unwrap_err();
+    assert_eq!(&TransactionErrorKind::NotAuthorized, err.kind());
+
+    drop_schema(&params, schema);
+}
+
+#[test]
+fn test_transfer() {
+    let params = match params() {
+        Some(params) => params,
+        None => return,
+    };
+    let schema = "payments_test_transfer";
+    reset(&params, schema);
+
+    let fees =
+        FeeSchedule::new().with_fee(TransactionType::Transfer, Fee::Flat(decimal_str("0.5")));
+    let mut bank = postgres_bank(&params, schema).with_fee_schedule(fees);
+    bank.transact(Transaction::new_deposit(1, 1, decimal_str("5.5")))
+        .unwrap();
+    bank.transact(Transaction::new_transfer(1, 2, 2, decimal_str("3")))
+        .unwrap();
+    drop(bank);
+
+    /* The destination is reloaded with the transfer to reverse both legs */
+    let mut bank = postgres_bank(&params, schema);
+    bank.transact(Transaction::new_dispute(1, 2)).unwrap();
+    assert_eq!(&decimal_str("3"), bank.account(2).unwrap().unwrap().held());
+    bank.transact(Transaction::new_chargeback(1, 2)).unwrap();
+
+    let source = bank.account(1).unwrap().unwrap();
+    assert_eq!(&decimal_str("5"), source.total());
+    assert_eq!(&decimal_str("0.5"), source.fees());
+    assert_eq!(decimal_str("0.5"), bank.revenue().unwrap());
+    let destination = bank.account(2).unwrap().unwrap();
+    assert_eq!(&Decimal::ZERO, destination.total());
+    assert!(destination.locked());
+
+    drop_schema(&params, schema);
+}
+
+#[test]
+fn test_overdraft_limits() {
+    let params = match params() {
+        Some(params) => params,
+        None => return,
+    };
+    let schema = "payments_test_overdraft_limits";
+    reset(&params, schema);
+
+    let limits = OverdraftLimits::new().with_limit(1, decimal_str("10"));
+    let mut bank = postgres_bank(&params, schema).with_overdraft_limits(limits);
+    bank.transact(Transaction::new_deposit(1, 1, decimal_str("5")))
+        .unwrap();
+    bank.transact(Transaction::new_withdrawal(1, 2, decimal_str("12")))
+        .unwrap();
+    drop(bank);
+
+    /* The limit is kept with the account */
+    let mut bank = postgres_bank(&params, schema);
+    assert_eq!(
+        &decimal_str("7"),
+        bank.account(1).unwrap().unwrap().overdraft()
+    );
+    bank.transact(Transaction::new_withdrawal(1, 3, decimal_str("3")))
+        .unwrap();
+    let err = bank
+        .transact(Transaction::new_withdrawal(1, 4, decimal_str("0.0001")))
+        .unwrap_err();
+    assert_eq!(&TransactionErrorKind::NegativeBalance, err.kind());
+    assert_eq!(
+        &decimal_str("-10"),
+        bank.account(1).unwrap().unwrap().available()
+    );
+
+    drop_schema(&params, schema);
+}
+
+#[test]
+fn test_concurrent_deposits() {
+    let params = match params() {
+        Some(params) => params,
+        None => return,
+    };
+    let schema = "payments_test_concurrent_deposits";
+    reset(&params, schema);
+
+    let handles: Vec<_> = (0..THREADS)
+        .map(|thread| {
+            let params = params.clone();
+            thread::spawn(move || {
+                let mut bank = postgres_bank(&params, schema);
+                for id in 0..200u32 {
+                    let client_id = (id % 5) as u16 + 1;
+                    let id = id * THREADS as u32 + thread as u32;
+                    bank.transact(Transaction::new_deposit(client_id, id, decimal_str("0.5")))
+                        .unwrap();
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    let bank = postgres_bank(&params, schema);
+    for client_id in 1..=5 {
+        let account = bank.account(client_id).unwrap().unwrap();
+        assert_eq!(&decimal_str("80"), account.total());
+        assert_eq!(&decimal_str("80"), account.available());
+    }
+
+    drop_schema(&params, schema);
+}
+
+#[test]
+fn test_concurrent_workload() {
+    let params = match params() {
+        Some(params) => params,
+        None => return,
+    };
+    let schema = "payments_test_concurrent_workload";
+    reset(&params, schema);
+
+    let mut generator = Generator::new(WorkloadConfig {
+        clients: 20,
+        transactions: 2_000,
+        malformed_rate: 0.01,
+        seed: 11,
+        ..WorkloadConfig::default()
+    });
+    let mut input = Vec::new();
+    generator.write(&mut input).unwrap();
+    let transactions: Vec<Transaction> = trimmed_csv_reader(&input[..])
+        .deserialize()
+        .filter_map(Result::ok)
+        .collect();
+
+    /* Every client is owned by a single process so that its transactions are applied in order */
+    let handles: Vec<_> = (0..THREADS)
+        .map(|thread| {
+            let params = params.clone();
+            let transactions: Vec<Transaction> = transactions
+                .iter()
+                .filter(|transaction| transaction.client_id % THREADS == thread)
+                .cloned()
+                .collect();
+            thread::spawn(move || {
+                let mut bank = postgres_bank(&params, schema);
+                for transaction in transactions {
+                    let _ = bank.transact(transaction);
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    let bank = postgres_bank(&params, schema);
+    assert_eq!(generator.expected().count(), bank.accounts().unwrap().len());
+    for expected in generator.expected() {
+        let account = bank.account(expected.client_id).unwrap().unwrap();
+        assert_eq!(&expected.available, account.available());
+        assert_eq!(&expected.held, account.held());
+        assert_eq!(&expected.total, account.total());
+        assert_eq!(expected.locked, account.locked());
+    }
+
+    drop_schema(&params, schema);
+}